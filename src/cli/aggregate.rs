@@ -0,0 +1,80 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use crate::cli::kube::Identity;
+use crate::cli::types::Connection;
+use crate::daemon::aggregate::{flow_ends, FlowEnd};
+use crate::daemon::conntrack::Role;
+
+/// Connections from one source identity to one destination identity, port
+/// and protocol, whatever their ephemeral source ports.
+#[derive(Debug, Clone)]
+pub struct FlowGroup {
+    pub src: String,
+    pub dst: String,
+    pub dst_port: u16,
+    pub proto: String,
+    pub src_class: Option<String>,
+    pub dst_class: Option<String>,
+    pub connections: usize,
+    pub bytes: u64,
+    pub throughput_bytes_per_sec: u64,
+    pub src_ips: BTreeSet<String>,
+    pub dst_ips: BTreeSet<String>,
+    pub states: BTreeMap<String, usize>,
+}
+
+/// Names one end of a group: the Kubernetes name behind the address, the
+/// local pod as `pod:<uid prefix>`, or the address itself.
+fn label(end: &FlowEnd, identities: &HashMap<String, Identity>) -> String {
+    match end {
+        FlowEnd::Pod { pod_uid } => format!("pod:{}", pod_uid.get(..8).unwrap_or(pod_uid)),
+        FlowEnd::Ip { ip } => {
+            let ip = ip.to_string();
+            identities.get(&ip).map(Identity::label).unwrap_or(ip)
+        }
+    }
+}
+
+/// Collapses connections into `(source) -> (destination, port, protocol)`
+/// groups, busiest first. Groups are keyed exactly like the daemon's
+/// `/v1/aggregates` rows; Kubernetes names only label them.
+pub fn group_flows(conns: &[Connection], identities: &HashMap<String, Identity>) -> Vec<FlowGroup> {
+    let mut groups: HashMap<(FlowEnd, FlowEnd, u16, String), FlowGroup> = HashMap::new();
+    for c in conns {
+        let (Ok(src_ip), Ok(dst_ip)) = (c.src_ip.parse(), c.dst_ip.parse()) else { continue };
+        let role = match c.role.as_deref() {
+            Some("client") => Some(Role::Client),
+            Some("server") => Some(Role::Server),
+            _ => None,
+        };
+        let (src, dst) = flow_ends(src_ip, dst_ip, c.pod_uid.as_deref(), role);
+        let g = groups.entry((src.clone(), dst.clone(), c.dst_port, c.proto.clone())).or_insert_with(|| FlowGroup {
+            src: label(&src, identities),
+            dst: label(&dst, identities),
+            dst_port: c.dst_port,
+            proto: c.proto.clone(),
+            src_class: c.src_class.clone(),
+            dst_class: c.dst_class.clone(),
+            connections: 0,
+            bytes: 0,
+            throughput_bytes_per_sec: 0,
+            src_ips: BTreeSet::new(),
+            dst_ips: BTreeSet::new(),
+            states: BTreeMap::new(),
+        });
+        g.connections += 1;
+        g.bytes += c.bytes;
+        g.throughput_bytes_per_sec += c.throughput_bytes_per_sec;
+        g.src_ips.insert(c.src_ip.clone());
+        g.dst_ips.insert(c.dst_ip.clone());
+        *g.states.entry(c.state.clone()).or_default() += 1;
+    }
+    let mut out: Vec<FlowGroup> = groups.into_values().collect();
+    out.sort_by(|a, b| {
+        b.throughput_bytes_per_sec
+            .cmp(&a.throughput_bytes_per_sec)
+            .then(b.connections.cmp(&a.connections))
+            .then_with(|| (&a.src, &a.dst, a.dst_port).cmp(&(&b.src, &b.dst, b.dst_port)))
+    });
+    out
+}
@@ -0,0 +1,291 @@
+use std::collections::{BTreeSet, HashMap};
+use std::net::IpAddr;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::cli::types::Connection;
+use crate::daemon::cidr::Cidr;
+
+/// How the egress report groups destinations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum GroupBy {
+    /// The CIDR map's longest match, else the destination's /24 or /48.
+    Cidr,
+    /// Autonomous system, from the ASN database.
+    Asn,
+}
+
+/// Settings shared by `kflow egress` and the TUI's egress view.
+pub struct EgressOptions {
+    /// `(network, label)` pairs; the longest matching prefix wins.
+    pub cidr_map: Vec<(Cidr, String)>,
+    /// A MaxMind-format ASN database such as GeoLite2-ASN.mmdb.
+    pub asn_db: Option<maxminddb::Reader<Vec<u8>>>,
+    pub group_by: GroupBy,
+    /// Count destinations classed `private` as well as `public`.
+    pub include_private: bool,
+}
+
+impl Default for EgressOptions {
+    fn default() -> Self {
+        EgressOptions { cidr_map: Vec::new(), asn_db: None, group_by: GroupBy::Cidr, include_private: false }
+    }
+}
+
+impl EgressOptions {
+    pub fn load(cidr_map: Option<&str>, asn_db: Option<&str>, group_by: GroupBy, include_private: bool) -> anyhow::Result<Self> {
+        let cidr_map = cidr_map.map(|p| load_cidr_map(Path::new(p))).transpose()?.unwrap_or_default();
+        let asn_db = asn_db
+            .map(|p| maxminddb::Reader::open_readfile(p).map_err(|e| anyhow::anyhow!("cannot open ASN database {}: {}", p, e)))
+            .transpose()?;
+        if group_by == GroupBy::Asn && asn_db.is_none() {
+            anyhow::bail!("grouping by ASN needs --asn-db");
+        }
+        Ok(EgressOptions { cidr_map, asn_db, group_by, include_private })
+    }
+
+    fn asn(&self, ip: IpAddr) -> Option<(u32, Option<String>)> {
+        let asn: maxminddb::geoip2::Asn = self.asn_db.as_ref()?.lookup(ip).ok()?;
+        Some((asn.autonomous_system_number?, asn.autonomous_system_organization.map(str::to_string)))
+    }
+}
+
+/// External destinations sharing a network or an autonomous system, summed
+/// over every node.
+#[derive(Debug, Clone, Serialize)]
+pub struct EgressGroup {
+    /// The CIDR map label, the network (`203.0.113.0/24`) or `AS<n>`.
+    pub group: String,
+    /// The matched network; absent when grouping by ASN.
+    pub cidr: Option<String>,
+    pub asn: Option<u32>,
+    pub org: Option<String>,
+    pub connections: usize,
+    pub bytes: u64,
+    pub throughput_bytes_per_sec: u64,
+    /// Distinct destination addresses.
+    pub destinations: usize,
+    pub nodes: Vec<String>,
+    /// Busiest destination ports, at most five.
+    pub top_ports: Vec<u16>,
+}
+
+#[derive(Default)]
+struct Accumulator {
+    cidr: Option<String>,
+    asn: Option<u32>,
+    org: Option<String>,
+    connections: usize,
+    bytes: u64,
+    throughput: u64,
+    destinations: BTreeSet<String>,
+    nodes: BTreeSet<String>,
+    ports: HashMap<u16, u64>,
+}
+
+/// Whether a connection leaves the cluster: its destination is classed
+/// `public`, or `private` when `include_private` is set.
+pub fn is_egress(c: &Connection, include_private: bool) -> bool {
+    match c.dst_class.as_deref() {
+        Some("public") => true,
+        Some("private") => include_private,
+        _ => false,
+    }
+}
+
+/// Groups every node's egress connections, busiest group first.
+pub fn egress_report(nodes: &HashMap<String, Vec<Connection>>, opts: &EgressOptions) -> Vec<EgressGroup> {
+    let mut groups: HashMap<String, Accumulator> = HashMap::new();
+    for (node, conns) in nodes {
+        for c in conns.iter().filter(|c| is_egress(c, opts.include_private)) {
+            let Ok(ip) = c.dst_ip.parse::<IpAddr>() else { continue };
+            let asn = opts.asn(ip);
+            let (key, cidr) = match opts.group_by {
+                GroupBy::Asn => match &asn {
+                    Some((n, _)) => (format!("AS{}", n), None),
+                    None => ("unknown".to_string(), None),
+                },
+                GroupBy::Cidr => match lookup_cidr_map(&opts.cidr_map, &ip) {
+                    Some((net, label)) => (label.to_string(), Some(net.to_string())),
+                    None => {
+                        let net = default_network(ip).to_string();
+                        (net.clone(), Some(net))
+                    }
+                },
+            };
+            let acc = groups.entry(key).or_insert_with(|| Accumulator { cidr, ..Default::default() });
+            if acc.asn.is_none()
+                && let Some((n, org)) = asn
+            {
+                acc.asn = Some(n);
+                acc.org = org;
+            }
+            acc.connections += 1;
+            acc.bytes += c.bytes;
+            acc.throughput += c.throughput_bytes_per_sec;
+            acc.destinations.insert(c.dst_ip.clone());
+            acc.nodes.insert(node.clone());
+            *acc.ports.entry(c.dst_port).or_default() += c.bytes.max(1);
+        }
+    }
+
+    let mut out: Vec<EgressGroup> = groups
+        .into_iter()
+        .map(|(group, acc)| {
+            let mut ports: Vec<(u16, u64)> = acc.ports.into_iter().collect();
+            ports.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+            EgressGroup {
+                group,
+                cidr: acc.cidr,
+                asn: acc.asn,
+                org: acc.org,
+                connections: acc.connections,
+                bytes: acc.bytes,
+                throughput_bytes_per_sec: acc.throughput,
+                destinations: acc.destinations.len(),
+                nodes: acc.nodes.into_iter().collect(),
+                top_ports: ports.into_iter().take(5).map(|(p, _)| p).collect(),
+            }
+        })
+        .collect();
+    out.sort_by(|a, b| {
+        b.throughput_bytes_per_sec
+            .cmp(&a.throughput_bytes_per_sec)
+            .then(b.bytes.cmp(&a.bytes))
+            .then(b.connections.cmp(&a.connections))
+            .then(a.group.cmp(&b.group))
+    });
+    out
+}
+
+/// The destination's /24 (IPv4) or /48 (IPv6).
+fn default_network(ip: IpAddr) -> Cidr {
+    match ip {
+        IpAddr::V4(v4) => Cidr { addr: IpAddr::V4((u32::from(v4) & 0xffff_ff00).into()), prefix: 24 },
+        IpAddr::V6(v6) => Cidr { addr: IpAddr::V6((u128::from(v6) & !((1u128 << 80) - 1)).into()), prefix: 48 },
+    }
+}
+
+fn lookup_cidr_map<'a>(map: &'a [(Cidr, String)], ip: &IpAddr) -> Option<(&'a Cidr, &'a str)> {
+    map.iter()
+        .filter(|(net, _)| net.contains(ip))
+        .max_by_key(|(net, _)| net.prefix)
+        .map(|(net, label)| (net, label.as_str()))
+}
+
+/// Reads a CIDR map: one `<cidr> <label>` per line, `#` starts a comment.
+/// The label may contain spaces.
+pub fn load_cidr_map(path: &Path) -> anyhow::Result<Vec<(Cidr, String)>> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("cannot read CIDR map {}: {}", path.display(), e))?;
+    let mut out = Vec::new();
+    for (n, line) in content.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (cidr, label) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let cidr: Cidr = cidr.parse().map_err(|e| anyhow::anyhow!("{}:{}: {}", path.display(), n + 1, e))?;
+        let label = label.trim();
+        out.push((cidr, if label.is_empty() { cidr.to_string() } else { label.to_string() }));
+    }
+    Ok(out)
+}
+
+/// Prints the report as a table, or one JSON array with `json`.
+pub fn print_report(groups: &[EgressGroup], json: bool) -> anyhow::Result<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(groups)?);
+        return Ok(());
+    }
+    println!("{:<28} {:<10} {:<28} {:>6} {:>6} {:>14} {:>12} {:<16} NODES",
+        "GROUP", "ASN", "ORG", "CONNS", "DSTS", "BYTES", "BYTES/S", "PORTS");
+    for g in groups {
+        let ports: Vec<String> = g.top_ports.iter().map(u16::to_string).collect();
+        println!("{:<28} {:<10} {:<28} {:>6} {:>6} {:>14} {:>12} {:<16} {}",
+            g.group,
+            g.asn.map(|n| format!("AS{}", n)).unwrap_or_else(|| "-".to_string()),
+            g.org.as_deref().unwrap_or("-"),
+            g.connections,
+            g.destinations,
+            g.bytes,
+            g.throughput_bytes_per_sec,
+            ports.join(","),
+            g.nodes.join(","),
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    fn conn(dst: &str, class: &str, port: u16, bytes: u64) -> Connection {
+        serde_json::from_value(json!({
+            "proto": "tcp", "src_ip": "10.244.1.5", "src_port": 40000, "dst_ip": dst, "dst_port": port,
+            "state": "ESTABLISHED", "bytes": bytes, "dst_class": class,
+        }))
+        .unwrap()
+    }
+
+    fn map() -> Vec<(Cidr, String)> {
+        [("0.0.0.0/0", "internet"), ("203.0.113.0/24", "partner"), ("203.0.113.7/32", "partner api"), ("2001:db8::/32", "partner v6")]
+            .into_iter()
+            .map(|(c, l)| (c.parse().unwrap(), l.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn longest_prefix_wins() {
+        let map = map();
+        let label = |addr: &str| lookup_cidr_map(&map, &ip(addr)).map(|(_, l)| l);
+        assert_eq!(label("203.0.113.7"), Some("partner api"));
+        assert_eq!(label("203.0.113.8"), Some("partner"));
+        assert_eq!(label("198.51.100.1"), Some("internet"));
+        assert_eq!(label("2001:db8:1::1"), Some("partner v6"));
+        assert_eq!(label("2606:4700::1111"), None);
+    }
+
+    #[test]
+    fn default_networks() {
+        assert_eq!(default_network(ip("198.51.100.77")).to_string(), "198.51.100.0/24");
+        assert_eq!(default_network(ip("2606:4700:10::6814:1")).to_string(), "2606:4700:10::/48");
+    }
+
+    #[test]
+    fn groups_egress_by_cidr() {
+        let nodes = HashMap::from([
+            ("node-a".to_string(), vec![
+                conn("203.0.113.7", "public", 443, 500),
+                conn("203.0.113.9", "public", 443, 300),
+                conn("198.51.100.1", "public", 80, 100),
+                conn("10.96.0.1", "service", 443, 10_000),
+                conn("192.168.5.5", "private", 5432, 50),
+            ]),
+            ("node-b".to_string(), vec![conn("203.0.113.20", "public", 8443, 200)]),
+        ]);
+        let opts = EgressOptions { cidr_map: map()[1..].to_vec(), ..Default::default() };
+        let groups = egress_report(&nodes, &opts);
+        let summary: Vec<(&str, Option<&str>, usize, u64, usize)> = groups
+            .iter()
+            .map(|g| (g.group.as_str(), g.cidr.as_deref(), g.connections, g.bytes, g.destinations))
+            .collect();
+        assert_eq!(summary, [
+            ("partner", Some("203.0.113.0/24"), 2, 500, 2),
+            ("partner api", Some("203.0.113.7/32"), 1, 500, 1),
+            ("198.51.100.0/24", Some("198.51.100.0/24"), 1, 100, 1),
+        ]);
+        assert_eq!(groups[0].nodes, ["node-a", "node-b"]);
+        assert_eq!(groups[0].top_ports, [443, 8443]);
+
+        let opts = EgressOptions { include_private: true, ..opts };
+        assert!(egress_report(&nodes, &opts).iter().any(|g| g.group == "192.168.5.0/24"));
+    }
+}
@@ -1,10 +1,24 @@
-use crate::cli::types::ConnectionsResponse;
+use crate::cli::types::{ConnectionsResponse, DeleteResponse, IpvsResponse, NodeEndpoint, NodeStatus, VersionResponse};
 use reqwest;
+use serde::de::DeserializeOwned;
 use std::process::Stdio;
-use tokio::process::Command;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::{Child, Command};
 use tokio::time::Duration;
 
-pub async fn fetch_via_portforward(pod: &str, local_port: u16) -> anyhow::Result<ConnectionsResponse> {
+/// The daemon API version this CLI speaks.
+pub const API_VERSION: &str = "v1";
+
+/// Per-node details beyond connections, stats and expectations. They are
+/// only fetched while the TUI shows them.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Extras {
+    pub info: bool,
+    pub listeners: bool,
+    pub ipvs: bool,
+}
+
+pub async fn fetch_via_portforward(pod: &str, local_port: u16, extras: Extras) -> anyhow::Result<(ConnectionsResponse, NodeStatus)> {
     let mut child = Command::new("kubectl")
         .args(["port-forward", &format!("pod/{pod}"), &format!("{local_port}:8080")])
         .stdout(Stdio::null())
@@ -13,10 +27,10 @@ pub async fn fetch_via_portforward(pod: &str, local_port: u16) -> anyhow::Result
 
     tokio::time::sleep(Duration::from_millis(300)).await;
 
-    let url = format!("http://127.0.0.1:{}/connections", local_port);
+    let base = format!("http://127.0.0.1:{}", local_port);
     let mut last_err = None;
     for _ in 0..6 {
-        match fetch_url(&url).await {
+        match fetch_node(&base, extras).await {
             Ok(r) => {
                 let _ = child.kill().await;
                 return Ok(r);
@@ -32,12 +46,153 @@ pub async fn fetch_via_portforward(pod: &str, local_port: u16) -> anyhow::Result
     Err(anyhow::anyhow!("failed to fetch {}: {:?}", pod, last_err))
 }
 
-pub async fn fetch_url(url: &str) -> anyhow::Result<ConnectionsResponse> {
+/// Negotiates the API version with the daemon at `base` and fetches its
+/// connections, plus whichever `extras` are asked for. An incompatible daemon
+/// is not an error: it is reported in the returned status (with no
+/// connections) so the TUI can say why.
+pub async fn fetch_node(base: &str, extras: Extras) -> anyhow::Result<(ConnectionsResponse, NodeStatus)> {
+    let client = reqwest::Client::new();
+    let resp = client.get(format!("{}/version", base)).send().await?;
+
+    if resp.status() == reqwest::StatusCode::NOT_FOUND {
+        // Daemons built before the versioned API only serve /connections.
+        let conns: ConnectionsResponse = fetch_url(&format!("{}/connections", base)).await?;
+        let status = NodeStatus {
+            accounting: conns.accounting,
+            api_warning: Some("daemon predates the versioned API; upgrade it to match this CLI".into()),
+            ..Default::default()
+        };
+        return Ok((conns, status));
+    }
+    if !resp.status().is_success() {
+        anyhow::bail!("status {} from /version", resp.status());
+    }
+
+    let version: VersionResponse = resp.json().await
+        .map_err(|e| anyhow::anyhow!("unreadable /version response: {}", e))?;
+    let mut status = NodeStatus {
+        daemon_version: Some(version.daemon_version.clone()),
+        ..Default::default()
+    };
+
+    if !version.api_versions.iter().any(|v| v == API_VERSION) {
+        status.api_warning = Some(format!(
+            "incompatible daemon {} (serves API {}, CLI needs {})",
+            version.daemon_version,
+            version.api_versions.join(","),
+            API_VERSION,
+        ));
+        let empty = ConnectionsResponse { node_name: version.node_name, accounting: Default::default(), connections: Vec::new() };
+        return Ok((empty, status));
+    }
+
+    let conns: ConnectionsResponse = fetch_url(&format!("{}/{}/connections", base, API_VERSION)).await
+        .map_err(|e| anyhow::anyhow!("daemon {} sent a response this CLI cannot read: {}", version.daemon_version, e))?;
+    status.accounting = conns.accounting;
+    status.stats = fetch_url(&format!("{}/{}/stats", base, API_VERSION)).await.ok();
+    status.expectations = fetch_url(&format!("{}/{}/expectations", base, API_VERSION)).await.unwrap_or_default();
+    // Info is best-effort: a daemon without it still has useful connections.
+    // IPVS needs it to tell whether the daemon serves `/ipvs`.
+    if extras.info || extras.ipvs {
+        status.info = fetch_url(&format!("{}/{}/info", base, API_VERSION)).await.ok();
+    }
+    if extras.listeners {
+        status.listeners = fetch_url(&format!("{}/{}/listeners", base, API_VERSION)).await.unwrap_or_default();
+    }
+    if extras.ipvs && status.info.as_ref().is_some_and(|i| i.capabilities.iter().any(|c| c == "ipvs")) {
+        status.ipvs = fetch_url::<IpvsResponse>(&format!("{}/{}/ipvs", base, API_VERSION)).await
+            .map(|r| r.virtual_services)
+            .unwrap_or_default();
+    }
+    Ok((conns, status))
+}
+
+pub async fn fetch_url<T: DeserializeOwned>(url: &str) -> anyhow::Result<T> {
     let client = reqwest::Client::new();
     let resp = client.get(url).send().await?;
     if !resp.status().is_success() {
         anyhow::bail!("status {}", resp.status());
     }
-    let v: ConnectionsResponse = resp.json().await?;
+    let v: T = resp.json().await?;
     Ok(v)
 }
+
+/// Credentials for the daemon's opt-in admin API.
+#[derive(Clone, Debug)]
+pub struct AdminAccess {
+    pub token: String,
+    /// Sent as `X-Kflow-User` for the daemon's audit log.
+    pub user: Option<String>,
+}
+
+/// Asks the daemon behind `endpoint` to delete (or, with `dry_run`, just
+/// match) the conntrack entry for connection `id`.
+pub async fn delete_connection(endpoint: &NodeEndpoint, access: &AdminAccess, id: &str, dry_run: bool) -> anyhow::Result<DeleteResponse> {
+    let (base, child) = match endpoint {
+        NodeEndpoint::Url(url) => (url.clone(), None),
+        NodeEndpoint::Pod(pod) => {
+            let (child, port) = start_port_forward(pod).await?;
+            (format!("http://127.0.0.1:{}", port), Some(child))
+        }
+    };
+
+    let mut req = reqwest::Client::new()
+        .delete(format!("{}/{}/connections/{}", base, API_VERSION, id))
+        .query(&[("dry_run", dry_run)])
+        .bearer_auth(&access.token);
+    if let Some(user) = &access.user {
+        req = req.header("X-Kflow-User", user);
+    }
+    let result = req.send().await;
+    if let Some(mut child) = child {
+        let _ = child.kill().await;
+    }
+
+    let resp = result?;
+    let status = resp.status();
+    if status.is_success() || status == reqwest::StatusCode::INTERNAL_SERVER_ERROR {
+        if let Ok(body) = resp.json::<DeleteResponse>().await {
+            return Ok(body);
+        }
+        anyhow::bail!("status {} with an unreadable body", status);
+    }
+    let msg = resp.text().await.unwrap_or_default();
+    anyhow::bail!("status {}: {}", status, msg.trim())
+}
+
+/// Port-forwards a free local port to the daemon pod, returning the
+/// kubectl process and the port it chose.
+async fn start_port_forward(pod: &str) -> anyhow::Result<(Child, u16)> {
+    let mut child = Command::new("kubectl")
+        .args(["port-forward", &format!("pod/{pod}"), ":8080"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    let stdout = child.stdout.take().ok_or_else(|| anyhow::anyhow!("no stdout from kubectl port-forward"))?;
+    let mut lines = BufReader::new(stdout).lines();
+    // "Forwarding from 127.0.0.1:41234 -> 8080"
+    let port = tokio::time::timeout(Duration::from_secs(10), async {
+        while let Some(line) = lines.next_line().await? {
+            if let Some(port) = line
+                .strip_prefix("Forwarding from 127.0.0.1:")
+                .and_then(|rest| rest.split_whitespace().next())
+                .and_then(|p| p.parse::<u16>().ok())
+            {
+                return Ok(port);
+            }
+        }
+        anyhow::bail!("kubectl port-forward exited before forwarding")
+    })
+    .await;
+    match port {
+        Ok(Ok(port)) => Ok((child, port)),
+        Ok(Err(e)) => {
+            let _ = child.kill().await;
+            Err(e)
+        }
+        Err(_) => {
+            let _ = child.kill().await;
+            anyhow::bail!("timed out waiting for kubectl port-forward to {}", pod)
+        }
+    }
+}
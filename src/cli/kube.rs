@@ -0,0 +1,854 @@
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::Command;
+use tokio::sync::RwLock;
+
+use crate::cli::types::Connection;
+use crate::daemon::cidr::Cidr;
+use crate::daemon::ipclass::{ClusterNetworks, IpClass};
+
+/// What an IP address belongs to in the cluster.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Identity {
+    /// `workload` is the owning controller, e.g. `deployment/web`.
+    Pod { namespace: String, name: String, workload: Option<String>, node: Option<String>, services: Vec<String> },
+    /// A Service's ClusterIP, external IP or load balancer address.
+    Service { namespace: String, name: String },
+    /// An EndpointSlice address that no known pod has, e.g. the backend of
+    /// a Service without a selector.
+    Endpoint { namespace: String, service: String },
+    Node { name: String },
+}
+
+impl Identity {
+    /// Short form for tables: `ns/pod`, `svc:ns/name`, `ep:ns/name`, `node:name`.
+    pub fn label(&self) -> String {
+        match self {
+            Identity::Pod { namespace, name, .. } => format!("{}/{}", namespace, name),
+            Identity::Service { namespace, name } => format!("svc:{}/{}", namespace, name),
+            Identity::Endpoint { namespace, service } => format!("ep:{}/{}", namespace, service),
+            Identity::Node { name } => format!("node:{}", name),
+        }
+    }
+
+    /// One line for the details pane.
+    pub fn describe(&self) -> String {
+        match self {
+            Identity::Pod { namespace, name, workload, node, services } => {
+                let mut s = format!("pod {}/{}", namespace, name);
+                if let Some(w) = workload {
+                    s.push_str(&format!(" of {}", w));
+                }
+                if let Some(node) = node {
+                    s.push_str(&format!(" on {}", node));
+                }
+                if !services.is_empty() {
+                    s.push_str(&format!(" (service {})", services.join(", ")));
+                }
+                s
+            }
+            Identity::Service { namespace, name } => format!("service {}/{}", namespace, name),
+            Identity::Endpoint { namespace, service } => format!("endpoint of service {}/{}", namespace, service),
+            Identity::Node { name } => format!("node {}", name),
+        }
+    }
+}
+
+/// One address behind a Service, from its EndpointSlices.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ServiceEndpoint {
+    pub ip: String,
+    /// `ready` condition; an absent condition counts as ready.
+    pub ready: bool,
+    /// `namespace/name` of the backing pod, from the endpoint's targetRef.
+    pub pod: Option<String>,
+}
+
+/// Where a Node runs, from its `topology.kubernetes.io/zone` and
+/// `topology.kubernetes.io/region` labels.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Topology {
+    pub zone: Option<String>,
+    pub region: Option<String>,
+}
+
+/// IP-to-identity index kept current by the watchers, plus the most recent
+/// API error so the TUI can surface it.
+#[derive(Debug, Default)]
+pub struct KubeNames {
+    pub by_ip: HashMap<String, Identity>,
+    /// Endpoints of every Service, keyed by `namespace/name`.
+    pub endpoints: HashMap<String, Vec<ServiceEndpoint>>,
+    /// Pod ranges from the Nodes' `spec.podCIDRs`.
+    pub pod_cidrs: Vec<Cidr>,
+    /// Zone and region of every Node, keyed by node name.
+    pub topology: HashMap<String, Topology>,
+    /// Set once every watched resource has been listed.
+    pub synced: bool,
+    pub last_error: Option<String>,
+}
+
+impl KubeNames {
+    /// `configured` plus what the API says: Node pod CIDRs, and node, Service
+    /// and pod addresses as single hosts (not every CNI fills in podCIDRs).
+    pub fn networks(&self, configured: &ClusterNetworks) -> ClusterNetworks {
+        let mut out = configured.clone();
+        out.pod_cidrs.extend(self.pod_cidrs.iter().copied());
+        for (ip, id) in &self.by_ip {
+            let Ok(addr) = ip.parse::<IpAddr>() else { continue };
+            let set = match id {
+                Identity::Node { .. } => &mut out.node_ips,
+                Identity::Service { .. } => &mut out.service_ips,
+                Identity::Pod { .. } | Identity::Endpoint { .. } => &mut out.pod_ips,
+            };
+            set.insert(addr);
+        }
+        out
+    }
+}
+
+/// Reclassifies both ends of each connection with the CLI's view of the
+/// cluster. A cluster class found by either side wins, since the daemon may
+/// be configured with ranges the CLI can't see and vice versa.
+pub fn refine_classes(conns: &mut [Connection], networks: &ClusterNetworks) {
+    let refine = |ip: &str, daemon: Option<&str>| -> Option<String> {
+        let ours = ip.parse::<IpAddr>().ok().map(|ip| networks.classify(&ip));
+        let theirs = daemon.and_then(IpClass::parse);
+        let class = match (ours, theirs) {
+            (Some(o), _) if o.in_cluster() => o,
+            (_, Some(t)) => t,
+            (o, None) => o?,
+        };
+        Some(class.as_str().to_string())
+    };
+    for c in conns.iter_mut() {
+        c.src_class = refine(&c.src_ip, c.src_class.as_deref());
+        c.dst_class = refine(&c.dst_ip, c.dst_class.as_deref());
+    }
+}
+
+pub type SharedKubeNames = Arc<RwLock<KubeNames>>;
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct ObjectMeta {
+    name: String,
+    namespace: String,
+    labels: HashMap<String, String>,
+    owner_references: Vec<OwnerReference>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct OwnerReference {
+    kind: String,
+    name: String,
+    controller: Option<bool>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct ListMeta {
+    resource_version: String,
+    #[serde(rename = "continue")]
+    continue_token: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct Pod {
+    metadata: ObjectMeta,
+    spec: PodSpec,
+    status: PodStatus,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct PodSpec {
+    node_name: Option<String>,
+    host_network: bool,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct PodStatus {
+    phase: String,
+    #[serde(rename = "podIP")]
+    pod_ip: Option<String>,
+    #[serde(rename = "podIPs")]
+    pod_ips: Vec<IpEntry>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+struct IpEntry {
+    ip: String,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct Service {
+    metadata: ObjectMeta,
+    spec: ServiceSpec,
+    status: ServiceStatus,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct ServiceSpec {
+    #[serde(rename = "clusterIPs")]
+    cluster_ips: Vec<String>,
+    #[serde(rename = "externalIPs")]
+    external_ips: Vec<String>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct ServiceStatus {
+    load_balancer: LoadBalancerStatus,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+struct LoadBalancerStatus {
+    ingress: Vec<IpEntry>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+struct EndpointSlice {
+    metadata: ObjectMeta,
+    endpoints: Vec<SliceEndpoint>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct SliceEndpoint {
+    addresses: Vec<String>,
+    conditions: EndpointConditions,
+    target_ref: Option<ObjectReference>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+struct EndpointConditions {
+    ready: Option<bool>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+struct ObjectReference {
+    kind: String,
+    namespace: String,
+    name: String,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+struct Node {
+    metadata: ObjectMeta,
+    spec: NodeSpec,
+    status: NodeStatus,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct NodeSpec {
+    #[serde(rename = "podCIDRs")]
+    pod_cidrs: Vec<String>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+struct NodeStatus {
+    addresses: Vec<NodeAddress>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+struct NodeAddress {
+    #[serde(rename = "type")]
+    kind: String,
+    address: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct List<T> {
+    #[serde(default)]
+    metadata: ListMeta,
+    #[serde(default = "Vec::new")]
+    items: Vec<T>,
+}
+
+#[derive(Debug, Deserialize)]
+struct WatchEvent {
+    #[serde(rename = "type")]
+    kind: String,
+    object: serde_json::Value,
+}
+
+/// The watched objects, keyed by `namespace/name`.
+#[derive(Default)]
+struct Store {
+    pods: HashMap<String, Pod>,
+    services: HashMap<String, Service>,
+    slices: HashMap<String, EndpointSlice>,
+    nodes: HashMap<String, Node>,
+    /// `PATH` of every resource listed at least once.
+    listed: HashSet<&'static str>,
+}
+
+trait Resource: DeserializeOwned + Send + 'static {
+    const PATH: &'static str;
+    fn meta(&self) -> &ObjectMeta;
+    fn table(store: &mut Store) -> &mut HashMap<String, Self>
+    where
+        Self: Sized;
+    fn key(&self) -> String {
+        format!("{}/{}", self.meta().namespace, self.meta().name)
+    }
+}
+
+impl Resource for Pod {
+    const PATH: &'static str = "/api/v1/pods";
+    fn meta(&self) -> &ObjectMeta { &self.metadata }
+    fn table(store: &mut Store) -> &mut HashMap<String, Self> { &mut store.pods }
+}
+
+impl Resource for Service {
+    const PATH: &'static str = "/api/v1/services";
+    fn meta(&self) -> &ObjectMeta { &self.metadata }
+    fn table(store: &mut Store) -> &mut HashMap<String, Self> { &mut store.services }
+}
+
+impl Resource for EndpointSlice {
+    const PATH: &'static str = "/apis/discovery.k8s.io/v1/endpointslices";
+    fn meta(&self) -> &ObjectMeta { &self.metadata }
+    fn table(store: &mut Store) -> &mut HashMap<String, Self> { &mut store.slices }
+}
+
+impl Resource for Node {
+    const PATH: &'static str = "/api/v1/nodes";
+    fn meta(&self) -> &ObjectMeta { &self.metadata }
+    fn table(store: &mut Store) -> &mut HashMap<String, Self> { &mut store.nodes }
+}
+
+/// Runs `kubectl proxy` on a free port, which takes care of kubeconfig and
+/// authentication, and watches the cluster through it until the proxy dies.
+pub async fn watch_via_kubectl_proxy(names: SharedKubeNames) {
+    let mut child = match Command::new("kubectl")
+        .args(["proxy", "--port=0"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+    {
+        Ok(c) => c,
+        Err(e) => {
+            names.write().await.last_error = Some(format!("kubectl proxy: {}", e));
+            return;
+        }
+    };
+    let Some(stdout) = child.stdout.take() else { return };
+    let mut lines = BufReader::new(stdout).lines();
+    // "Starting to serve on 127.0.0.1:37251"
+    let base = tokio::time::timeout(Duration::from_secs(10), async {
+        while let Ok(Some(line)) = lines.next_line().await {
+            if let Some(addr) = line.strip_prefix("Starting to serve on ") {
+                return Some(format!("http://{}", addr.trim()));
+            }
+        }
+        None
+    })
+    .await
+    .ok()
+    .flatten();
+    let Some(base) = base else {
+        names.write().await.last_error = Some("kubectl proxy did not start".to_string());
+        return;
+    };
+    tokio::select! {
+        _ = watch_cluster(base, names) => {}
+        _ = child.wait() => {}
+    }
+}
+
+/// Lists and watches Pods, Services, EndpointSlices and Nodes on the API
+/// server at `base` (plain HTTP, e.g. a `kubectl proxy` or a test server)
+/// and keeps `names` up to date. Runs forever.
+pub async fn watch_cluster(base: String, names: SharedKubeNames) {
+    let client = reqwest::Client::new();
+    let store = Arc::new(Mutex::new(Store::default()));
+    let dirty = Arc::new(AtomicBool::new(false));
+
+    let indexer = {
+        let (store, dirty, names) = (store.clone(), dirty.clone(), names.clone());
+        async move {
+            let mut tick = tokio::time::interval(Duration::from_secs(1));
+            loop {
+                tick.tick().await;
+                if dirty.swap(false, Ordering::SeqCst) {
+                    let (by_ip, endpoints, pod_cidrs, topology, synced) = {
+                        let store = store.lock().unwrap();
+                        let synced = [Pod::PATH, Service::PATH, EndpointSlice::PATH, Node::PATH].iter().all(|p| store.listed.contains(p));
+                        (index(&store), service_endpoints(&store), node_pod_cidrs(&store), node_topology(&store), synced)
+                    };
+                    let mut n = names.write().await;
+                    n.by_ip = by_ip;
+                    n.endpoints = endpoints;
+                    n.pod_cidrs = pod_cidrs;
+                    n.topology = topology;
+                    n.synced = synced;
+                }
+            }
+        }
+    };
+
+    tokio::join!(
+        indexer,
+        run_watch::<Pod>(&client, &base, &store, &dirty, &names),
+        run_watch::<Service>(&client, &base, &store, &dirty, &names),
+        run_watch::<EndpointSlice>(&client, &base, &store, &dirty, &names),
+        run_watch::<Node>(&client, &base, &store, &dirty, &names),
+    );
+}
+
+/// List, then watch from the list's resource version, resuming after the
+/// server closes the stream and relisting when the version has expired.
+async fn run_watch<R: Resource>(
+    client: &reqwest::Client,
+    base: &str,
+    store: &Mutex<Store>,
+    dirty: &AtomicBool,
+    names: &SharedKubeNames,
+) {
+    loop {
+        let mut version = match list::<R>(client, base, store).await {
+            Ok(v) => v,
+            Err(e) => {
+                names.write().await.last_error = Some(format!("list {}: {}", R::PATH, e));
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                continue;
+            }
+        };
+        dirty.store(true, Ordering::SeqCst);
+        {
+            let mut n = names.write().await;
+            if n.last_error.as_deref().is_some_and(|e| e.contains(R::PATH)) {
+                n.last_error = None;
+            }
+        }
+        loop {
+            match watch::<R>(client, base, store, dirty, &version).await {
+                Ok(v) => {
+                    // A stream that ends without progress shouldn't be retried in a tight loop.
+                    if v == version {
+                        tokio::time::sleep(Duration::from_secs(1)).await;
+                    }
+                    version = v;
+                }
+                Err(e) => {
+                    names.write().await.last_error = Some(format!("watch {}: {}", R::PATH, e));
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    break;
+                }
+            }
+        }
+    }
+}
+
+async fn list<R: Resource>(client: &reqwest::Client, base: &str, store: &Mutex<Store>) -> anyhow::Result<String> {
+    let mut items = HashMap::new();
+    let mut continue_token: Option<String> = None;
+    let version = loop {
+        let mut req = client.get(format!("{}{}", base, R::PATH)).query(&[("limit", "500")]);
+        if let Some(token) = &continue_token {
+            req = req.query(&[("continue", token)]);
+        }
+        let page: List<R> = req.send().await?.error_for_status()?.json().await?;
+        items.extend(page.items.into_iter().map(|o| (o.key(), o)));
+        match page.metadata.continue_token.filter(|t| !t.is_empty()) {
+            Some(token) => continue_token = Some(token),
+            None => break page.metadata.resource_version,
+        }
+    };
+    let mut store = store.lock().unwrap();
+    *R::table(&mut store) = items;
+    store.listed.insert(R::PATH);
+    Ok(version)
+}
+
+/// Applies watch events until the server ends the stream, returning the last
+/// resource version seen. An expired version (410 Gone) is an error, so the
+/// caller relists.
+async fn watch<R: Resource>(
+    client: &reqwest::Client,
+    base: &str,
+    store: &Mutex<Store>,
+    dirty: &AtomicBool,
+    version: &str,
+) -> anyhow::Result<String> {
+    let mut resp = client
+        .get(format!("{}{}", base, R::PATH))
+        .query(&[("watch", "1"), ("allowWatchBookmarks", "true"), ("timeoutSeconds", "300"), ("resourceVersion", version)])
+        .send()
+        .await?
+        .error_for_status()?;
+
+    let mut version = version.to_string();
+    let mut buf: Vec<u8> = Vec::new();
+    while let Some(chunk) = resp.chunk().await? {
+        buf.extend_from_slice(&chunk);
+        while let Some(pos) = buf.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = buf.drain(..=pos).collect();
+            if line.iter().all(|b| b.is_ascii_whitespace()) {
+                continue;
+            }
+            let event: WatchEvent = serde_json::from_slice(&line)?;
+            if let Some(v) = event.object.pointer("/metadata/resourceVersion").and_then(|v| v.as_str()) {
+                version = v.to_string();
+            }
+            match event.kind.as_str() {
+                "ADDED" | "MODIFIED" => {
+                    let obj: R = serde_json::from_value(event.object)?;
+                    R::table(&mut store.lock().unwrap()).insert(obj.key(), obj);
+                    dirty.store(true, Ordering::SeqCst);
+                }
+                "DELETED" => {
+                    let obj: R = serde_json::from_value(event.object)?;
+                    R::table(&mut store.lock().unwrap()).remove(&obj.key());
+                    dirty.store(true, Ordering::SeqCst);
+                }
+                "ERROR" => {
+                    let message = event.object.get("message").and_then(|m| m.as_str()).unwrap_or("watch error");
+                    anyhow::bail!("{}", message);
+                }
+                // BOOKMARK only moves the resource version on.
+                _ => {}
+            }
+        }
+    }
+    Ok(version)
+}
+
+/// Collects each Service's endpoints across its EndpointSlices. An address
+/// listed by several slices (e.g. during a rollout) is kept once, ready if
+/// any slice says so.
+fn service_endpoints(store: &Store) -> HashMap<String, Vec<ServiceEndpoint>> {
+    let mut out: HashMap<String, Vec<ServiceEndpoint>> = HashMap::new();
+    for slice in store.slices.values() {
+        let Some(service) = slice.metadata.labels.get("kubernetes.io/service-name") else { continue };
+        let endpoints = out.entry(format!("{}/{}", slice.metadata.namespace, service)).or_default();
+        for ep in &slice.endpoints {
+            let ready = ep.conditions.ready.unwrap_or(true);
+            let pod = ep.target_ref.as_ref()
+                .filter(|r| r.kind == "Pod")
+                .map(|r| format!("{}/{}", if r.namespace.is_empty() { &slice.metadata.namespace } else { &r.namespace }, r.name));
+            for ip in &ep.addresses {
+                match endpoints.iter_mut().find(|e| &e.ip == ip) {
+                    Some(existing) => existing.ready |= ready,
+                    None => endpoints.push(ServiceEndpoint { ip: ip.clone(), ready, pod: pod.clone() }),
+                }
+            }
+        }
+    }
+    for endpoints in out.values_mut() {
+        endpoints.sort_by(|a, b| a.ip.cmp(&b.ip));
+    }
+    out
+}
+
+fn node_pod_cidrs(store: &Store) -> Vec<Cidr> {
+    store.nodes.values().flat_map(|n| &n.spec.pod_cidrs).filter_map(|c| c.parse().ok()).collect()
+}
+
+/// Each Node's zone and region, falling back to the deprecated
+/// `failure-domain.beta.kubernetes.io` labels.
+fn node_topology(store: &Store) -> HashMap<String, Topology> {
+    store.nodes.values().map(|n| {
+        let label = |key: &str| {
+            n.metadata.labels.get(&format!("topology.kubernetes.io/{}", key))
+                .or_else(|| n.metadata.labels.get(&format!("failure-domain.beta.kubernetes.io/{}", key)))
+                .cloned()
+        };
+        (n.metadata.name.clone(), Topology { zone: label("zone"), region: label("region") })
+    }).collect()
+}
+
+/// Waits until the watchers have listed everything once, or `timeout`
+/// passes. Returns whether they did.
+pub async fn wait_until_synced(names: &SharedKubeNames, timeout: Duration) -> bool {
+    let deadline = tokio::time::Instant::now() + timeout;
+    while tokio::time::Instant::now() < deadline {
+        if names.read().await.synced {
+            return true;
+        }
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+    names.read().await.synced
+}
+
+/// The pod's controller as `kind/name`, lowercased. A ReplicaSet named
+/// `<deployment>-<pod-template-hash>` is reported as its Deployment.
+fn workload(meta: &ObjectMeta) -> Option<String> {
+    let owner = meta.owner_references.iter().find(|o| o.controller == Some(true)).or(meta.owner_references.first())?;
+    if owner.kind == "ReplicaSet"
+        && let Some(hash) = meta.labels.get("pod-template-hash")
+        && let Some(deployment) = owner.name.strip_suffix(&format!("-{}", hash))
+    {
+        return Some(format!("deployment/{}", deployment));
+    }
+    Some(format!("{}/{}", owner.kind.to_lowercase(), owner.name))
+}
+
+/// Maps every known IP to what it is. Node addresses come first so that
+/// host-network pods show as their node; Service and EndpointSlice entries
+/// fill in what pods don't cover.
+fn index(store: &Store) -> HashMap<String, Identity> {
+    let mut services_by_ip: HashMap<&str, Vec<String>> = HashMap::new();
+    for slice in store.slices.values() {
+        let Some(service) = slice.metadata.labels.get("kubernetes.io/service-name") else { continue };
+        for ep in &slice.endpoints {
+            for addr in &ep.addresses {
+                services_by_ip.entry(addr.as_str()).or_default().push(format!("{}/{}", slice.metadata.namespace, service));
+            }
+        }
+    }
+
+    let mut out = HashMap::new();
+    for node in store.nodes.values() {
+        for a in node.status.addresses.iter().filter(|a| a.kind == "InternalIP" || a.kind == "ExternalIP") {
+            out.insert(a.address.clone(), Identity::Node { name: node.metadata.name.clone() });
+        }
+    }
+    for pod in store.pods.values() {
+        // Finished pods keep their IP in status, but it may already be reused.
+        if pod.spec.host_network || pod.status.phase == "Succeeded" || pod.status.phase == "Failed" {
+            continue;
+        }
+        let ips = pod.status.pod_ips.iter().map(|p| p.ip.as_str()).chain(pod.status.pod_ip.as_deref());
+        for ip in ips.filter(|ip| !ip.is_empty()) {
+            let mut services = services_by_ip.get(ip).cloned().unwrap_or_default();
+            services.sort();
+            services.dedup();
+            out.insert(ip.to_string(), Identity::Pod {
+                namespace: pod.metadata.namespace.clone(),
+                name: pod.metadata.name.clone(),
+                workload: workload(&pod.metadata),
+                node: pod.spec.node_name.clone(),
+                services,
+            });
+        }
+    }
+    for svc in store.services.values() {
+        let ips = svc.spec.cluster_ips.iter().chain(&svc.spec.external_ips).map(String::as_str)
+            .chain(svc.status.load_balancer.ingress.iter().map(|i| i.ip.as_str()));
+        for ip in ips.filter(|ip| !ip.is_empty() && *ip != "None") {
+            out.insert(ip.to_string(), Identity::Service { namespace: svc.metadata.namespace.clone(), name: svc.metadata.name.clone() });
+        }
+    }
+    for (ip, services) in services_by_ip {
+        if let Some((namespace, service)) = services.first().and_then(|s| s.split_once('/')) {
+            out.entry(ip.to_string()).or_insert_with(|| Identity::Endpoint { namespace: namespace.to_string(), service: service.to_string() });
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use axum::extract::{Query, State};
+    use axum::http::Uri;
+    use axum::response::{IntoResponse, Response};
+    use axum::{Json, Router};
+    use serde_json::{json, Value};
+    use tokio::sync::Notify;
+
+    use super::*;
+
+    /// One watch response: sent once `gate` (if any) is notified, then the
+    /// stream ends, as when the API server drops the connection.
+    struct WatchScript {
+        gate: Option<Arc<Notify>>,
+        events: Vec<Value>,
+    }
+
+    /// A scripted API server. List requests get the next entry of `lists`
+    /// (the last one repeats); watch requests get the next script, and hang
+    /// like an idle watch once there is none.
+    #[derive(Default)]
+    struct FakeApi {
+        lists: HashMap<String, VecDeque<Value>>,
+        watches: HashMap<String, VecDeque<WatchScript>>,
+        /// `(path, resourceVersion)` of every watch request, in order.
+        watched: Vec<(String, String)>,
+        /// How often each path was listed.
+        listed: HashMap<String, usize>,
+    }
+
+    type Shared = Arc<Mutex<FakeApi>>;
+
+    async fn serve(State(api): State<Shared>, uri: Uri, Query(q): Query<HashMap<String, String>>) -> Response {
+        let path = uri.path().to_string();
+        if q.contains_key("watch") {
+            let script = {
+                let mut api = api.lock().unwrap();
+                api.watched.push((path.clone(), q.get("resourceVersion").cloned().unwrap_or_default()));
+                api.watches.get_mut(&path).and_then(|w| w.pop_front())
+            };
+            let Some(script) = script else { return std::future::pending().await };
+            if let Some(gate) = script.gate {
+                gate.notified().await;
+            }
+            return script.events.iter().map(|e| format!("{}\n", e)).collect::<String>().into_response();
+        }
+        let mut api = api.lock().unwrap();
+        *api.listed.entry(path.clone()).or_default() += 1;
+        let lists = api.lists.entry(path).or_default();
+        let list = if lists.len() > 1 { lists.pop_front() } else { lists.front().cloned() };
+        Json(list.unwrap_or_else(|| json!({ "metadata": { "resourceVersion": "1" }, "items": [] }))).into_response()
+    }
+
+    async fn start(api: Shared) -> String {
+        let app = Router::new().fallback(serve).with_state(api);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        base
+    }
+
+    fn list(version: &str, items: Vec<Value>) -> Value {
+        json!({ "metadata": { "resourceVersion": version }, "items": items })
+    }
+
+    fn event(kind: &str, object: Value) -> Value {
+        json!({ "type": kind, "object": object })
+    }
+
+    fn pod(name: &str, ip: &str, version: &str) -> Value {
+        json!({
+            "metadata": { "name": name, "namespace": "default", "resourceVersion": version },
+            "spec": { "nodeName": "node-a" },
+            "status": { "phase": "Running", "podIP": ip },
+        })
+    }
+
+    async fn wait_for(names: &SharedKubeNames, what: &str, cond: impl Fn(&KubeNames) -> bool) {
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(10);
+        loop {
+            {
+                let n = names.read().await;
+                if cond(&n) {
+                    return;
+                }
+                if tokio::time::Instant::now() > deadline {
+                    panic!("timed out waiting for {}; have {:?}, last error {:?}", what, n.by_ip.keys().collect::<Vec<_>>(), n.last_error);
+                }
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    }
+
+    fn pod_name(n: &KubeNames, ip: &str) -> Option<String> {
+        match n.by_ip.get(ip) {
+            Some(Identity::Pod { name, .. }) => Some(name.clone()),
+            _ => None,
+        }
+    }
+
+    #[tokio::test]
+    async fn watch_applies_events_resumes_after_disconnect_and_relists_when_gone() {
+        let gone = Arc::new(Notify::new());
+        let mut api = FakeApi::default();
+        api.lists.insert(Pod::PATH.into(), VecDeque::from([
+            list("10", vec![pod("a", "10.1.0.5", "10")]),
+            list("20", vec![pod("c", "10.1.0.8", "20")]),
+        ]));
+        api.watches.insert(Pod::PATH.into(), VecDeque::from([
+            WatchScript {
+                gate: None,
+                events: vec![
+                    event("ADDED", pod("b", "10.1.0.6", "11")),
+                    event("MODIFIED", pod("a", "10.1.0.7", "12")),
+                    event("DELETED", pod("b", "10.1.0.6", "13")),
+                ],
+            },
+            WatchScript {
+                gate: Some(gone.clone()),
+                events: vec![event("ERROR", json!({ "kind": "Status", "code": 410, "reason": "Expired", "message": "too old resource version: 13 (15)" }))],
+            },
+        ]));
+        api.lists.insert(Service::PATH.into(), VecDeque::from([list("5", vec![json!({
+            "metadata": { "name": "web", "namespace": "default" },
+            "spec": { "clusterIPs": ["10.96.0.10"] },
+        })])]));
+        api.lists.insert(EndpointSlice::PATH.into(), VecDeque::from([list("5", vec![json!({
+            "metadata": { "name": "web-abc", "namespace": "default", "labels": { "kubernetes.io/service-name": "web" } },
+            "endpoints": [{ "addresses": ["10.1.0.9"], "conditions": { "ready": false } }],
+        })])]));
+        api.lists.insert(Node::PATH.into(), VecDeque::from([list("5", vec![json!({
+            "metadata": { "name": "node-a", "labels": { "topology.kubernetes.io/zone": "zone-1" } },
+            "spec": { "podCIDRs": ["10.1.0.0/24"] },
+            "status": { "addresses": [{ "type": "InternalIP", "address": "192.168.1.1" }] },
+        })])]));
+        let api: Shared = Arc::new(Mutex::new(api));
+        let base = start(api.clone()).await;
+        let names: SharedKubeNames = Default::default();
+        let watcher = tokio::spawn(watch_cluster(base, names.clone()));
+
+        // The first stream's events land, then it ends.
+        wait_for(&names, "the first watch's events", |n| {
+            n.synced && pod_name(n, "10.1.0.7").as_deref() == Some("a") && !n.by_ip.contains_key("10.1.0.6") && !n.by_ip.contains_key("10.1.0.5")
+        })
+        .await;
+        {
+            let n = names.read().await;
+            assert_eq!(n.by_ip.get("10.96.0.10"), Some(&Identity::Service { namespace: "default".into(), name: "web".into() }));
+            assert_eq!(n.by_ip.get("10.1.0.9"), Some(&Identity::Endpoint { namespace: "default".into(), service: "web".into() }));
+            assert_eq!(n.by_ip.get("192.168.1.1"), Some(&Identity::Node { name: "node-a".into() }));
+            assert_eq!(n.endpoints["default/web"], vec![ServiceEndpoint { ip: "10.1.0.9".into(), ready: false, pod: None }]);
+            assert_eq!(n.topology["node-a"].zone.as_deref(), Some("zone-1"));
+            assert_eq!(n.pod_cidrs, vec!["10.1.0.0/24".parse::<Cidr>().unwrap()]);
+            let networks = n.networks(&ClusterNetworks::default());
+            assert_eq!(networks.pod_cidrs, n.pod_cidrs);
+            assert!(networks.service_cidrs.is_empty());
+            assert!(networks.pod_ips.contains(&"10.1.0.9".parse().unwrap()));
+            assert!(networks.service_ips.contains(&"10.96.0.10".parse().unwrap()));
+            assert!(networks.node_ips.contains(&"192.168.1.1".parse().unwrap()));
+        }
+
+        // After the disconnect the watch resumes from the last version seen.
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+        while !api.lock().unwrap().watched.contains(&(Pod::PATH.into(), "13".into())) {
+            assert!(tokio::time::Instant::now() < deadline, "watch not resumed from version 13");
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+
+        // 410 Gone makes it relist, which replaces what the watch built.
+        gone.notify_one();
+        wait_for(&names, "the relist", |n| pod_name(n, "10.1.0.8").as_deref() == Some("c") && !n.by_ip.contains_key("10.1.0.7")).await;
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+        while !api.lock().unwrap().watched.contains(&(Pod::PATH.into(), "20".into())) {
+            assert!(tokio::time::Instant::now() < deadline, "watch not restarted from the relist's version");
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        {
+            let api = api.lock().unwrap();
+            assert_eq!(api.listed[Pod::PATH], 2);
+            assert_eq!(api.listed[Service::PATH], 1);
+            let pod_watches: Vec<&str> = api.watched.iter().filter(|(p, _)| p == Pod::PATH).map(|(_, v)| v.as_str()).collect();
+            assert_eq!(pod_watches, ["10", "13", "20"]);
+        }
+        watcher.abort();
+    }
+}
@@ -185,9 +185,9 @@ fn update_manifest_conntrack(manifest: &str, ct_repl: &str) -> String {
         if trimmed.starts_with("name:") && trimmed.contains("CONNTRACK_PATH") {
             for j in (i+1)..((i+6).min(lines.len())) {
                 let t = lines[j].trim_start();
-                if t.starts_with("value:") {
+                if let Some(rest) = t.strip_prefix("value:") {
                     let indent = &lines[j][..lines[j].find(t).unwrap_or(0)];
-                    let rest = t["value:".len()..].trim_start();
+                    let rest = rest.trim_start();
                     let quote = if rest.starts_with('"') { '"' } else if rest.starts_with('\'') { '\'' } else { '"' };
                     let new_val = if ct_repl == "auto" {
                         format!("value: {}auto{}", quote, quote)
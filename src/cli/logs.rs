@@ -0,0 +1,158 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+
+use crate::cli::types::FlowRecord;
+use crate::daemon::flowlog::rotated_files;
+
+/// Filters for `kflow logs query`. Every filter that is set must match.
+#[derive(Debug, Default)]
+pub struct LogQuery {
+    pub since: Option<u64>,
+    pub until: Option<u64>,
+    pub ip: Option<IpAddr>,
+    pub port: Option<u16>,
+    pub proto: Option<String>,
+    pub state: Option<String>,
+    pub node: Option<String>,
+}
+
+impl LogQuery {
+    fn matches(&self, r: &FlowRecord) -> bool {
+        if let Some(since) = self.since && r.end < since { return false; }
+        if let Some(until) = self.until && r.start > until { return false; }
+        if let Some(ip) = self.ip && ![&r.src_ip, &r.dst_ip].iter().any(|a| a.parse::<IpAddr>().ok() == Some(ip)) {
+            return false;
+        }
+        if let Some(port) = self.port && r.src_port != port && r.dst_port != port { return false; }
+        if let Some(ref proto) = self.proto && !r.proto.eq_ignore_ascii_case(proto) { return false; }
+        if let Some(ref state) = self.state && !r.states.iter().any(|s| s.state.eq_ignore_ascii_case(state)) {
+            return false;
+        }
+        if let Some(ref node) = self.node && r.node_name.as_deref() != Some(node.as_str()) { return false; }
+        true
+    }
+}
+
+/// Reads `path` and its rotated siblings, oldest first, and prints matching
+/// records either as a table or as JSON Lines.
+pub fn run_logs_query(path: &str, query: &LogQuery, json: bool) -> anyhow::Result<()> {
+    let path = Path::new(path);
+    let mut files = rotated_files(path);
+    files.sort_by_key(|(key, _)| *key);
+    let mut files: Vec<PathBuf> = files.into_iter().map(|(_, p)| p).collect();
+    if path.exists() {
+        files.push(path.to_path_buf());
+    }
+    if files.is_empty() {
+        anyhow::bail!("no flow log found at {}", path.display());
+    }
+
+    if !json {
+        println!("{:<20} {:<20} {:>8} {:<5} {:<28} {:<28} {:>12} {:>9} {:<24} NODE",
+            "START", "END", "DURATION", "PROTO", "SRC", "DST", "BYTES", "PACKETS", "STATES");
+    }
+    for file in files {
+        let reader = BufReader::new(File::open(&file)?);
+        for (n, line) in reader.lines().map_while(Result::ok).enumerate() {
+            if line.trim().is_empty() { continue; }
+            let record: FlowRecord = match serde_json::from_str(&line) {
+                Ok(r) => r,
+                Err(e) => {
+                    eprintln!("{}:{}: skipping unreadable record: {}", file.display(), n + 1, e);
+                    continue;
+                }
+            };
+            if !query.matches(&record) { continue; }
+            if json {
+                println!("{}", line);
+            } else {
+                let states: Vec<&str> = record.states.iter().map(|s| s.state.as_str()).collect();
+                println!("{:<20} {:<20} {:>7}s {:<5} {:<28} {:<28} {:>12} {:>9} {:<24} {}",
+                    format_unix_utc(record.start),
+                    format_unix_utc(record.end),
+                    record.end.saturating_sub(record.start),
+                    record.proto,
+                    format!("{}:{}", record.src_ip, record.src_port),
+                    format!("{}:{}", record.dst_ip, record.dst_port),
+                    record.bytes,
+                    record.packets,
+                    states.join(">"),
+                    record.node_name.as_deref().unwrap_or("-"),
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Accepts unix seconds or a relative age such as `30s`, `15m`, `2h`, `7d`.
+pub fn parse_time_arg(s: &str, now: u64) -> anyhow::Result<u64> {
+    let s = s.trim();
+    if let Ok(ts) = s.parse::<u64>() {
+        return Ok(ts);
+    }
+    let Some((at, unit)) = s.char_indices().last() else {
+        anyhow::bail!("invalid time '': use unix seconds or e.g. 15m, 2h, 7d");
+    };
+    let scale = match unit {
+        's' => 1,
+        'm' => 60,
+        'h' => 3600,
+        'd' => 86400,
+        _ => anyhow::bail!("invalid time unit in '{}': use s, m, h or d", s),
+    };
+    let n: u64 = s[..at].parse().map_err(|_| anyhow::anyhow!("invalid time '{}': use unix seconds or e.g. 15m, 2h, 7d", s))?;
+    Ok(now.saturating_sub(n.saturating_mul(scale)))
+}
+
+/// Formats unix seconds as `YYYY-MM-DDTHH:MM:SSZ`.
+pub fn format_unix_utc(ts: u64) -> String {
+    let days = (ts / 86400) as i64;
+    let rem = ts % 86400;
+    // Civil-from-days, after Howard Hinnant's algorithm.
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400 + if m <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", y, m, d, rem / 3600, (rem % 3600) / 60, rem % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_absolute_and_relative_times() {
+        let now = 1_700_000_000;
+        assert_eq!(parse_time_arg("1699990000", now).unwrap(), 1_699_990_000);
+        assert_eq!(parse_time_arg(" 30s ", now).unwrap(), now - 30);
+        assert_eq!(parse_time_arg("15m", now).unwrap(), now - 900);
+        assert_eq!(parse_time_arg("2h", now).unwrap(), now - 7200);
+        assert_eq!(parse_time_arg("7d", now).unwrap(), now - 604_800);
+        assert_eq!(parse_time_arg("99999999999999999d", now).unwrap(), 0);
+        for bad in ["", "5é", "é", "m", "5w", "-5m", "1.5h", "h5"] {
+            assert!(parse_time_arg(bad, now).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn ip_filter_matches_whole_addresses() {
+        let record: FlowRecord = serde_json::from_value(serde_json::json!({
+            "proto": "tcp", "src_ip": "10.0.0.10", "src_port": 40000, "dst_ip": "2001:db8::1", "dst_port": 443,
+            "start": 100, "end": 200, "bytes": 0, "packets": 0, "states": [],
+        }))
+        .unwrap();
+        let by_ip = |ip: &str| LogQuery { ip: Some(ip.parse().unwrap()), ..Default::default() }.matches(&record);
+        assert!(by_ip("10.0.0.10"));
+        assert!(by_ip("2001:db8:0::1"));
+        assert!(!by_ip("10.0.0.1"));
+        assert!(!by_ip("0.0.0.10"));
+    }
+}
@@ -0,0 +1,153 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::cli::kube::{Identity, ServiceEndpoint};
+use crate::cli::types::Connection;
+
+fn leg(c: &Connection) -> Option<&str> {
+    c.mesh.as_ref().map(|m| m.leg.as_str())
+}
+
+/// Addresses behind a Service IP; the sidecar connects to these directly
+/// instead of the ClusterIP the app dialled.
+fn service_endpoints<'a>(
+    ip: &str,
+    identities: &HashMap<String, Identity>,
+    endpoints: &'a HashMap<String, Vec<ServiceEndpoint>>,
+) -> Vec<&'a str> {
+    let eps = match identities.get(ip) {
+        Some(Identity::Service { namespace, name }) => endpoints.get(&format!("{}/{}", namespace, name)),
+        _ => None,
+    };
+    eps.into_iter().flatten().map(|e| e.ip.as_str()).collect()
+}
+
+/// The sidecar hops in a connection list, indexed by what they are matched
+/// on so each redirected connection only looks at its candidates.
+struct Hops<'a> {
+    /// Upstream legs by protocol, pod IP and peer IP.
+    upstream: HashMap<(&'a str, &'a str, &'a str), Vec<usize>>,
+    /// Loopback legs by protocol and app port.
+    loopback: HashMap<(&'a str, u16), Vec<usize>>,
+}
+
+impl<'a> Hops<'a> {
+    fn index(conns: &'a [Connection]) -> Self {
+        let mut hops = Hops { upstream: HashMap::new(), loopback: HashMap::new() };
+        for (i, c) in conns.iter().enumerate() {
+            match leg(c) {
+                Some("upstream") => hops.upstream.entry((c.proto.as_str(), c.src_ip.as_str(), c.dst_ip.as_str())).or_default().push(i),
+                Some("loopback") => hops.loopback.entry((c.proto.as_str(), c.dst_port)).or_default().push(i),
+                _ => {}
+            }
+        }
+        hops
+    }
+
+    /// Indexes of the hops carrying the redirected connection `app`.
+    fn carrying(
+        &self,
+        conns: &[Connection],
+        app: &Connection,
+        identities: &HashMap<String, Identity>,
+        endpoints: &HashMap<String, Vec<ServiceEndpoint>>,
+    ) -> Vec<usize> {
+        let proto = app.proto.as_str();
+        match leg(app) {
+            // The sidecar's connection from the same pod to the destination,
+            // or to any of the Service's endpoints.
+            Some("outbound") => {
+                let direct = self.upstream.get(&(proto, app.src_ip.as_str(), app.dst_ip.as_str())).into_iter().flatten()
+                    .copied()
+                    .filter(|i| conns[*i].dst_port == app.dst_port);
+                let via_endpoints = service_endpoints(&app.dst_ip, identities, endpoints).into_iter()
+                    .filter(|ip| *ip != app.dst_ip)
+                    .flat_map(|ip| self.upstream.get(&(proto, app.src_ip.as_str(), ip)).into_iter().flatten().copied());
+                direct.chain(via_endpoints).collect()
+            }
+            // The sidecar handing the connection to the app on its port.
+            Some("inbound") => self.loopback.get(&(proto, app.dst_port)).into_iter().flatten()
+                .copied()
+                .filter(|i| {
+                    let other = &conns[*i];
+                    (other.dst_ip == app.dst_ip || other.dst_class.as_deref() == Some("loopback"))
+                        && (other.netns.is_none() || app.netns.is_none() || other.netns == app.netns)
+                })
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Folds sidecar hops into the app-facing connection they carry, so each
+/// meshed connection appears once with its true peer. Returns the remaining
+/// connections and, by connection id, the raw legs behind each collapsed
+/// one, redirected leg first. Only legs the daemon marked as sidecar hops
+/// are folded. Proxies pool upstream connections, so one upstream leg may
+/// be listed under several app connections. Legs that match no app
+/// connection stay in the list.
+pub fn collapse(
+    conns: &[Connection],
+    identities: &HashMap<String, Identity>,
+    endpoints: &HashMap<String, Vec<ServiceEndpoint>>,
+) -> (Vec<Connection>, HashMap<String, Vec<Connection>>) {
+    let hops = Hops::index(conns);
+    let mut legs: HashMap<String, Vec<Connection>> = HashMap::new();
+    let mut folded: HashSet<usize> = HashSet::new();
+    for app in conns.iter().filter(|c| c.is_mesh_redirect()) {
+        let mut raw = vec![app.clone()];
+        for i in hops.carrying(conns, app, identities, endpoints) {
+            raw.push(conns[i].clone());
+            folded.insert(i);
+        }
+        legs.insert(app.id.clone(), raw);
+    }
+    let kept = conns.iter()
+        .enumerate()
+        .filter(|(i, _)| !folded.contains(i))
+        .map(|(_, c)| c.clone())
+        .collect();
+    (kept, legs)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn conn(id: &str, src: (&str, u16), dst: (&str, u16), leg: Option<&str>) -> Connection {
+        serde_json::from_value(json!({
+            "id": id,
+            "proto": "tcp",
+            "src_ip": src.0,
+            "src_port": src.1,
+            "dst_ip": dst.0,
+            "dst_port": dst.1,
+            "state": "ESTABLISHED",
+            "mesh": leg.map(|l| json!({ "mesh": "istio", "leg": l })),
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn folds_marked_hops_into_the_connection_they_carry() {
+        let identities = HashMap::from([("10.96.0.10".to_string(), Identity::Service { namespace: "default".into(), name: "web".into() })]);
+        let endpoints = HashMap::from([("default/web".to_string(), vec![ServiceEndpoint { ip: "10.1.0.9".into(), ready: true, pod: None }])]);
+        let conns = vec![
+            conn("out", ("10.1.0.5", 40000), ("10.96.0.10", 80), Some("outbound")),
+            conn("up", ("10.1.0.5", 40001), ("10.1.0.9", 8080), Some("upstream")),
+            // Same pod and endpoint, but not a sidecar hop.
+            conn("plain", ("10.1.0.5", 40002), ("10.1.0.9", 8080), None),
+            conn("in", ("10.1.0.7", 50000), ("10.1.0.5", 9090), Some("inbound")),
+            conn("lo", ("127.0.0.6", 50001), ("10.1.0.5", 9090), Some("loopback")),
+            conn("other-port", ("127.0.0.6", 50002), ("10.1.0.5", 9091), Some("loopback")),
+        ];
+
+        let (kept, legs) = collapse(&conns, &identities, &endpoints);
+        let kept: Vec<&str> = kept.iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(kept, ["out", "plain", "in", "other-port"]);
+        let ids = |id: &str| legs[id].iter().map(|c| c.id.clone()).collect::<Vec<_>>();
+        assert_eq!(ids("out"), ["out", "up"]);
+        assert_eq!(ids("in"), ["in", "lo"]);
+    }
+}
@@ -9,13 +9,24 @@ pub const DEFAULT_DAEMONSET: &str = include_str!(concat!(env!("CARGO_MANIFEST_DI
 
 pub mod types;
 pub mod kubectl;
+pub mod kube;
 pub mod fetch;
+pub mod egress;
+pub mod aggregate;
+pub mod services;
+pub mod zones;
+pub mod mesh;
+pub mod logs;
 pub mod tui;
 
-use types::Connection;
+use types::{Connection, NodeEndpoint, NodeStatus};
+use crate::daemon::cidr::Cidr;
+use crate::daemon::ipclass::ClusterNetworks;
 use kubectl::{run_kubectl_apply, run_kubectl_delete, discover_pods};
-use fetch::{fetch_url, fetch_via_portforward};
+use fetch::{fetch_node, fetch_via_portforward, AdminAccess, Extras};
+use egress::{egress_report, print_report, EgressOptions, GroupBy};
 use tui::run_tui;
+use logs::{parse_time_arg, run_logs_query, LogQuery};
 
 #[derive(Parser, Debug)]
 #[command(name = "kflow-cli")]
@@ -32,6 +43,44 @@ struct Args {
     #[arg(long, default_value_t = 18080)]
     start_port: u16,
 
+    /// Token for the daemons' admin API (also read from KFLOW_ADMIN_TOKEN).
+    /// Enables deleting conntrack entries from the TUI.
+    #[arg(long)]
+    admin_token: Option<String>,
+
+    /// Kubernetes API base URL (plain HTTP, e.g. a running `kubectl proxy`)
+    /// used to name pods, Services and Nodes. In --kube mode the CLI starts
+    /// its own `kubectl proxy` when this is unset.
+    #[arg(long)]
+    kube_api: Option<String>,
+
+    /// Pod CIDRs, comma separated, on top of the Nodes' podCIDRs.
+    #[arg(long, value_delimiter = ',')]
+    pod_cidr: Vec<Cidr>,
+
+    /// Service CIDRs, comma separated; the API doesn't publish them.
+    #[arg(long, value_delimiter = ',')]
+    service_cidr: Vec<Cidr>,
+
+    /// File of `<cidr> <label>` lines naming external networks in the
+    /// egress view; unmatched destinations fall back to their /24 or /48.
+    #[arg(long, global = true)]
+    cidr_map: Option<String>,
+
+    /// MaxMind-format ASN database (e.g. GeoLite2-ASN.mmdb) for the egress
+    /// view's ASN and organisation columns.
+    #[arg(long, global = true)]
+    asn_db: Option<String>,
+
+    /// Group egress destinations by network or by autonomous system.
+    #[arg(long, global = true, value_enum, default_value = "cidr")]
+    group_by: GroupBy,
+
+    /// Count private (RFC 1918, ULA) destinations outside the cluster's
+    /// ranges as egress too.
+    #[arg(long, global = true)]
+    include_private: bool,
+
     #[command(subcommand)]
     cmd: Option<CommandSub>,
 }
@@ -54,6 +103,50 @@ enum CommandSub {
         #[arg(long)]
         conntrack: Option<String>,
     },
+    /// Work with the daemon's on-disk flow log (see KFLOW_FLOW_LOG).
+    Logs {
+        #[command(subcommand)]
+        cmd: LogsSub,
+    },
+    /// Print external destinations across all nodes, grouped by CIDR or ASN.
+    Egress {
+        #[arg(short = 'o', long, default_value = "table")]
+        output: String,
+    },
+    /// Print traffic between availability zones, from the Nodes'
+    /// topology.kubernetes.io labels. Needs --kube or --kube-api.
+    Zones {
+        #[arg(short = 'o', long, default_value = "table")]
+        output: String,
+    },
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum LogsSub {
+    /// Print finished flows from a flow log and its rotated files.
+    Query {
+        #[arg(long, default_value = "/var/log/kflow/flows.jsonl")]
+        file: String,
+        /// Unix seconds or a relative age such as 15m, 2h, 7d.
+        #[arg(long)]
+        since: Option<String>,
+        #[arg(long)]
+        until: Option<String>,
+        /// Match flows from or to this address.
+        #[arg(long)]
+        ip: Option<std::net::IpAddr>,
+        #[arg(long)]
+        port: Option<u16>,
+        #[arg(long)]
+        proto: Option<String>,
+        /// Match flows that passed through this conntrack state.
+        #[arg(long)]
+        state: Option<String>,
+        #[arg(long)]
+        node: Option<String>,
+        #[arg(short = 'o', long, default_value = "table")]
+        output: String,
+    },
 }
 
 pub async fn run_cli() -> anyhow::Result<()> {
@@ -74,10 +167,27 @@ pub async fn run_cli() -> anyhow::Result<()> {
                 run_kubectl_delete(file_ref, namespace.as_deref(), conn_ref).await?;
                 return Ok(());
             }
+            CommandSub::Logs { cmd: LogsSub::Query { file, since, until, ip, port, proto, state, node, output } } => {
+                let now = crate::daemon::unix_now();
+                let query = LogQuery {
+                    since: since.as_deref().map(|s| parse_time_arg(s, now)).transpose()?,
+                    until: until.as_deref().map(|s| parse_time_arg(s, now)).transpose()?,
+                    ip: *ip,
+                    port: *port,
+                    proto: proto.clone(),
+                    state: state.clone(),
+                    node: node.clone(),
+                };
+                run_logs_query(file, &query, output == "json")?;
+                return Ok(());
+            }
+            CommandSub::Egress { .. } | CommandSub::Zones { .. } => {}
         }
     }
+    let egress = EgressOptions::load(args.cidr_map.as_deref(), args.asn_db.as_deref(), args.group_by, args.include_private)?;
 
     let state: Arc<RwLock<HashMap<String, Vec<Connection>>>> = Arc::new(RwLock::new(HashMap::new()));
+    let node_status: Arc<RwLock<HashMap<String, NodeStatus>>> = Arc::new(RwLock::new(HashMap::new()));
 
     let endpoints_list: Vec<String> = if args.local {
         vec!["http://localhost:8080".to_string()]
@@ -89,46 +199,156 @@ pub async fn run_cli() -> anyhow::Result<()> {
 
     let did_fetch_once = Arc::new(AtomicBool::new(false));
     let is_kube_mode = args.kube || (!args.local && args.endpoints.is_none());
+    let kube_names: kube::SharedKubeNames = Default::default();
+    let configured_networks = ClusterNetworks { pod_cidrs: args.pod_cidr.clone(), service_cidrs: args.service_cidr.clone(), ..Default::default() };
+
+    if let Some(CommandSub::Egress { output }) = &args.cmd {
+        // Node pod CIDRs and Service addresses keep cluster traffic out of
+        // the report when the API is reachable; otherwise only the flags do.
+        let watching = spawn_kube_watch(args.kube_api.as_deref(), is_kube_mode, &kube_names);
+        let ((mut map, statuses), synced) = tokio::join!(
+            fetch_all(&endpoints_list, is_kube_mode, args.local, args.start_port, Extras::default()),
+            async { !watching || kube::wait_until_synced(&kube_names, Duration::from_secs(30)).await },
+        );
+        for (node, st) in &statuses {
+            if let Some(e) = &st.fetch_error {
+                eprintln!("{}: {}", node, e);
+            }
+        }
+        let names = kube_names.read().await;
+        if !synced {
+            eprintln!("Kubernetes API not fully listed yet{}; cluster addresses may be reported as egress",
+                names.last_error.as_deref().map(|e| format!(" ({})", e)).unwrap_or_default());
+        }
+        let networks = names.networks(&configured_networks);
+        for conns in map.values_mut() {
+            kube::refine_classes(conns, &networks);
+        }
+        print_report(&egress_report(&map, &egress), output == "json")?;
+        return Ok(());
+    }
+
+    if let Some(CommandSub::Zones { output }) = &args.cmd {
+        if !spawn_kube_watch(args.kube_api.as_deref(), is_kube_mode, &kube_names) {
+            anyhow::bail!("the zones report needs Node labels from the Kubernetes API; use --kube or --kube-api");
+        }
+        let ((map, statuses), synced) = tokio::join!(
+            fetch_all(&endpoints_list, is_kube_mode, args.local, args.start_port, Extras::default()),
+            kube::wait_until_synced(&kube_names, Duration::from_secs(30)),
+        );
+        for (node, st) in &statuses {
+            if let Some(e) = &st.fetch_error {
+                eprintln!("{}: {}", node, e);
+            }
+        }
+        let names = kube_names.read().await;
+        if !synced {
+            eprintln!("Kubernetes API not fully listed yet{}; the report may be incomplete",
+                names.last_error.as_deref().map(|e| format!(" ({})", e)).unwrap_or_default());
+        }
+        zones::print_report(&zones::zone_pairs(&map, &names.by_ip, &names.topology), output == "json")?;
+        return Ok(());
+    }
+
+    // The TUI says which per-node extras it shows; a change refetches at once.
+    let (extras_tx, mut extras_rx) = tokio::sync::watch::channel(Extras::default());
     if !endpoints_list.is_empty() {
         let state_clone = state.clone();
+        let status_clone = node_status.clone();
         let endpoints_clone = endpoints_list.clone();
         let start_port = args.start_port;
         let kube_mode = is_kube_mode;
         let local_mode = args.local;
         let did_fetch = did_fetch_once.clone();
+        let names = kube_names.clone();
         tokio::spawn(async move {
             let mut tick = tokio::time::interval(Duration::from_secs(2));
             loop {
-                tick.tick().await;
-                let mut map = HashMap::new();
-                if kube_mode {
-                    for (i, pod) in endpoints_clone.iter().enumerate() {
-                        let port = start_port + (i as u16);
-                        if let Ok(resp) = fetch_via_portforward(pod, port).await {
-                            let node = resp.node_name.unwrap_or_else(|| pod.clone());
-                            map.insert(node, resp.connections);
-                        }
-                    }
-                } else if local_mode {
-                    if let Ok(resp) = fetch_url("http://localhost:8080/connections").await {
-                        let node = resp.node_name.unwrap_or_else(|| "localhost".to_string());
-                        map.insert(node, resp.connections);
-                    }
-                } else {
-                    for ep in &endpoints_clone {
-                        if let Ok(resp) = fetch_url(&format!("{}/connections", ep)).await {
-                            let node = resp.node_name.unwrap_or_else(|| ep.clone());
-                            map.insert(node, resp.connections);
-                        }
-                    }
+                tokio::select! {
+                    _ = tick.tick() => {}
+                    _ = extras_rx.changed() => {}
+                }
+                let extras = *extras_rx.borrow_and_update();
+                let (mut map, statuses) = fetch_all(&endpoints_clone, kube_mode, local_mode, start_port, extras).await;
+                let networks = names.read().await.networks(&configured_networks);
+                for conns in map.values_mut() {
+                    kube::refine_classes(conns, &networks);
                 }
-                let mut w = state_clone.write().await;
-                *w = map;
+                *state_clone.write().await = map;
+                *status_clone.write().await = statuses;
                 did_fetch.store(true, Ordering::SeqCst);
             }
         });
     }
 
-    run_tui(state, is_kube_mode, did_fetch_once).await?;
+    spawn_kube_watch(args.kube_api.as_deref(), is_kube_mode, &kube_names);
+
+    let admin = args.admin_token.clone()
+        .or_else(|| std::env::var("KFLOW_ADMIN_TOKEN").ok())
+        .filter(|t| !t.is_empty())
+        .map(|token| AdminAccess { token, user: std::env::var("USER").ok() });
+    let feed = tui::Feed { connections: state, statuses: node_status, did_fetch_once, extras: extras_tx };
+    run_tui(feed, is_kube_mode, admin, kube_names, egress).await?;
     Ok(())
 }
+
+/// Starts keeping `names` current from the API server at `kube_api`, or
+/// through a `kubectl proxy` in kube mode. Returns whether it did.
+fn spawn_kube_watch(kube_api: Option<&str>, kube_mode: bool, names: &kube::SharedKubeNames) -> bool {
+    if let Some(base) = kube_api {
+        tokio::spawn(kube::watch_cluster(base.trim_end_matches('/').to_string(), names.clone()));
+    } else if kube_mode {
+        tokio::spawn(kube::watch_via_kubectl_proxy(names.clone()));
+    } else {
+        return false;
+    }
+    true
+}
+
+/// Reads every daemon once, keyed by node name.
+async fn fetch_all(
+    endpoints: &[String],
+    kube_mode: bool,
+    local_mode: bool,
+    start_port: u16,
+    extras: Extras,
+) -> (HashMap<String, Vec<Connection>>, HashMap<String, NodeStatus>) {
+    let mut map = HashMap::new();
+    let mut statuses = HashMap::new();
+    if kube_mode {
+        for (i, pod) in endpoints.iter().enumerate() {
+            let port = start_port + (i as u16);
+            record_fetch(fetch_via_portforward(pod, port, extras).await, pod, NodeEndpoint::Pod(pod.clone()), &mut map, &mut statuses);
+        }
+    } else if local_mode {
+        let base = "http://localhost:8080";
+        record_fetch(fetch_node(base, extras).await, "localhost", NodeEndpoint::Url(base.to_string()), &mut map, &mut statuses);
+    } else {
+        for ep in endpoints {
+            record_fetch(fetch_node(ep, extras).await, ep, NodeEndpoint::Url(ep.clone()), &mut map, &mut statuses);
+        }
+    }
+    (map, statuses)
+}
+
+/// Files one daemon's fetch result under its node name, or under `fallback`
+/// (the pod or endpoint) when the daemon couldn't be read at all.
+fn record_fetch(
+    result: anyhow::Result<(types::ConnectionsResponse, NodeStatus)>,
+    fallback: &str,
+    endpoint: NodeEndpoint,
+    map: &mut HashMap<String, Vec<Connection>>,
+    statuses: &mut HashMap<String, NodeStatus>,
+) {
+    match result {
+        Ok((resp, mut status)) => {
+            status.endpoint = Some(endpoint);
+            let node = resp.node_name.unwrap_or_else(|| fallback.to_string());
+            statuses.insert(node.clone(), status);
+            map.insert(node, resp.connections);
+        }
+        Err(e) => {
+            statuses.insert(fallback.to_string(), NodeStatus { fetch_error: Some(e.to_string()), endpoint: Some(endpoint), ..Default::default() });
+        }
+    }
+}
@@ -0,0 +1,207 @@
+use std::collections::HashMap;
+
+use crate::cli::kube::{Identity, KubeNames};
+use crate::cli::types::{Connection, NodeStatus};
+
+/// How a backend relates to its Service's current endpoints.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackendStatus {
+    Ready,
+    /// Listed, but its `ready` condition is false.
+    NotReady,
+    /// Receiving connections but no longer an endpoint: conntrack entries
+    /// that outlived a pod or an endpoint change.
+    Stale,
+    /// An endpoint with no connections in view.
+    Idle,
+    /// The Service's endpoints aren't known (no Kubernetes data, or a
+    /// NodePort or hostPort that isn't a Service address).
+    Unknown,
+}
+
+impl BackendStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BackendStatus::Ready => "ready",
+            BackendStatus::NotReady => "not ready",
+            BackendStatus::Stale => "STALE",
+            BackendStatus::Idle => "idle",
+            BackendStatus::Unknown => "-",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BackendLoad {
+    pub ip: String,
+    /// Port connections were translated to; `None` for idle endpoints.
+    pub port: Option<u16>,
+    /// Kubernetes name of the backend, when known.
+    pub name: Option<String>,
+    pub status: BackendStatus,
+    pub connections: usize,
+    pub bytes: u64,
+    pub throughput_bytes_per_sec: u64,
+    /// IPVS `ActiveConn` and `InActConn`, summed over nodes, when the
+    /// frontend is an IPVS virtual service.
+    pub ipvs_active: Option<u64>,
+    pub ipvs_inactive: Option<u64>,
+}
+
+impl BackendLoad {
+    fn new(ip: &str, port: Option<u16>, name: Option<String>, status: BackendStatus) -> Self {
+        BackendLoad {
+            ip: ip.to_string(),
+            port,
+            name,
+            status,
+            connections: 0,
+            bytes: 0,
+            throughput_bytes_per_sec: 0,
+            ipvs_active: None,
+            ipvs_inactive: None,
+        }
+    }
+}
+
+/// Connections to one translated frontend (a ClusterIP, NodePort or other
+/// DNATed address and port) and how they spread over its backends.
+#[derive(Debug, Clone)]
+pub struct ServiceLoad {
+    pub proto: String,
+    pub frontend_ip: String,
+    pub frontend_port: u16,
+    /// `namespace/name` when the frontend is a known Service address.
+    pub service: Option<String>,
+    /// IPVS scheduler, e.g. `rr`, when the frontend is a virtual service.
+    pub scheduler: Option<String>,
+    pub connections: usize,
+    pub backends: Vec<BackendLoad>,
+}
+
+impl ServiceLoad {
+    /// What a backend's share is measured in: conntrack flows, or IPVS
+    /// connections when no flows are in view.
+    fn weight(&self, b: &BackendLoad) -> u64 {
+        if self.connections > 0 {
+            b.connections as u64
+        } else {
+            b.ipvs_active.unwrap_or(0) + b.ipvs_inactive.unwrap_or(0)
+        }
+    }
+
+    /// A backend's percentage of the frontend's connections.
+    pub fn share(&self, b: &BackendLoad) -> f64 {
+        let total: u64 = self.backends.iter().map(|b| self.weight(b)).sum();
+        if total == 0 { 0.0 } else { self.weight(b) as f64 * 100.0 / total as f64 }
+    }
+
+    /// The busiest serving backend's connections over an even split across
+    /// serving backends (ready, idle or unknown); 1.0 is perfectly balanced.
+    pub fn imbalance(&self) -> f64 {
+        let serving: Vec<u64> = self.backends.iter()
+            .filter(|b| matches!(b.status, BackendStatus::Ready | BackendStatus::Idle | BackendStatus::Unknown))
+            .map(|b| self.weight(b))
+            .collect();
+        let total: u64 = serving.iter().sum();
+        let max = serving.iter().copied().max().unwrap_or(0);
+        if total == 0 {
+            return 0.0;
+        }
+        max as f64 * serving.len() as f64 / total as f64
+    }
+}
+
+/// Groups DNATed connections from every node by frontend and backend, adds
+/// IPVS virtual services that have connections, and idle endpoints of known
+/// Services. Busiest frontend first.
+pub fn service_loads(nodes: &HashMap<String, Vec<Connection>>, statuses: &HashMap<String, NodeStatus>, names: &KubeNames) -> Vec<ServiceLoad> {
+    let mut loads: HashMap<(String, String, u16), ServiceLoad> = HashMap::new();
+    for c in nodes.values().flatten() {
+        let Some((backend_ip, backend_port)) = c.backend() else { continue };
+        let load = frontend(&mut loads, &c.proto, &c.dst_ip, c.dst_port, names);
+        load.connections += 1;
+        let backend = backend(load, backend_ip, backend_port, names);
+        backend.connections += 1;
+        backend.bytes += c.bytes;
+        backend.throughput_bytes_per_sec += c.throughput_bytes_per_sec;
+    }
+    for vs in statuses.values().flat_map(|s| &s.ipvs) {
+        let (Some(vip), Some(vport)) = (&vs.address, vs.port) else { continue };
+        let key = (vs.proto.clone(), vip.clone(), vport);
+        let busy = vs.real_servers.iter().any(|rs| rs.active_conns + rs.inactive_conns > 0);
+        if !busy && !loads.contains_key(&key) {
+            continue;
+        }
+        let load = frontend(&mut loads, &vs.proto, vip, vport, names);
+        load.scheduler = Some(vs.scheduler.clone());
+        for rs in &vs.real_servers {
+            let b = backend(load, &rs.address, rs.port, names);
+            *b.ipvs_active.get_or_insert(0) += rs.active_conns;
+            *b.ipvs_inactive.get_or_insert(0) += rs.inactive_conns;
+        }
+    }
+
+    let mut out: Vec<ServiceLoad> = loads.into_values().collect();
+    for load in &mut out {
+        let Some(endpoints) = load.service.as_ref().and_then(|s| names.endpoints.get(s)) else { continue };
+        for b in &mut load.backends {
+            let endpoint = endpoints.iter().find(|e| e.ip == b.ip);
+            b.status = match endpoint {
+                Some(e) if e.ready => BackendStatus::Ready,
+                Some(_) => BackendStatus::NotReady,
+                None => BackendStatus::Stale,
+            };
+            if let Some(pod) = endpoint.and_then(|e| e.pod.clone()) {
+                b.name = Some(pod);
+            }
+        }
+        for e in endpoints {
+            if !load.backends.iter().any(|b| b.ip == e.ip) {
+                let name = e.pod.clone().or_else(|| names.by_ip.get(&e.ip).map(Identity::label));
+                let status = if e.ready { BackendStatus::Idle } else { BackendStatus::NotReady };
+                load.backends.push(BackendLoad::new(&e.ip, None, name, status));
+            }
+        }
+    }
+    for load in &mut out {
+        load.backends.sort_by(|a, b| b.connections.cmp(&a.connections).then(a.ip.cmp(&b.ip)));
+    }
+    out.sort_by(|a, b| {
+        b.connections
+            .cmp(&a.connections)
+            .then_with(|| (&a.frontend_ip, a.frontend_port).cmp(&(&b.frontend_ip, b.frontend_port)))
+    });
+    out
+}
+
+fn frontend<'a>(
+    loads: &'a mut HashMap<(String, String, u16), ServiceLoad>,
+    proto: &str,
+    ip: &str,
+    port: u16,
+    names: &KubeNames,
+) -> &'a mut ServiceLoad {
+    loads.entry((proto.to_string(), ip.to_string(), port)).or_insert_with(|| ServiceLoad {
+        proto: proto.to_string(),
+        frontend_ip: ip.to_string(),
+        frontend_port: port,
+        service: match names.by_ip.get(ip) {
+            Some(Identity::Service { namespace, name }) => Some(format!("{}/{}", namespace, name)),
+            _ => None,
+        },
+        scheduler: None,
+        connections: 0,
+        backends: Vec::new(),
+    })
+}
+
+fn backend<'a>(load: &'a mut ServiceLoad, ip: &str, port: u16, names: &KubeNames) -> &'a mut BackendLoad {
+    match load.backends.iter().position(|b| b.ip == ip && b.port == Some(port)) {
+        Some(i) => &mut load.backends[i],
+        None => {
+            load.backends.push(BackendLoad::new(ip, Some(port), names.by_ip.get(ip).map(Identity::label), BackendStatus::Unknown));
+            load.backends.last_mut().unwrap()
+        }
+    }
+}
@@ -11,8 +11,16 @@ use ratatui::{backend::CrosstermBackend, Terminal};
 use ratatui::layout::{Constraint, Direction, Layout};
 use ratatui::widgets::{Block, Borders, Clear, List, ListItem, Paragraph};
 use ratatui::style::{Style, Color, Modifier};
+use ratatui::text::{Line, Span};
 
-use crate::cli::types::Connection;
+use crate::cli::aggregate::{group_flows, FlowGroup};
+use crate::cli::egress::{egress_report, EgressGroup, EgressOptions};
+use crate::cli::fetch::{delete_connection, AdminAccess, Extras};
+use crate::cli::services::{service_loads, ServiceLoad};
+use crate::cli::mesh::collapse;
+use crate::cli::zones::{connection_zones, totals, zone_pairs, zones, Locality, ZonePair};
+use crate::cli::kube::{Identity, SharedKubeNames};
+use crate::cli::types::{Accounting, Connection, DeleteResponse, NodeStatus};
 
 const PORT_MAPPINGS: &[(u16, &str)] = &[
     (1, "TCPMUX"),
@@ -83,10 +91,41 @@ fn build_name_index() -> std::collections::HashMap<String, Vec<u16>> {
     m
 }
 
+/// Endpoint classes the `e` key cycles through; `external` means either end
+/// is outside the cluster (private or public).
+const CLASS_FILTERS: &[&str] = &["external", "pod", "service", "node", "private", "public", "link_local", "loopback"];
+
+fn conn_has_class(c: &Connection, class: &str) -> bool {
+    let classes = [c.src_class.as_deref(), c.dst_class.as_deref()];
+    match class {
+        "external" => classes.iter().any(|k| matches!(k, Some("private") | Some("public"))),
+        _ => classes.contains(&Some(class)),
+    }
+}
+
+/// Short form of an endpoint class for the table.
+fn class_abbrev(class: Option<&str>) -> &'static str {
+    match class {
+        Some("pod") => "pod",
+        Some("service") => "svc",
+        Some("node") => "node",
+        Some("loopback") => "lo",
+        Some("link_local") => "ll",
+        Some("multicast") => "mc",
+        Some("private") => "priv",
+        Some("public") => "pub",
+        _ => "?",
+    }
+}
+
 fn conn_matches_search(c: &Connection, term: &str, name_index: &std::collections::HashMap<String, Vec<u16>>) -> bool {
     let s = term.trim().to_lowercase();
     if s.is_empty() { return true; }
 
+    if CLASS_FILTERS.contains(&s.as_str()) || s == "multicast" {
+        return conn_has_class(c, &s);
+    }
+
     if let Ok(pnum) = s.parse::<u16>() {
         return c.src_port == pnum || c.dst_port == pnum;
     }
@@ -95,8 +134,11 @@ fn conn_matches_search(c: &Connection, term: &str, name_index: &std::collections
         return c.src_ip.contains(&s) || c.dst_ip.contains(&s);
     }
 
-    if let Some(ports) = name_index.get(&s) {
-        if ports.iter().any(|p| *p == c.src_port || *p == c.dst_port) { return true; }
+    if let Some(ports) = name_index.get(&s)
+        && ports.iter().any(|p| *p == c.src_port || *p == c.dst_port) { return true; }
+
+    if c.mark_labels.iter().chain(&c.zone_label).any(|l| l.to_lowercase().contains(&s)) {
+        return true;
     }
 
     let src_desc = port_reservation_info(c.src_port).to_lowercase();
@@ -126,6 +168,76 @@ fn format_throughput(bytes_per_sec: u64) -> String {
     }
 }
 
+fn format_micros(us: u32) -> String {
+    if us >= 1000 {
+        format!("{:.1}ms", us as f64 / 1000.0)
+    } else {
+        format!("{}us", us)
+    }
+}
+
+fn format_rtt(c: &Connection) -> String {
+    c.tcp_info.as_ref().map(|t| format_micros(t.rtt_us)).unwrap_or_else(|| "-".to_string())
+}
+
+fn format_retrans(c: &Connection) -> String {
+    c.tcp_info.as_ref().map(|t| t.total_retrans.to_string()).unwrap_or_else(|| "-".to_string())
+}
+
+/// What the daemon knows about the local end of a connection: the owning
+/// process and container, its network namespace, and TCP diagnostics.
+fn format_local_details(c: &Connection) -> String {
+    let mut lines = Vec::new();
+    if let Some(p) = &c.process {
+        let cmd = if p.cmdline.is_empty() { p.comm.as_str() } else { p.cmdline.as_str() };
+        lines.push(format!("Process: {} (pid {}) {}", p.comm, p.pid, cmd));
+    }
+    if c.container_id.is_some() || c.pod_uid.is_some() {
+        let container: String = c.container_id.as_deref().map(|id| id.chars().take(12).collect()).unwrap_or_else(|| "-".to_string());
+        lines.push(format!("Container: {}  pod UID: {}", container, c.pod_uid.as_deref().unwrap_or("-")));
+    }
+    if let Some(ns) = c.netns {
+        lines.push(format!("Network namespace: {}", ns));
+    }
+    if let Some(mark) = c.mark.filter(|m| *m != 0) {
+        let labels = if c.mark_labels.is_empty() { String::new() } else { format!(" ({})", c.mark_labels.join(", ")) };
+        lines.push(format!("Mark: {:#010x}{}", mark, labels));
+    }
+    if let Some(zone) = c.zone.filter(|z| *z != 0 || c.zone_label.is_some()) {
+        let label = c.zone_label.as_deref().map(|l| format!(" ({})", l)).unwrap_or_default();
+        lines.push(format!("Zone: {}{}", zone, label));
+    }
+    if let Some(t) = &c.tcp_info {
+        lines.push(format!(
+            "TCP: rtt {} (var {}), cwnd {}, unacked {}, retrans {} now / {} total{}",
+            format_rtt(c),
+            format_micros(t.rttvar_us),
+            t.cwnd,
+            t.unacked,
+            t.retrans,
+            t.total_retrans,
+            t.pacing_rate.map(|r| format!(", pacing {}", format_throughput(r))).unwrap_or_default(),
+        ));
+    }
+    lines.join("\n")
+}
+
+fn format_age(c: &Connection) -> String {
+    let Some(secs) = c.age_secs else { return "-".to_string() };
+    // Without kernel timestamps the age is only since the daemon first saw it.
+    let prefix = if c.start_source.as_deref() == Some("kernel") { "" } else { ">" };
+    let body = if secs < 60 {
+        format!("{}s", secs)
+    } else if secs < 3600 {
+        format!("{}m", secs / 60)
+    } else if secs < 86400 {
+        format!("{}h{}m", secs / 3600, (secs % 3600) / 60)
+    } else {
+        format!("{}d{}h", secs / 86400, (secs % 86400) / 3600)
+    };
+    format!("{}{}", prefix, body)
+}
+
 fn port_reservation_info(port: u16) -> String {
     match port {
         1 => "TCP Port Service Multiplexer (TCPMUX)".into(),
@@ -243,18 +355,309 @@ async fn spawn_dns_resolver(
         drop(map);
 
         for ip in ips_to_resolve {
-            if let Ok(addr) = ip.parse::<std::net::IpAddr>() {
-                if let Ok(lookup) = resolver.reverse_lookup(addr).await {
-                    if let Some(name) = lookup.iter().next() {
-                        let hostname = name.to_string().trim_end_matches('.').to_string();
-                        hosts_cache.write().await.insert(ip.clone(), hostname);
-                    }
-                }
+            if let Ok(addr) = ip.parse::<std::net::IpAddr>()
+                && let Ok(lookup) = resolver.reverse_lookup(addr).await
+                && let Some(name) = lookup.iter().next() {
+                let hostname = name.to_string().trim_end_matches('.').to_string();
+                hosts_cache.write().await.insert(ip.clone(), hostname);
             }
         }
     }
 }
 
+fn format_node_info(node: &str, status: Option<&NodeStatus>) -> String {
+    let Some(info) = status.and_then(|s| s.info.as_ref()) else {
+        let reason = status.and_then(|s| s.warning()).unwrap_or("daemon did not report /info");
+        return format!("Node: {}\n\nNo node info available: {}", node, reason);
+    };
+    let ct = &info.conntrack;
+    let flag = |v: Option<bool>| match v { Some(true) => "1", Some(false) => "0", None => "?" };
+    let num = |v: Option<u64>| v.map(|n| n.to_string()).unwrap_or_else(|| "?".into());
+
+    let mut out = format!(
+        "Node: {}\nKernel: {}\nDaemon: {} ({})\n\nConntrack source: {} (requested: {})\n",
+        info.node_name.as_deref().unwrap_or(node),
+        info.kernel_version.as_deref().unwrap_or("?"),
+        info.daemon_version,
+        info.capabilities.join(", "),
+        ct.resolved_path.as_deref().unwrap_or("(none found)"),
+        ct.requested_path,
+    );
+    out.push_str("Candidates probed:\n");
+    for c in &ct.candidates {
+        let what = if let Some(ref e) = c.error {
+            format!("error: {}", e)
+        } else if c.has_content {
+            "exists, has content".to_string()
+        } else if c.exists {
+            "exists, empty".to_string()
+        } else {
+            "missing".to_string()
+        };
+        out.push_str(&format!("  {:<32} {}\n", c.path, what));
+    }
+    out.push_str(&format!(
+        "\nnf_conntrack_acct: {}  nf_conntrack_timestamp: {}\nEntries: {} / {} (nf_conntrack_count / nf_conntrack_max)",
+        flag(ct.acct),
+        flag(ct.timestamp),
+        num(ct.count),
+        num(ct.max),
+    ));
+    out
+}
+
+fn format_expectations(node: &str, status: Option<&NodeStatus>) -> String {
+    let expectations = status.map(|s| s.expectations.as_slice()).unwrap_or_default();
+    if expectations.is_empty() {
+        return format!("Node: {}\n\nNo conntrack expectations. Helpers (FTP, SIP, TFTP, H.323, ...) create these when a control connection announces a data flow.", node);
+    }
+    let mut out = format!("Node: {}\n\n{:<10} {:<5} {:<24} {:<24} {:<8} {}\n", node, "Helper", "Proto", "Expected src", "Expected dst", "Timeout", "Master");
+    for e in expectations {
+        let helper = match (&e.helper, &e.class) {
+            (Some(h), Some(c)) => format!("{}/{}", h, c),
+            (Some(h), None) => h.clone(),
+            _ => "-".to_string(),
+        };
+        let master = e.master.as_ref()
+            .map(|m| format!("{} {}:{} -> {}:{}", m.proto, m.src_ip, m.src_port, m.dst_ip, m.dst_port))
+            .unwrap_or_else(|| "(not found)".to_string());
+        let timeout = e.timeout_secs.map(|t| format!("{}s", t)).unwrap_or_else(|| "-".to_string());
+        let flags = if e.flags.is_empty() { String::new() } else { format!(" [{}]", e.flags.join(",")) };
+        out.push_str(&format!(
+            "{:<10} {:<5} {:<24} {:<24} {:<8} {}{}\n",
+            helper,
+            e.proto,
+            format!("{}:{}", e.src_ip, e.src_port),
+            format!("{}:{}", e.dst_ip, e.dst_port),
+            timeout,
+            master,
+            flags,
+        ));
+    }
+    out
+}
+
+/// Counters from `/proc/net/stat/nf_conntrack` that mean packets are being
+/// lost or the table is under pressure.
+const DROP_COUNTERS: &[&str] = &["drop", "early_drop", "insert_failed", "search_restart", "invalid"];
+
+fn format_flow_group_details(g: &FlowGroup) -> String {
+    let states: Vec<String> = g.states.iter().map(|(s, n)| format!("{} {}", s, n)).collect();
+    let ips = |set: &std::collections::BTreeSet<String>| {
+        let shown: Vec<&str> = set.iter().take(8).map(String::as_str).collect();
+        let more = if set.len() > shown.len() { format!(" (+{} more)", set.len() - shown.len()) } else { String::new() };
+        format!("{}{}", shown.join(", "), more)
+    };
+    format!(
+        "Source: {}\nDestination: {}:{} ({})\nConnections: {} ({})\nBytes: {}\nSource addresses: {}\nDestination addresses: {}",
+        g.src, g.dst, g.dst_port, g.proto, g.connections, states.join(", "), format_bytes(g.bytes), ips(&g.src_ips), ips(&g.dst_ips),
+    )
+}
+
+fn format_service_loads(loads: &[ServiceLoad]) -> String {
+    if loads.is_empty() {
+        return "No NATed connections in view: nothing was sent to a ClusterIP, NodePort, IPVS virtual service or other DNAT address.".to_string();
+    }
+    let mut out = String::new();
+    for load in loads {
+        let name = load.service.as_deref().map(|s| format!("svc:{} ", s)).unwrap_or_default();
+        let scheduler = load.scheduler.as_deref().map(|s| format!(", IPVS {}", s)).unwrap_or_default();
+        out.push_str(&format!("{}{}:{}/{}  {} connections, {} backends, busiest at {:.1}x an even share{}\n",
+            name, load.frontend_ip, load.frontend_port, load.proto, load.connections, load.backends.len(), load.imbalance(), scheduler));
+        for b in &load.backends {
+            let addr = match b.port {
+                Some(p) => format!("{}:{}", b.ip, p),
+                None => b.ip.clone(),
+            };
+            let ipvs = match (b.ipvs_active, b.ipvs_inactive) {
+                (Some(a), Some(i)) => format!("  ipvs {} active, {} inactive", a, i),
+                _ => String::new(),
+            };
+            out.push_str(&format!("    {:<40} {:<32} {:>6} {:>5.1}% {:>9} {:>10}  {}{}\n",
+                addr,
+                b.name.as_deref().unwrap_or("-"),
+                b.connections,
+                load.share(b),
+                format_bytes(b.bytes),
+                format_throughput(b.throughput_bytes_per_sec),
+                b.status.as_str(),
+                ipvs,
+            ));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// The raw sidecar legs behind a collapsed meshed connection.
+fn format_mesh_legs(legs: &[Connection]) -> String {
+    let mut out = String::from("Sidecar legs:");
+    for l in legs {
+        let leg = l.mesh.as_ref().map(|m| m.leg.as_str()).unwrap_or("upstream?");
+        out.push_str(&format!("\n  {:<9} {}:{} -> {}:{}  {}  {}",
+            leg, l.src_ip, l.src_port, l.dst_ip, l.dst_port, l.state, format_bytes(l.bytes)));
+    }
+    out
+}
+
+/// `client -> Service (ClusterIP:port) -> backend` for a DNATed connection.
+fn format_service_path(c: &Connection, identities: &HashMap<String, Identity>) -> Option<String> {
+    let (ip, port) = c.backend()?;
+    let name = |ip: &str| identities.get(ip).map(|id| format!("{} ", id.label())).unwrap_or_default();
+    Some(format!("Service path: {}({}:{}) -> {}({}:{}) -> {}({}:{})",
+        name(&c.src_ip), c.src_ip, c.src_port,
+        name(&c.dst_ip), c.dst_ip, c.dst_port,
+        name(ip), ip, port))
+}
+
+fn format_bytes(bytes: u64) -> String {
+    if bytes < 1024 {
+        format!("{}B", bytes)
+    } else if bytes < 1024 * 1024 {
+        format!("{:.1}KB", bytes as f64 / 1024.0)
+    } else if bytes < 1024 * 1024 * 1024 {
+        format!("{:.1}MB", bytes as f64 / (1024.0 * 1024.0))
+    } else {
+        format!("{:.1}GB", bytes as f64 / (1024.0 * 1024.0 * 1024.0))
+    }
+}
+
+/// Bytes/s from each source zone (rows) to each destination zone
+/// (columns), then totals per locality and the busiest pairs.
+fn format_zone_matrix(pairs: &[ZonePair]) -> String {
+    if pairs.is_empty() {
+        return "No traffic between known zones. Zones come from the Nodes' topology.kubernetes.io/zone label, so Kubernetes names must be available.".to_string();
+    }
+    let names = zones(pairs);
+    let width = names.iter().map(String::len).max().unwrap_or(0).max(10) + 2;
+    let mut out = format!("{:<width$}", "src \\ dst", width = width);
+    for z in &names {
+        out.push_str(&format!("{:>width$}", z, width = width));
+    }
+    out.push('\n');
+    for src in &names {
+        out.push_str(&format!("{:<width$}", src, width = width));
+        for dst in &names {
+            let cell = pairs.iter()
+                .find(|p| &p.src_zone == src && &p.dst_zone == dst)
+                // An idle pair reads 0, unlike a pair with no connections.
+                .map(|p| if p.throughput_bytes_per_sec == 0 { "0".to_string() } else { format_throughput(p.throughput_bytes_per_sec) })
+                .unwrap_or_else(|| "-".to_string());
+            out.push_str(&format!("{:>width$}", cell, width = width));
+        }
+        out.push('\n');
+    }
+    out.push('\n');
+    for (locality, rate, bytes) in totals(pairs) {
+        out.push_str(&format!("{:<13} {:>10}  {:>9} total\n", locality.as_str(), format_throughput(rate), format_bytes(bytes)));
+    }
+    out.push_str("\nBusiest pairs:\n");
+    for p in pairs.iter().take(10) {
+        out.push_str(&format!("  {} -> {}  {}  {} connections, {}\n",
+            p.src_zone, p.dst_zone, p.locality.as_str(), p.connections, format_throughput(p.throughput_bytes_per_sec)));
+    }
+    out
+}
+
+fn format_egress(groups: &[EgressGroup]) -> String {
+    if groups.is_empty() {
+        return "No connections to external destinations.".to_string();
+    }
+    let mut out = format!("{:<26} {:<9} {:<24} {:>6} {:>5} {:>9} {:>10} {:<14} {}\n",
+        "Group", "ASN", "Org", "Conns", "Dsts", "Bytes", "Rate", "Ports", "Nodes");
+    for g in groups {
+        let ports: Vec<String> = g.top_ports.iter().map(u16::to_string).collect();
+        out.push_str(&format!("{:<26} {:<9} {:<24} {:>6} {:>5} {:>9} {:>10} {:<14} {}\n",
+            g.group,
+            g.asn.map(|n| format!("AS{}", n)).unwrap_or_else(|| "-".to_string()),
+            g.org.as_deref().unwrap_or("-"),
+            g.connections,
+            g.destinations,
+            format_bytes(g.bytes),
+            format_throughput(g.throughput_bytes_per_sec),
+            ports.join(","),
+            g.nodes.join(","),
+        ));
+    }
+    out
+}
+
+fn format_listeners(node: &str, status: Option<&NodeStatus>) -> String {
+    let listeners = status.map(|s| s.listeners.as_slice()).unwrap_or_default();
+    if listeners.is_empty() {
+        return format!("Node: {}\n\nNo listening sockets reported (older daemon, or socket tables not readable).", node);
+    }
+    let mut out = format!("Node: {}\n\n{:<5} {:<40} {:<6} {:<7} {}\n", node, "Proto", "Address", "Port", "UID", "Process");
+    for l in listeners {
+        let process = l.process.as_ref()
+            .map(|p| format!("{} ({})", p.comm, p.pid))
+            .unwrap_or_else(|| format!("- (inode {})", l.inode));
+        out.push_str(&format!("{:<5} {:<40} {:<6} {:<7} {}\n", l.proto, l.address, l.port, l.uid, process));
+    }
+    out
+}
+
+fn format_node_summary(status: Option<&NodeStatus>) -> (String, Color) {
+    let Some(stats) = status.and_then(|s| s.stats.as_ref()) else {
+        return ("(no conntrack stats)".to_string(), Color::DarkGray);
+    };
+    let fill = stats.fill_percent.unwrap_or(0.0);
+    let mut out = match (stats.count, stats.max) {
+        (Some(c), Some(m)) => format!("Table: {}/{} ({:.1}%)", c, m, fill),
+        _ => "Table: ?".to_string(),
+    };
+    let rate = |name: &str| stats.rates_per_sec.get(name).copied().unwrap_or(0.0);
+    for name in DROP_COUNTERS {
+        out.push_str(&format!("\n{}: {:.1}/s", name, rate(name)));
+    }
+    let dropping = ["drop", "early_drop", "insert_failed"].iter().any(|n| rate(n) > 0.0);
+    let color = if fill >= 90.0 || dropping {
+        Color::Red
+    } else if fill >= 75.0 {
+        Color::Yellow
+    } else {
+        Color::Gray
+    };
+    (out, color)
+}
+
+/// Where the delete-connection flow is: preview first, then explicit
+/// confirmation, then the daemon's answer.
+enum AdminStep {
+    Checking,
+    Confirm(DeleteResponse),
+    Deleting,
+    Done(String),
+    Failed(String),
+}
+
+struct AdminModal {
+    node: String,
+    conn: Connection,
+    step: AdminStep,
+}
+
+fn format_admin_modal(m: &AdminModal) -> String {
+    let c = &m.conn;
+    let flow = format!("{} {}:{} -> {}:{} ({})", c.proto, c.src_ip, c.src_port, c.dst_ip, c.dst_port, c.state);
+    match &m.step {
+        AdminStep::Checking => format!("Delete conntrack entry on {}?\n\n{}\n\nChecking which entries match (dry run)...", m.node, flow),
+        AdminStep::Confirm(resp) if resp.matched.is_empty() => {
+            format!("No current conntrack entry matches\n\n{}\n\non {}. Press Esc to close.", flow, m.node)
+        }
+        AdminStep::Confirm(resp) => {
+            let mut out = format!("Delete {} conntrack entr{} on {}?\n", resp.matched.len(), if resp.matched.len() == 1 { "y" } else { "ies" }, m.node);
+            for c in &resp.matched {
+                out.push_str(&format!("\n  {} {}:{} -> {}:{} {} ({} bytes)", c.proto, c.src_ip, c.src_port, c.dst_ip, c.dst_port, c.state, c.bytes));
+            }
+            out.push_str("\n\nTraffic on this flow will be re-evaluated by netfilter (NAT, policy) on its next packet.\n\nPress 'y' to delete, 'n' or Esc to cancel.");
+            out
+        }
+        AdminStep::Deleting => format!("Deleting conntrack entry on {}...\n\n{}", m.node, flow),
+        AdminStep::Done(msg) | AdminStep::Failed(msg) => format!("{}\n\nPress Enter or Esc to close.", msg),
+    }
+}
+
 fn truncate_ipv6(ip: &str, max_len: usize) -> String {
     if ip.contains(':') && ip.len() > max_len {
         format!("{}...", &ip[..max_len.saturating_sub(3)])
@@ -271,7 +674,17 @@ fn display_ip(ip: &str, hosts: &HashMap<String, String>, show_hostnames: bool) -
     }
 }
 
-pub async fn run_tui(state: Arc<RwLock<HashMap<String, Vec<Connection>>>>, kube_mode: bool, did_fetch_once: Arc<AtomicBool>) -> anyhow::Result<()> {
+/// What the background fetcher shares with the TUI.
+pub struct Feed {
+    pub connections: Arc<RwLock<HashMap<String, Vec<Connection>>>>,
+    pub statuses: Arc<RwLock<HashMap<String, NodeStatus>>>,
+    pub did_fetch_once: Arc<AtomicBool>,
+    /// Tells the fetcher which extras the open views need.
+    pub extras: tokio::sync::watch::Sender<Extras>,
+}
+
+pub async fn run_tui(feed: Feed, kube_mode: bool, admin: Option<AdminAccess>, kube_names: SharedKubeNames, egress: EgressOptions) -> anyhow::Result<()> {
+    let Feed { connections: state, statuses: node_status, did_fetch_once, extras } = feed;
     enable_raw_mode()?;
     let mut stdout = std::io::stdout();
     execute!(stdout, EnterAlternateScreen)?;
@@ -281,6 +694,7 @@ pub async fn run_tui(state: Arc<RwLock<HashMap<String, Vec<Connection>>>>, kube_
     let refresh_interval = Duration::from_millis(1000);
     let mut last_refresh = tokio::time::Instant::now();
 
+    #[derive(Clone, Copy)]
     enum Focus { Nodes, Shared, Connections }
     enum SortMode { None, ByState }
     enum FilterMode { None, Established, TimeWait }
@@ -290,6 +704,7 @@ pub async fn run_tui(state: Arc<RwLock<HashMap<String, Vec<Connection>>>>, kube_
     let hosts_cache = Arc::new(RwLock::new(load_hosts_file()));
     let mut show_hostnames = false;
     let mut ip_version_filter = IpVersionFilter::Both;
+    let mut class_filter: Option<&str> = None;
     
     tokio::spawn(spawn_dns_resolver(state.clone(), hosts_cache.clone()));
     
@@ -308,7 +723,31 @@ pub async fn run_tui(state: Arc<RwLock<HashMap<String, Vec<Connection>>>>, kube_
 
     let mut modal_dismissed = false;
     let mut help_modal = false;
+    let mut info_modal = false;
+    let mut expect_modal = false;
+    let mut listeners_modal = false;
+    let mut egress_modal = false;
+    let mut zones_modal = false;
+    let mut aggregate_mode = false;
+    let mut mesh_collapse = false;
+    let mut services_modal = false;
+    let mut admin_modal: Option<AdminModal> = None;
+    let (admin_tx, mut admin_rx) = tokio::sync::mpsc::unbounded_channel::<(bool, anyhow::Result<DeleteResponse>)>();
+    let mut selected_conn: Option<Connection> = None;
     loop {
+        while let Ok((dry_run, result)) = admin_rx.try_recv() {
+            let Some(m) = admin_modal.as_mut() else { continue };
+            m.step = match (dry_run, result) {
+                (true, Ok(resp)) => AdminStep::Confirm(resp),
+                (false, Ok(resp)) if !resp.errors.is_empty() => AdminStep::Failed(format!("Delete failed on {}: {}", m.node, resp.errors.join("; "))),
+                (false, Ok(resp)) if resp.deleted == 0 => AdminStep::Done(format!("The entry on {} was already gone.", m.node)),
+                (false, Ok(resp)) => AdminStep::Done(format!("Deleted {} conntrack entr{} on {}.", resp.deleted, if resp.deleted == 1 { "y" } else { "ies" }, m.node)),
+                (_, Err(e)) => AdminStep::Failed(format!("Request to {} failed: {}", m.node, e)),
+            };
+        }
+        let wanted = Extras { info: info_modal, listeners: listeners_modal, ipvs: services_modal };
+        extras.send_if_modified(|e| std::mem::replace(e, wanted) != wanted);
+
         let map = state.read().await.clone();
         let mut nodes: Vec<_> = map.keys().cloned().collect();
         nodes.sort();
@@ -348,10 +787,31 @@ pub async fn run_tui(state: Arc<RwLock<HashMap<String, Vec<Connection>>>>, kube_
         }
 
         let mut edge_list: Vec<((String, String), (usize, String))> = edges.into_iter().collect();
-        edge_list.sort_by(|a, b| b.1 .0.cmp(&a.1 .0));
+        edge_list.sort_by_key(|e| std::cmp::Reverse(e.1 .0));
 
-        let hosts = hosts_cache.read().await.clone();
+        // Kubernetes names take precedence over reverse DNS.
+        let mut hosts = hosts_cache.read().await.clone();
+        let (identities, kube_error) = {
+            let k = kube_names.read().await;
+            (k.by_ip.clone(), k.last_error.clone())
+        };
+        hosts.extend(identities.iter().map(|(ip, id)| (ip.clone(), id.label())));
+        let statuses = node_status.read().await.clone();
+        let egress_groups = if egress_modal { egress_report(&map, &egress) } else { Vec::new() };
+        let topology = kube_names.read().await.topology.clone();
+        let zone_traffic = if zones_modal { zone_pairs(&map, &identities, &topology) } else { Vec::new() };
+        let loads = if services_modal { service_loads(&map, &statuses, &*kube_names.read().await) } else { Vec::new() };
+        let endpoints = if mesh_collapse { kube_names.read().await.endpoints.clone() } else { HashMap::new() };
+        let mut warnings: Vec<String> = statuses.iter()
+            .filter_map(|(node, st)| st.warning().map(|w| format!("{}: {}", node, w)))
+            .collect();
+        warnings.sort();
+        if let Some(e) = kube_error {
+            warnings.push(format!("kubernetes API: {}", e));
+        }
 
+        // Rows in the connections pane, as drawn, for cursor movement.
+        let mut conn_count = 0usize;
         terminal.draw(|f| {
             let size = f.area();
             let outer = Layout::default()
@@ -378,7 +838,12 @@ pub async fn run_tui(state: Arc<RwLock<HashMap<String, Vec<Connection>>>>, kube_
                 } else {
                     n.to_string()
                 };
-                ListItem::new(format!("{} ({})", name, c))
+                let flag = if statuses.get(n).and_then(|s| s.warning()).is_some() { " !" } else { "" };
+                let mut spans = vec![Span::raw(format!("{} ({}){}", name, c, flag))];
+                if statuses.get(n).map(|s| s.accounting) == Some(Accounting::Disabled) {
+                    spans.push(Span::styled(" [no bytes]", Style::default().fg(Color::Yellow)));
+                }
+                ListItem::new(Line::from(spans))
             }).collect();
             let mut stateful = ratatui::widgets::ListState::default();
             if !nodes.is_empty() {
@@ -388,7 +853,17 @@ pub async fn run_tui(state: Arc<RwLock<HashMap<String, Vec<Connection>>>>, kube_
                 .block(Block::default().borders(Borders::ALL).title("Nodes"))
                 .highlight_style(Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD))
                 .highlight_symbol("» ");
-            f.render_stateful_widget(list, chunks[0], &mut stateful);
+            let left_chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Min(3), Constraint::Length(8)].as_ref())
+                .split(chunks[0]);
+            f.render_stateful_widget(list, left_chunks[0], &mut stateful);
+
+            let (summary, summary_color) = format_node_summary(nodes.get(selected).and_then(|n| statuses.get(n)));
+            let summary = Paragraph::new(summary)
+                .block(Block::default().borders(Borders::ALL).title("Node Summary"))
+                .style(Style::default().fg(summary_color));
+            f.render_widget(summary, left_chunks[1]);
 
             let shared_items: Vec<ListItem> = edge_list.iter().map(|((a,b),(count,ip))| {
                 ListItem::new(format!("{} <-> {} ({}) [{}]", a, b, count, ip))
@@ -404,24 +879,31 @@ pub async fn run_tui(state: Arc<RwLock<HashMap<String, Vec<Connection>>>>, kube_
             f.render_stateful_widget(shared_list, right_chunks[0], &mut shared_state);
 
             let selected_node = nodes.get(selected).cloned();
+            // Nodes in view whose kernel isn't counting bytes, so zero throughput means "unknown".
+            let shown_nodes: Vec<String> = match (&pair_filter, &selected_node) {
+                (Some((a, b)), _) => vec![a.clone(), b.clone()],
+                (None, Some(n)) => vec![n.clone()],
+                (None, None) => vec![],
+            };
+            let acct_off: Vec<String> = shown_nodes.into_iter()
+                .filter(|n| statuses.get(n).map(|s| s.accounting) == Some(Accounting::Disabled))
+                .collect();
             if show_details {
                 let mut conns: Vec<Connection> = Vec::new();
                 if let Some((ref a, ref b)) = pair_filter {
-                    if let Some(list_a) = map.get(a) {
-                        if let Some(other_ips) = ip_sets.get(b) {
-                            for c in list_a.iter() {
-                                if other_ips.contains(&c.src_ip) || other_ips.contains(&c.dst_ip) {
-                                    conns.push(c.clone());
-                                }
+                    if let Some(list_a) = map.get(a)
+                        && let Some(other_ips) = ip_sets.get(b) {
+                        for c in list_a.iter() {
+                            if other_ips.contains(&c.src_ip) || other_ips.contains(&c.dst_ip) {
+                                conns.push(c.clone());
                             }
                         }
                     }
-                    if let Some(list_b) = map.get(b) {
-                        if let Some(other_ips) = ip_sets.get(a) {
-                            for c in list_b.iter() {
-                                if other_ips.contains(&c.src_ip) || other_ips.contains(&c.dst_ip) {
-                                    conns.push(c.clone());
-                                }
+                    if let Some(list_b) = map.get(b)
+                        && let Some(other_ips) = ip_sets.get(a) {
+                        for c in list_b.iter() {
+                            if other_ips.contains(&c.src_ip) || other_ips.contains(&c.dst_ip) {
+                                conns.push(c.clone());
                             }
                         }
                     }
@@ -433,6 +915,10 @@ pub async fn run_tui(state: Arc<RwLock<HashMap<String, Vec<Connection>>>>, kube_
                     conns.retain(|c| conn_matches_search(c, term, &name_index));
                 }
 
+                if let Some(class) = class_filter {
+                    conns.retain(|c| conn_has_class(c, class));
+                }
+
                 match filter_mode {
                     FilterMode::Established => {
                         conns.retain(|c| c.state.eq_ignore_ascii_case("ESTABLISHED"));
@@ -453,36 +939,88 @@ pub async fn run_tui(state: Arc<RwLock<HashMap<String, Vec<Connection>>>>, kube_
                     IpVersionFilter::Both => {}
                 }
 
+                let mesh_legs = if mesh_collapse {
+                    let (kept, legs) = collapse(&conns, &identities, &endpoints);
+                    conns = kept;
+                    legs
+                } else {
+                    HashMap::new()
+                };
+
                 // Default: rank by throughput descending unless explicitly sorting by state
                 match sort_mode {
                     SortMode::ByState => {
                         conns.sort_by(|a, b| a.state.cmp(&b.state));
                     }
                     SortMode::None => {
-                        conns.sort_by(|a, b| b.throughput_bytes_per_sec.cmp(&a.throughput_bytes_per_sec));
+                        conns.sort_by_key(|c| std::cmp::Reverse(c.throughput_bytes_per_sec));
                     }
                 }
 
-                let items: Vec<ListItem> = conns.iter().map(|c| {
+                let groups = if aggregate_mode { group_flows(&conns, &identities) } else { Vec::new() };
+                conn_count = if aggregate_mode { groups.len() } else { conns.len() };
+                let items: Vec<ListItem> = if aggregate_mode {
+                    groups.iter().map(|g| {
+                        let throughput = if !acct_off.is_empty() && g.throughput_bytes_per_sec == 0 {
+                            "n/a".to_string()
+                        } else {
+                            format_throughput(g.throughput_bytes_per_sec)
+                        };
+                        let dst = format!("{}:{}", display_ip(&g.dst, &hosts, show_hostnames), g.dst_port);
+                        let line = format!("{:<6} {:<9} {:<36} {:<36} {:>6} {:<12} {:<20}", g.proto, format!("{}>{}", class_abbrev(g.src_class.as_deref()), class_abbrev(g.dst_class.as_deref())), display_ip(&g.src, &hosts, show_hostnames), dst, g.connections, throughput, port_reservation_info(g.dst_port));
+                        ListItem::new(line)
+                    }).collect()
+                } else { conns.iter().map(|c| {
                     let src_ip_str = display_ip(&c.src_ip.to_string(), &hosts, show_hostnames);
                     let dst_ip_str = display_ip(&c.dst_ip.to_string(), &hosts, show_hostnames);
                     let src = format!("{}:{}", src_ip_str, c.src_port);
                     let dst = format!("{}:{}", dst_ip_str, c.dst_port);
                     let port_info = port_reservation_info(c.dst_port);
-                    let throughput = format_throughput(c.throughput_bytes_per_sec);
-                    let line = format!("{:<6} {:<22} {:<22} {:<12} {:<12} {:<20}", c.proto, src, dst, c.state, throughput, port_info);
+                    let throughput = if !acct_off.is_empty() && c.throughput_bytes_per_sec == 0 {
+                        "n/a".to_string()
+                    } else {
+                        format_throughput(c.throughput_bytes_per_sec)
+                    };
+                    let role = match c.role.as_deref() {
+                        Some("server") => "srv",
+                        Some("client") => "cli",
+                        _ => "-",
+                    };
+                    let line = format!("{:<6} {:<4} {:<9} {:<22} {:<22} {:<12} {:<8} {:<8} {:<5} {:<12} {:<20}", c.proto, role, format!("{}>{}", class_abbrev(c.src_class.as_deref()), class_abbrev(c.dst_class.as_deref())), src, dst, c.state, format_age(c), format_rtt(c), format_retrans(c), throughput, port_info);
+                    let line = match c.backend() {
+                        Some((ip, port)) => format!("{} -> {}:{}", line, display_ip(ip, &hosts, show_hostnames), port),
+                        None => line,
+                    };
+                    let line = match (&c.mesh, mesh_legs.get(&c.id)) {
+                        (Some(m), Some(legs)) => format!("{} [{}: {} legs]", line, m.mesh, legs.len()),
+                        (Some(m), None) => format!("{} [{} {}]", line, m.mesh, m.leg),
+                        (None, _) => line,
+                    };
+                    let line = if c.mark_labels.is_empty() { line } else { format!("{} [{}]", line, c.mark_labels.join(", ")) };
+                    let line = match connection_zones(c, &identities, &topology) {
+                        Some((_, _, locality)) if locality != Locality::SameZone => format!("{} [{}]", line, locality.as_str()),
+                        _ => line,
+                    };
                     ListItem::new(line)
-                }).collect();
+                }).collect() };
 
                 if !items.is_empty() {
                     let mut list_state = ratatui::widgets::ListState::default();
                     if conn_selected >= items.len() { conn_selected = items.len() - 1; }
                     list_state.select(Some(conn_selected));
+                    selected_conn = if aggregate_mode { None } else { conns.get(conn_selected).cloned() };
 
-                    let title = match sort_mode {
-                        SortMode::None => "Connections (ranked by throughput)",
-                        SortMode::ByState => "Connections (sorted by state)",
-                    };
+                    let mut title = match (aggregate_mode, &sort_mode) {
+                        (true, _) => "Flows grouped by source -> destination:port (ranked by throughput)",
+                        (false, SortMode::None) => "Connections (ranked by throughput)",
+                        (false, SortMode::ByState) => "Connections (sorted by state)",
+                    }.to_string();
+                    if mesh_collapse {
+                        title.push_str(" — sidecar legs collapsed");
+                    }
+                    if !acct_off.is_empty() {
+                        title.push_str(" — byte counters off (nf_conntrack_acct=0)");
+                    }
 
                     let conn_chunks = Layout::default()
                         .direction(Direction::Vertical)
@@ -495,15 +1033,38 @@ pub async fn run_tui(state: Arc<RwLock<HashMap<String, Vec<Connection>>>>, kube_
                         .highlight_symbol("» ");
                     f.render_stateful_widget(list, conn_chunks[0], &mut list_state);
 
-                    let snippet = {
+                    let snippet = if aggregate_mode {
+                        groups.get(conn_selected)
+                            .map(|g| format!("{}\n\n{}", format_flow_group_details(g), rfc1700_snippet_for_port(g.dst_port)))
+                            .unwrap_or_default()
+                    } else {
                         let idx = conn_selected.min(conns.len().saturating_sub(1));
                         if let Some(c) = conns.get(idx) {
-                            let p = c.dst_port;
-                            rfc1700_snippet_for_port(p)
+                            let snippet = rfc1700_snippet_for_port(c.dst_port);
+                            let local = [("Source", &c.src_ip), ("Destination", &c.dst_ip)]
+                                .iter()
+                                .filter_map(|(side, ip)| identities.get(*ip).map(|id| format!("{}: {}", side, id.describe())))
+                                .chain(format_service_path(c, &identities))
+                                .chain(connection_zones(c, &identities, &topology).map(|(src, dst, locality)| format!("Zones: {} -> {} ({})",
+                                    src.zone.as_deref().unwrap_or("-"), dst.zone.as_deref().unwrap_or("-"), locality.as_str())))
+                                .chain(mesh_legs.get(&c.id).map(|legs| format_mesh_legs(legs)))
+                                .chain(Some(format_local_details(c)).filter(|s| !s.is_empty()))
+                                .collect::<Vec<_>>()
+                                .join("\n");
+                            if local.is_empty() { snippet } else { format!("{}\n\n{}", local, snippet) }
                         } else {
                             "".to_string()
                         }
                     };
+                    let snippet = if acct_off.is_empty() {
+                        snippet
+                    } else {
+                        format!(
+                            "{}\n\nByte counters can't be trusted on {}: nf_conntrack_acct is 0, so throughput reads as zero.\nEnable on the node with `sysctl -w net.netfilter.nf_conntrack_acct=1`; only flows created afterwards are counted.",
+                            snippet,
+                            acct_off.join(", "),
+                        )
+                    };
                     let details = if snippet.is_empty() {
                         Paragraph::new("(no details)")
                             .block(Block::default().borders(Borders::ALL).title("Details"))
@@ -513,6 +1074,7 @@ pub async fn run_tui(state: Arc<RwLock<HashMap<String, Vec<Connection>>>>, kube_
                     };
                     f.render_widget(details, conn_chunks[1]);
                 } else {
+                    selected_conn = None;
                     let conn_chunks = Layout::default()
                         .direction(Direction::Vertical)
                         .constraints([Constraint::Percentage(100)].as_ref())
@@ -522,6 +1084,7 @@ pub async fn run_tui(state: Arc<RwLock<HashMap<String, Vec<Connection>>>>, kube_
                     f.render_widget(paragraph, conn_chunks[0]);
                 }
             } else {
+                selected_conn = None;
                 let paragraph = Paragraph::new("Press Right or Enter to view connections for the selected node. Use Up/Down to choose a node. Press 't' to toggle sort by state. Press 'f' to cycle state filter. Press 'p' to search by IP. Press 'q' to quit.")
                     .block(Block::default().borders(Borders::ALL).title("Connections"));
                 f.render_widget(paragraph, right_chunks[1]);
@@ -529,9 +1092,10 @@ pub async fn run_tui(state: Arc<RwLock<HashMap<String, Vec<Connection>>>>, kube_
             let focus_str = match focus { Focus::Nodes => "Nodes", Focus::Shared => "Shared", Focus::Connections => "Connections" };
             let focus_color = match focus { Focus::Nodes => Color::Cyan, Focus::Shared => Color::Magenta, Focus::Connections => Color::Green };
             let ip_filter_str = match ip_version_filter { IpVersionFilter::Both => "both", IpVersionFilter::Ipv4Only => "IPv4", IpVersionFilter::Ipv6Only => "IPv6" };
-            let status = format!("Focus: {} | Filter: {} | Search: {} | Names: {} | IP: {}{}",
+            let status = format!("Focus: {} | Filter: {} | Class: {} | Search: {} | Names: {} | IP: {}{}",
                 focus_str,
                 match filter_mode { FilterMode::None => "none".to_string(), FilterMode::Established => "ESTABLISHED".to_string(), FilterMode::TimeWait => "TIME_WAIT".to_string(), },
+                class_filter.unwrap_or("any"),
                 search_term.as_deref().unwrap_or("<none>"),
                 if show_hostnames { "ON" } else { "OFF" },
                 ip_filter_str,
@@ -540,19 +1104,114 @@ pub async fn run_tui(state: Arc<RwLock<HashMap<String, Vec<Connection>>>>, kube_
                     _ => "".to_string(),
                 }
             );
-            let sb = Paragraph::new(status)
-                .block(Block::default().borders(Borders::ALL).title("Status"))
-                .style(Style::default().fg(focus_color));
+            let mut status_spans = vec![Span::styled(status, Style::default().fg(focus_color))];
+            if let Some(first) = warnings.first() {
+                let more = if warnings.len() > 1 { format!(" (+{} more)", warnings.len() - 1) } else { String::new() };
+                status_spans.push(Span::styled(
+                    format!(" | WARNING {}{}", first, more),
+                    Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+                ));
+            }
+            let sb = Paragraph::new(Line::from(status_spans))
+                .block(Block::default().borders(Borders::ALL).title("Status"));
             f.render_widget(sb, status_area);
 
             let modal_visible = kube_mode && nodes.is_empty() && did_fetch_once.load(Ordering::SeqCst) && !modal_dismissed;
-                if help_modal {
+            if let Some(m) = &admin_modal {
+                let mw = (size.width.saturating_mul(70)) / 100;
+                let mh = (size.height.saturating_mul(50)) / 100;
+                let mx = size.x + (size.width.saturating_sub(mw)) / 2;
+                let my = size.y + (size.height.saturating_sub(mh)) / 2;
+                let area = ratatui::layout::Rect::new(mx, my, mw, mh);
+                let color = match m.step {
+                    AdminStep::Failed(_) => Color::Red,
+                    AdminStep::Done(_) => Color::Green,
+                    _ => Color::Yellow,
+                };
+                f.render_widget(Clear, area);
+                let p = Paragraph::new(format_admin_modal(m))
+                    .block(Block::default().borders(Borders::ALL).title("Delete conntrack entry"))
+                    .style(Style::default().fg(color).bg(Color::Black));
+                f.render_widget(p, area);
+            } else if services_modal {
+                let mw = (size.width.saturating_mul(90)) / 100;
+                let mh = (size.height.saturating_mul(80)) / 100;
+                let mx = size.x + (size.width.saturating_sub(mw)) / 2;
+                let my = size.y + (size.height.saturating_sub(mh)) / 2;
+                let area = ratatui::layout::Rect::new(mx, my, mw, mh);
+                f.render_widget(Clear, area);
+                let p = Paragraph::new(format_service_loads(&loads))
+                    .block(Block::default().borders(Borders::ALL).title("Services: connections per backend across all nodes (Esc to close)"))
+                    .style(Style::default().fg(Color::White).bg(Color::Black));
+                f.render_widget(p, area);
+            } else if zones_modal {
+                let mw = (size.width.saturating_mul(80)) / 100;
+                let mh = (size.height.saturating_mul(70)) / 100;
+                let mx = size.x + (size.width.saturating_sub(mw)) / 2;
+                let my = size.y + (size.height.saturating_sub(mh)) / 2;
+                let area = ratatui::layout::Rect::new(mx, my, mw, mh);
+                f.render_widget(Clear, area);
+                let p = Paragraph::new(format_zone_matrix(&zone_traffic))
+                    .block(Block::default().borders(Borders::ALL).title("Traffic between zones, bytes/s (Esc to close)"))
+                    .style(Style::default().fg(Color::White).bg(Color::Black));
+                f.render_widget(p, area);
+            } else if egress_modal {
+                let mw = (size.width.saturating_mul(90)) / 100;
+                let mh = (size.height.saturating_mul(80)) / 100;
+                let mx = size.x + (size.width.saturating_sub(mw)) / 2;
+                let my = size.y + (size.height.saturating_sub(mh)) / 2;
+                let area = ratatui::layout::Rect::new(mx, my, mw, mh);
+                f.render_widget(Clear, area);
+                let p = Paragraph::new(format_egress(&egress_groups))
+                    .block(Block::default().borders(Borders::ALL).title("Egress across all nodes (Esc to close)"))
+                    .style(Style::default().fg(Color::White).bg(Color::Black));
+                f.render_widget(p, area);
+            } else if listeners_modal {
+                let mw = (size.width.saturating_mul(80)) / 100;
+                let mh = (size.height.saturating_mul(70)) / 100;
+                let mx = size.x + (size.width.saturating_sub(mw)) / 2;
+                let my = size.y + (size.height.saturating_sub(mh)) / 2;
+                let area = ratatui::layout::Rect::new(mx, my, mw, mh);
+                let node = nodes.get(selected).cloned().unwrap_or_default();
+                let text = format_listeners(&node, statuses.get(&node));
+                f.render_widget(Clear, area);
+                let p = Paragraph::new(text)
+                    .block(Block::default().borders(Borders::ALL).title("Listening sockets (Esc to close)"))
+                    .style(Style::default().fg(Color::White).bg(Color::Black));
+                f.render_widget(p, area);
+            } else if expect_modal {
+                let mw = (size.width.saturating_mul(85)) / 100;
+                let mh = (size.height.saturating_mul(70)) / 100;
+                let mx = size.x + (size.width.saturating_sub(mw)) / 2;
+                let my = size.y + (size.height.saturating_sub(mh)) / 2;
+                let area = ratatui::layout::Rect::new(mx, my, mw, mh);
+                let node = nodes.get(selected).cloned().unwrap_or_default();
+                let text = format_expectations(&node, statuses.get(&node));
+                f.render_widget(Clear, area);
+                let p = Paragraph::new(text)
+                    .block(Block::default().borders(Borders::ALL).title("Expectations (Esc to close)"))
+                    .style(Style::default().fg(Color::White).bg(Color::Black));
+                f.render_widget(p, area);
+            } else if info_modal {
+                let mw = (size.width.saturating_mul(70)) / 100;
+                let mh = (size.height.saturating_mul(70)) / 100;
+                let mx = size.x + (size.width.saturating_sub(mw)) / 2;
+                let my = size.y + (size.height.saturating_sub(mh)) / 2;
+                let area = ratatui::layout::Rect::new(mx, my, mw, mh);
+                let node = nodes.get(selected).cloned().unwrap_or_default();
+                let text = format_node_info(&node, statuses.get(&node));
+                f.render_widget(Clear, area);
+                let p = Paragraph::new(text)
+                    .block(Block::default().borders(Borders::ALL).title("Node Info (Esc to close)"))
+                    .style(Style::default().fg(Color::White).bg(Color::Black));
+                f.render_widget(p, area);
+            } else if help_modal {
                 let mw = (size.width.saturating_mul(70)) / 100;
                 let mh = (size.height.saturating_mul(70)) / 100;
                 let mx = size.x + (size.width.saturating_sub(mw)) / 2;
                 let my = size.y + (size.height.saturating_sub(mh)) / 2;
                 let area = ratatui::layout::Rect::new(mx, my, mw, mh);
-                let help_text = "Key bindings:\n\nUp/Down: move selection\nLeft/Right or Tab: change focus pane\nEnter: open connections / toggle details\nq: quit\np: start search (type term, Enter to apply, Esc to cancel)\nEsc: cancel typing / dismiss modal\nt: toggle sort by state\nf: cycle state filter (none -> ESTABLISHED -> TIME_WAIT)\nc: clear pair-filter\nn: toggle hostnames / IPs\nv: cycle IP version filter (both -> IPv4 -> IPv6)\nh: show this help\n\nPress Enter, Esc, or 'h' to close.";
+                let help_text = "Key bindings:\n\nUp/Down: move selection\nLeft/Right or Tab: change focus pane\nEnter: open connections / toggle details\nq: quit\np: start search (port, IP, service name, endpoint class or mark label; Enter to apply, Esc to cancel)\nEsc: cancel typing / dismiss modal\nt: toggle sort by state\na: toggle grouping of connections by source -> destination workload, port and protocol\nm: toggle collapsing of Istio/Linkerd sidecar legs into one app-to-app connection\nf: cycle state filter (none -> ESTABLISHED -> TIME_WAIT)\nc: clear pair-filter\nn: toggle names (Kubernetes, then DNS) / IPs\nv: cycle IP version filter (both -> IPv4 -> IPv6)\ne: cycle endpoint class filter (external, pod, service, node, private, public, link_local, loopback)\ni: show kernel/conntrack info for the selected node\nx: show conntrack expectations (FTP/SIP/TFTP/H.323 helpers)\nl: show listening sockets on the selected node\ns: show Services (ClusterIP, NodePort) with connections per backend, to spot imbalance and stale backends\ng: show egress to external destinations across all nodes, by CIDR or ASN\nz: show bytes/s between availability zones, to spot cross-zone and cross-region traffic\nD: delete the selected connection's conntrack entry (needs --admin-token; asks to confirm)\nh: show this help\n\nPress Enter, Esc, or 'h' to close.";
                 f.render_widget(Clear, area);
                 let p = Paragraph::new(help_text)
                     .block(Block::default().borders(Borders::ALL).title("kflow — Help"))
@@ -572,173 +1231,275 @@ pub async fn run_tui(state: Arc<RwLock<HashMap<String, Vec<Connection>>>>, kube_
             }
         })?;
 
-        let conn_count = if let Some(node) = state.read().await.keys().cloned().collect::<Vec<_>>().get(selected) {
-            let mut c = state.read().await.get(node).cloned().unwrap_or_default();
-            if let Some(ref term) = search_term { c.retain(|x| conn_matches_search(x, term, &name_index)); }
-            match filter_mode {
-                FilterMode::Established => c.retain(|x| x.state.eq_ignore_ascii_case("ESTABLISHED")),
-                FilterMode::TimeWait => c.retain(|x| x.state.eq_ignore_ascii_case("TIME_WAIT") || x.state.eq_ignore_ascii_case("TIME-WAIT")),
-                FilterMode::None => {}
-            }
-            match sort_mode {
-                SortMode::ByState => c.sort_by(|a,b| a.state.cmp(&b.state)),
-                SortMode::None => c.sort_by(|a,b| b.throughput_bytes_per_sec.cmp(&a.throughput_bytes_per_sec)),
-            }
-            c.len()
-        } else { 0 };
 
         let timeout = refresh_interval.checked_sub(last_refresh.elapsed()).unwrap_or_default();
-        if event::poll(timeout)? {
-            if let event::Event::Key(key) = event::read()? {
-                // Prioritize help modal dismissal if it's visible
-                if help_modal {
-                    match key.code {
-                        event::KeyCode::Enter | event::KeyCode::Esc | event::KeyCode::Char('h') => {
-                            help_modal = false;
+        if event::poll(timeout)?
+            && let event::Event::Key(key) = event::read()? {
+            if let Some(m) = admin_modal.as_mut() {
+                match (&m.step, key.code) {
+                    (AdminStep::Confirm(resp), event::KeyCode::Char('y')) if !resp.matched.is_empty() => {
+                        let endpoint = statuses.get(&m.node).and_then(|s| s.endpoint.clone());
+                        if let (Some(endpoint), Some(access)) = (endpoint, admin.clone()) {
+                            let tx = admin_tx.clone();
+                            let id = m.conn.id.clone();
+                            tokio::spawn(async move {
+                                let _ = tx.send((false, delete_connection(&endpoint, &access, &id, false).await));
+                            });
+                            m.step = AdminStep::Deleting;
                         }
-                        _ => {}
                     }
-                    continue;
+                    (_, event::KeyCode::Esc | event::KeyCode::Enter | event::KeyCode::Char('n')) => admin_modal = None,
+                    _ => {}
                 }
+                continue;
+            }
 
-                let modal_visible = kube_mode && nodes.is_empty() && !modal_dismissed;
-                if modal_visible {
-                    match key.code {
-                        event::KeyCode::Enter | event::KeyCode::Esc | event::KeyCode::Char('c') => {
-                            modal_dismissed = true;
-                        }
-                        _ => {}
+            if services_modal {
+                if let event::KeyCode::Enter | event::KeyCode::Esc | event::KeyCode::Char('s') = key.code {
+                    services_modal = false;
+                }
+                continue;
+            }
+            if zones_modal {
+                if let event::KeyCode::Enter | event::KeyCode::Esc | event::KeyCode::Char('z') = key.code {
+                    zones_modal = false;
+                }
+                continue;
+            }
+            if egress_modal {
+                if let event::KeyCode::Enter | event::KeyCode::Esc | event::KeyCode::Char('g') = key.code {
+                    egress_modal = false;
+                }
+                continue;
+            }
+            if listeners_modal {
+                if let event::KeyCode::Enter | event::KeyCode::Esc | event::KeyCode::Char('l') = key.code {
+                    listeners_modal = false;
+                }
+                continue;
+            }
+
+            if expect_modal {
+                if let event::KeyCode::Enter | event::KeyCode::Esc | event::KeyCode::Char('x') = key.code {
+                    expect_modal = false;
+                }
+                continue;
+            }
+
+            if info_modal {
+                if let event::KeyCode::Enter | event::KeyCode::Esc | event::KeyCode::Char('i') = key.code {
+                    info_modal = false;
+                }
+                continue;
+            }
+
+            // Prioritize help modal dismissal if it's visible
+            if help_modal {
+                match key.code {
+                    event::KeyCode::Enter | event::KeyCode::Esc | event::KeyCode::Char('h') => {
+                        help_modal = false;
                     }
-                    continue;
+                    _ => {}
                 }
+                continue;
+            }
 
-                if let InputMode::Searching = input_mode {
-                    match key.code {
-                        event::KeyCode::Esc => {
-                            input_mode = InputMode::Normal;
-                            search_buffer.clear();
-                        }
-                        event::KeyCode::Enter => {
-                            if !search_buffer.trim().is_empty() {
-                                search_term = Some(search_buffer.trim().to_string());
-                            } else {
-                                search_term = None;
-                            }
-                            input_mode = InputMode::Normal;
-                        }
-                        event::KeyCode::Backspace => { search_buffer.pop(); }
-                        event::KeyCode::Char(c) => { search_buffer.push(c); }
-                        _ => {}
+            let modal_visible = kube_mode && nodes.is_empty() && !modal_dismissed;
+            if modal_visible {
+                match key.code {
+                    event::KeyCode::Enter | event::KeyCode::Esc | event::KeyCode::Char('c') => {
+                        modal_dismissed = true;
                     }
-                } else {
-                    match key.code {
-                        event::KeyCode::Char('q') => break,
-                        event::KeyCode::Down => {
-                            if let Focus::Nodes = focus {
-                                if !state.read().await.is_empty() {
-                                    selected = selected.saturating_add(1).min(state.read().await.len().saturating_sub(1));
-                                    show_details = false;
-                                    conn_selected = 0;
-                                }
-                            } else if let Focus::Shared = focus {
-                                if !edge_list.is_empty() {
-                                    shared_selected = shared_selected.saturating_add(1).min(edge_list.len().saturating_sub(1));
-                                }
-                            } else {
-                                if conn_count > 0 {
-                                    conn_selected = conn_selected.saturating_add(1).min(conn_count.saturating_sub(1));
-                                }
-                            }
+                    _ => {}
+                }
+                continue;
+            }
+
+            if let InputMode::Searching = input_mode {
+                match key.code {
+                    event::KeyCode::Esc => {
+                        input_mode = InputMode::Normal;
+                        search_buffer.clear();
+                    }
+                    event::KeyCode::Enter => {
+                        if !search_buffer.trim().is_empty() {
+                            search_term = Some(search_buffer.trim().to_string());
+                        } else {
+                            search_term = None;
                         }
-                        event::KeyCode::Up => {
-                            if let Focus::Nodes = focus {
-                                if selected > 0 { selected -= 1; }
+                        input_mode = InputMode::Normal;
+                    }
+                    event::KeyCode::Backspace => { search_buffer.pop(); }
+                    event::KeyCode::Char(c) => { search_buffer.push(c); }
+                    _ => {}
+                }
+            } else {
+                match key.code {
+                    event::KeyCode::Char('q') => break,
+                    event::KeyCode::Down => {
+                        if let Focus::Nodes = focus {
+                            if !state.read().await.is_empty() {
+                                selected = selected.saturating_add(1).min(state.read().await.len().saturating_sub(1));
                                 show_details = false;
                                 conn_selected = 0;
-                            } else if let Focus::Shared = focus {
-                                if shared_selected > 0 { shared_selected -= 1; }
-                            } else {
-                                if conn_selected > 0 { conn_selected -= 1; }
                             }
-                        }
-                        event::KeyCode::Enter => {
-                            if let Focus::Shared = focus {
-                                if !edge_list.is_empty() && shared_selected < edge_list.len() {
-                                    let ((a,b), _) = &edge_list[shared_selected];
-                                    pair_filter = Some((a.clone(), b.clone()));
-                                    show_details = true;
-                                    focus = Focus::Connections;
-                                    conn_selected = 0;
-                                }
-                            } else if let Focus::Nodes = focus {
-                                pair_filter = None;
-                                show_details = true;
-                                focus = Focus::Connections;
-                                conn_selected = 0;
-                            } else {
-                                show_details = !show_details;
-                                if show_details { focus = Focus::Connections; } else { focus = Focus::Nodes; }
+                        } else if let Focus::Shared = focus {
+                            if !edge_list.is_empty() {
+                                shared_selected = shared_selected.saturating_add(1).min(edge_list.len().saturating_sub(1));
                             }
-                        }
-                        event::KeyCode::Right | event::KeyCode::Tab => {
-                            let prev = match focus { Focus::Nodes => Focus::Nodes, Focus::Shared => Focus::Shared, Focus::Connections => Focus::Connections };
-                            focus = match focus {
-                                Focus::Nodes => Focus::Shared,
-                                Focus::Shared => Focus::Connections,
-                                Focus::Connections => Focus::Nodes,
-                            };
-                            if let Focus::Connections = focus {
-                                if !show_details { show_details = true; }
-                                if let Focus::Nodes = prev { pair_filter = None; }
+                        } else {
+                            if conn_count > 0 {
+                                conn_selected = conn_selected.saturating_add(1).min(conn_count.saturating_sub(1));
                             }
                         }
-                        event::KeyCode::Left => {
-                            let prev = match focus { Focus::Nodes => Focus::Nodes, Focus::Shared => Focus::Shared, Focus::Connections => Focus::Connections };
-                            focus = match focus {
-                                Focus::Nodes => Focus::Connections,
-                                Focus::Shared => Focus::Nodes,
-                                Focus::Connections => Focus::Shared,
-                            };
-                            if let Focus::Connections = focus { if let Focus::Nodes = prev { pair_filter = None; } }
-                        }
-                        event::KeyCode::Char('r') => {}
-                        event::KeyCode::Char('t') => {
-                            sort_mode = match sort_mode {
-                                SortMode::None => SortMode::ByState,
-                                SortMode::ByState => SortMode::None,
-                            };
-                            conn_selected = 0;
-                        }
-                        event::KeyCode::Char('f') => {
-                            filter_mode = match filter_mode {
-                                FilterMode::None => FilterMode::Established,
-                                FilterMode::Established => FilterMode::TimeWait,
-                                FilterMode::TimeWait => FilterMode::None,
-                            };
+                    }
+                    event::KeyCode::Up => {
+                        if let Focus::Nodes = focus {
+                            selected = selected.saturating_sub(1);
+                            show_details = false;
                             conn_selected = 0;
+                        } else if let Focus::Shared = focus {
+                            shared_selected = shared_selected.saturating_sub(1);
+                        } else {
+                            conn_selected = conn_selected.saturating_sub(1);
                         }
-                        event::KeyCode::Char('p') => {
-                            input_mode = InputMode::Searching;
-                            search_buffer.clear();
-                        }
-                        event::KeyCode::Char('h') => {
-                            help_modal = true;
-                        }
-                        event::KeyCode::Char('c') => {
+                    }
+                    event::KeyCode::Enter => {
+                        if let Focus::Shared = focus {
+                            if !edge_list.is_empty() && shared_selected < edge_list.len() {
+                                let ((a,b), _) = &edge_list[shared_selected];
+                                pair_filter = Some((a.clone(), b.clone()));
+                                show_details = true;
+                                focus = Focus::Connections;
+                                conn_selected = 0;
+                            }
+                        } else if let Focus::Nodes = focus {
                             pair_filter = None;
+                            show_details = true;
+                            focus = Focus::Connections;
+                            conn_selected = 0;
+                        } else {
+                            show_details = !show_details;
+                            if show_details { focus = Focus::Connections; } else { focus = Focus::Nodes; }
                         }
-                        event::KeyCode::Char('n') => {
-                            show_hostnames = !show_hostnames;
+                    }
+                    event::KeyCode::Right | event::KeyCode::Tab => {
+                        let prev = focus;
+                        focus = match focus {
+                            Focus::Nodes => Focus::Shared,
+                            Focus::Shared => Focus::Connections,
+                            Focus::Connections => Focus::Nodes,
+                        };
+                        if let Focus::Connections = focus {
+                            if !show_details { show_details = true; }
+                            if let Focus::Nodes = prev { pair_filter = None; }
                         }
-                        event::KeyCode::Char('v') => {
-                            ip_version_filter = match ip_version_filter {
-                                IpVersionFilter::Both => IpVersionFilter::Ipv4Only,
-                                IpVersionFilter::Ipv4Only => IpVersionFilter::Ipv6Only,
-                                IpVersionFilter::Ipv6Only => IpVersionFilter::Both,
+                    }
+                    event::KeyCode::Left => {
+                        let prev = focus;
+                        focus = match focus {
+                            Focus::Nodes => Focus::Connections,
+                            Focus::Shared => Focus::Nodes,
+                            Focus::Connections => Focus::Shared,
+                        };
+                        if let Focus::Connections = focus && let Focus::Nodes = prev { pair_filter = None; }
+                    }
+                    event::KeyCode::Char('r') => {}
+                    event::KeyCode::Char('t') => {
+                        sort_mode = match sort_mode {
+                            SortMode::None => SortMode::ByState,
+                            SortMode::ByState => SortMode::None,
+                        };
+                        conn_selected = 0;
+                    }
+                    event::KeyCode::Char('f') => {
+                        filter_mode = match filter_mode {
+                            FilterMode::None => FilterMode::Established,
+                            FilterMode::Established => FilterMode::TimeWait,
+                            FilterMode::TimeWait => FilterMode::None,
+                        };
+                        conn_selected = 0;
+                    }
+                    event::KeyCode::Char('p') => {
+                        input_mode = InputMode::Searching;
+                        search_buffer.clear();
+                    }
+                    event::KeyCode::Char('h') => {
+                        help_modal = true;
+                    }
+                    event::KeyCode::Char('i') => {
+                        info_modal = true;
+                    }
+                    event::KeyCode::Char('x') => {
+                        expect_modal = true;
+                    }
+                    event::KeyCode::Char('g') => {
+                        egress_modal = true;
+                    }
+                    event::KeyCode::Char('z') => {
+                        zones_modal = true;
+                    }
+                    event::KeyCode::Char('s') => {
+                        services_modal = true;
+                    }
+                    event::KeyCode::Char('a') => {
+                        aggregate_mode = !aggregate_mode;
+                        conn_selected = 0;
+                    }
+                    event::KeyCode::Char('m') => {
+                        mesh_collapse = !mesh_collapse;
+                        conn_selected = 0;
+                    }
+                    event::KeyCode::Char('l') => {
+                        listeners_modal = true;
+                    }
+                    event::KeyCode::Char('D') => {
+                        if let (Focus::Connections, true, Some(conn)) = (focus, show_details, selected_conn.clone()) {
+                            // With a pair filter the list mixes two nodes; delete on the one that reported it.
+                            let node = match &pair_filter {
+                                Some((a, b)) => [a, b].into_iter()
+                                    .find(|n| map.get(*n).map(|v| v.iter().any(|c| c.id == conn.id)).unwrap_or(false))
+                                    .cloned(),
+                                None => nodes.get(selected).cloned(),
+                            }.unwrap_or_default();
+                            let endpoint = statuses.get(&node).and_then(|s| s.endpoint.clone());
+                            let step = match (&admin, endpoint) {
+                                (None, _) => AdminStep::Failed("Admin actions are disabled. Start kflow with --admin-token (or KFLOW_ADMIN_TOKEN) matching the daemons' KFLOW_ADMIN_TOKEN.".into()),
+                                (_, _) if conn.id.is_empty() => AdminStep::Failed(format!("The daemon on {} is too old to identify connections; upgrade it.", node)),
+                                (_, None) => AdminStep::Failed(format!("Don't know how to reach the daemon on {}.", node)),
+                                (Some(access), Some(endpoint)) => {
+                                    let tx = admin_tx.clone();
+                                    let access = access.clone();
+                                    let id = conn.id.clone();
+                                    tokio::spawn(async move {
+                                        let _ = tx.send((true, delete_connection(&endpoint, &access, &id, true).await));
+                                    });
+                                    AdminStep::Checking
+                                }
                             };
+                            admin_modal = Some(AdminModal { node, conn, step });
                         }
-                        _ => {}
                     }
+                    event::KeyCode::Char('c') => {
+                        pair_filter = None;
+                    }
+                    event::KeyCode::Char('n') => {
+                        show_hostnames = !show_hostnames;
+                    }
+                    event::KeyCode::Char('e') => {
+                        class_filter = match class_filter {
+                            None => CLASS_FILTERS.first().copied(),
+                            Some(cur) => CLASS_FILTERS.iter().skip_while(|c| **c != cur).nth(1).copied(),
+                        };
+                    }
+                    event::KeyCode::Char('v') => {
+                        ip_version_filter = match ip_version_filter {
+                            IpVersionFilter::Both => IpVersionFilter::Ipv4Only,
+                            IpVersionFilter::Ipv4Only => IpVersionFilter::Ipv6Only,
+                            IpVersionFilter::Ipv6Only => IpVersionFilter::Both,
+                        };
+                    }
+                    _ => {}
                 }
             }
         }
@@ -1,7 +1,10 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Connection {
+    #[serde(default)]
+    pub id: String,
     pub proto: String,
     pub src_ip: String,
     pub src_port: u16,
@@ -11,11 +14,355 @@ pub struct Connection {
     #[serde(default)]
     pub bytes: u64,
     #[serde(default)]
+    pub packets: u64,
+    #[serde(default)]
     pub throughput_bytes_per_sec: u64,
+    #[serde(default)]
+    pub age_secs: Option<u64>,
+    #[serde(default)]
+    pub started_at: Option<u64>,
+    /// `kernel` when the age comes from conntrack timestamps, `first_seen` when
+    /// it is only as old as the daemon's first sighting.
+    #[serde(default)]
+    pub start_source: Option<String>,
+    /// `server` or `client` when the daemon could tell which side this node is.
+    #[serde(default)]
+    pub role: Option<String>,
+    /// Present when the daemon collects `tcp_info` and owns a socket for this flow.
+    #[serde(default)]
+    pub tcp_info: Option<TcpInfo>,
+    #[serde(default)]
+    pub process: Option<ProcessRef>,
+    #[serde(default)]
+    pub container_id: Option<String>,
+    #[serde(default)]
+    pub pod_uid: Option<String>,
+    /// Network namespace inode, when the daemon reads every namespace.
+    #[serde(default)]
+    pub netns: Option<u64>,
+    /// `pod`, `service`, `node`, `loopback`, `link_local`, `multicast`,
+    /// `private` or `public`; refined by the CLI with what it knows about
+    /// the cluster.
+    #[serde(default)]
+    pub src_class: Option<String>,
+    #[serde(default)]
+    pub dst_class: Option<String>,
+    /// Reply direction when the flow was NATed; after a Service DNAT,
+    /// `src_ip:src_port` is the backend.
+    #[serde(default)]
+    pub reply: Option<ReplyTuple>,
+    /// IPVS real server the flow was scheduled to (kube-proxy IPVS mode).
+    #[serde(default)]
+    pub ipvs: Option<IpvsRef>,
+    /// Sidecar proxy hop this flow is, in an Istio or Linkerd meshed pod.
+    #[serde(default)]
+    pub mesh: Option<MeshInfo>,
+    /// Connection mark (`ctmark`) and conntrack zone.
+    #[serde(default)]
+    pub mark: Option<u32>,
+    #[serde(default)]
+    pub zone: Option<u16>,
+    /// Labels from the daemon's mark decoders (e.g. Cilium identities,
+    /// Calico policy verdicts).
+    #[serde(default)]
+    pub mark_labels: Vec<String>,
+    #[serde(default)]
+    pub zone_label: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct MeshInfo {
+    /// `istio` or `linkerd`.
+    pub mesh: String,
+    /// `outbound`, `inbound`, `upstream` or `loopback`.
+    pub leg: String,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct IpvsRef {
+    pub real_ip: String,
+    pub real_port: u16,
+    pub state: String,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ReplyTuple {
+    pub src_ip: String,
+    pub src_port: u16,
+    pub dst_ip: String,
+    pub dst_port: u16,
+}
+
+impl Connection {
+    /// Where a DNATed flow really went, e.g. the pod behind a ClusterIP:
+    /// the reply's source, when it differs from the original destination,
+    /// else the IPVS real server. A sidecar redirect has no backend.
+    pub fn backend(&self) -> Option<(&str, u16)> {
+        if self.is_mesh_redirect() {
+            return None;
+        }
+        let reply = self.reply.as_ref()
+            .filter(|r| r.src_ip != self.dst_ip || r.src_port != self.dst_port)
+            .map(|r| (r.src_ip.as_str(), r.src_port));
+        reply.or_else(|| self.ipvs.as_ref().map(|v| (v.real_ip.as_str(), v.real_port)))
+    }
+
+    /// Whether the flow was redirected into a sidecar proxy, i.e. is the
+    /// app-facing leg of a meshed connection.
+    pub fn is_mesh_redirect(&self) -> bool {
+        self.mesh.as_ref().is_some_and(|m| m.leg == "outbound" || m.leg == "inbound")
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TcpInfo {
+    pub rtt_us: u32,
+    pub rttvar_us: u32,
+    pub retrans: u32,
+    pub total_retrans: u32,
+    pub cwnd: u32,
+    pub unacked: u32,
+    #[serde(default)]
+    pub pacing_rate: Option<u64>,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct ConnectionsResponse {
     pub node_name: Option<String>,
+    #[serde(default)]
+    pub accounting: Accounting,
     pub connections: Vec<Connection>,
 }
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Accounting {
+    Enabled,
+    Disabled,
+    #[default]
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct VersionResponse {
+    pub daemon_version: String,
+    pub api_versions: Vec<String>,
+    #[serde(default)]
+    pub target: String,
+    #[serde(default)]
+    pub node_name: Option<String>,
+}
+
+/// What the CLI learned about a daemon besides its connections; surfaced in
+/// the TUI so a node that cannot be read doesn't just silently disappear.
+#[derive(Clone, Debug, Default)]
+pub struct NodeStatus {
+    pub daemon_version: Option<String>,
+    pub accounting: Accounting,
+    pub info: Option<NodeInfo>,
+    pub stats: Option<ConntrackStats>,
+    pub expectations: Vec<Expectation>,
+    pub listeners: Vec<Listener>,
+    /// IPVS virtual services, on nodes with kube-proxy in IPVS mode.
+    pub ipvs: Vec<VirtualService>,
+    pub api_warning: Option<String>,
+    pub fetch_error: Option<String>,
+    /// How this node's daemon was reached, for follow-up requests.
+    pub endpoint: Option<NodeEndpoint>,
+}
+
+#[derive(Clone, Debug)]
+pub enum NodeEndpoint {
+    /// A daemon URL such as `http://10.0.0.5:8080`.
+    Url(String),
+    /// A daemon pod reached through `kubectl port-forward`.
+    Pod(String),
+}
+
+impl NodeStatus {
+    pub fn warning(&self) -> Option<&str> {
+        self.api_warning.as_deref().or(self.fetch_error.as_deref())
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct NodeInfo {
+    pub node_name: Option<String>,
+    #[serde(default)]
+    pub kernel_version: Option<String>,
+    pub daemon_version: String,
+    #[serde(default)]
+    pub capabilities: Vec<String>,
+    pub conntrack: ConntrackInfo,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ConntrackInfo {
+    pub requested_path: String,
+    #[serde(default)]
+    pub resolved_path: Option<String>,
+    #[serde(default)]
+    pub candidates: Vec<CandidateProbe>,
+    #[serde(default)]
+    pub acct: Option<bool>,
+    #[serde(default)]
+    pub timestamp: Option<bool>,
+    #[serde(default)]
+    pub max: Option<u64>,
+    #[serde(default)]
+    pub count: Option<u64>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CandidateProbe {
+    pub path: String,
+    pub exists: bool,
+    pub has_content: bool,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct ConntrackStats {
+    #[serde(default)]
+    pub sampled_at: u64,
+    #[serde(default)]
+    pub count: Option<u64>,
+    #[serde(default)]
+    pub max: Option<u64>,
+    #[serde(default)]
+    pub fill_percent: Option<f64>,
+    #[serde(default)]
+    pub counters: BTreeMap<String, u64>,
+    #[serde(default)]
+    pub rates_per_sec: BTreeMap<String, f64>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Expectation {
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+    pub proto: String,
+    pub src_ip: String,
+    pub src_port: u16,
+    pub dst_ip: String,
+    pub dst_port: u16,
+    #[serde(default)]
+    pub flags: Vec<String>,
+    #[serde(default)]
+    pub helper: Option<String>,
+    #[serde(default)]
+    pub class: Option<String>,
+    #[serde(default)]
+    pub master: Option<FlowRef>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct IpvsResponse {
+    #[serde(default)]
+    pub virtual_services: Vec<VirtualService>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct VirtualService {
+    pub proto: String,
+    #[serde(default)]
+    pub address: Option<String>,
+    #[serde(default)]
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub fwmark: Option<u32>,
+    pub scheduler: String,
+    #[serde(default)]
+    pub flags: Option<String>,
+    #[serde(default)]
+    pub real_servers: Vec<RealServer>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RealServer {
+    pub address: String,
+    pub port: u16,
+    pub forward: String,
+    pub weight: i64,
+    pub active_conns: u64,
+    pub inactive_conns: u64,
+    #[serde(default)]
+    pub flows: usize,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Listener {
+    pub proto: String,
+    pub address: String,
+    pub port: u16,
+    #[serde(default)]
+    pub inode: u64,
+    #[serde(default)]
+    pub uid: u32,
+    #[serde(default)]
+    pub process: Option<ProcessRef>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ProcessRef {
+    pub pid: u32,
+    pub comm: String,
+    #[serde(default)]
+    pub cmdline: String,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct FlowRef {
+    pub proto: String,
+    pub src_ip: String,
+    pub src_port: u16,
+    pub dst_ip: String,
+    pub dst_port: u16,
+}
+
+/// One line of the daemon's flow log (`KFLOW_FLOW_LOG`).
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct FlowRecord {
+    #[serde(default)]
+    pub node_name: Option<String>,
+    #[serde(default)]
+    pub id: String,
+    pub proto: String,
+    pub src_ip: String,
+    pub src_port: u16,
+    pub dst_ip: String,
+    pub dst_port: u16,
+    #[serde(default)]
+    pub netns: Option<u64>,
+    pub start: u64,
+    pub end: u64,
+    #[serde(default)]
+    pub start_source: Option<String>,
+    #[serde(default)]
+    pub bytes: u64,
+    #[serde(default)]
+    pub packets: u64,
+    #[serde(default)]
+    pub states: Vec<StateChange>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct StateChange {
+    pub at: u64,
+    pub state: String,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct DeleteResponse {
+    #[serde(default)]
+    pub node_name: Option<String>,
+    pub dry_run: bool,
+    #[serde(default)]
+    pub matched: Vec<Connection>,
+    #[serde(default)]
+    pub deleted: usize,
+    #[serde(default)]
+    pub errors: Vec<String>,
+}
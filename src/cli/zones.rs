@@ -0,0 +1,256 @@
+use std::borrow::Cow;
+use std::collections::{BTreeSet, HashMap};
+
+use serde::Serialize;
+
+use crate::cli::kube::{Identity, Topology};
+use crate::cli::types::Connection;
+
+/// How far a connection travels between the nodes at its two ends.
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "kebab-case")]
+pub enum Locality {
+    SameZone,
+    CrossZone,
+    CrossRegion,
+}
+
+impl Locality {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Locality::SameZone => "same-zone",
+            Locality::CrossZone => "cross-zone",
+            Locality::CrossRegion => "cross-region",
+        }
+    }
+}
+
+/// Compares two nodes' topology; `None` when either zone is unknown.
+pub fn locality(a: &Topology, b: &Topology) -> Option<Locality> {
+    let (za, zb) = (a.zone.as_ref()?, b.zone.as_ref()?);
+    if let (Some(ra), Some(rb)) = (&a.region, &b.region)
+        && ra != rb
+    {
+        return Some(Locality::CrossRegion);
+    }
+    Some(if za == zb { Locality::SameZone } else { Locality::CrossZone })
+}
+
+/// Zone and region of the node an address lives on: the node itself, or
+/// the node a pod is scheduled to.
+fn topology_of<'a>(ip: &str, identities: &HashMap<String, Identity>, topology: &'a HashMap<String, Topology>) -> Option<&'a Topology> {
+    let node = match identities.get(ip)? {
+        Identity::Node { name } => name,
+        Identity::Pod { node: Some(node), .. } => node,
+        _ => return None,
+    };
+    topology.get(node)
+}
+
+/// Zones at both ends of a connection and how they relate. A DNATed
+/// destination counts as the backend it was translated to.
+pub fn connection_zones<'a>(
+    c: &Connection,
+    identities: &HashMap<String, Identity>,
+    topology: &'a HashMap<String, Topology>,
+) -> Option<(&'a Topology, &'a Topology, Locality)> {
+    let dst_ip = c.backend().map(|(ip, _)| ip).unwrap_or(&c.dst_ip);
+    let src = topology_of(&c.src_ip, identities, topology)?;
+    let dst = topology_of(dst_ip, identities, topology)?;
+    Some((src, dst, locality(src, dst)?))
+}
+
+/// What identifies a flow across nodes: the daemon's id, or the same
+/// `proto-src-sport-dst-dport` form when an older daemon sent none.
+fn flow_key(c: &Connection) -> Cow<'_, str> {
+    if c.id.is_empty() {
+        Cow::Owned(format!("{}-{}-{}-{}-{}", c.proto, c.src_ip, c.src_port, c.dst_ip, c.dst_port))
+    } else {
+        Cow::Borrowed(&c.id)
+    }
+}
+
+/// Traffic from one zone to another, summed over every node.
+#[derive(Debug, Clone, Serialize)]
+pub struct ZonePair {
+    pub src_zone: String,
+    pub dst_zone: String,
+    pub locality: Locality,
+    pub connections: usize,
+    pub bytes: u64,
+    pub throughput_bytes_per_sec: u64,
+}
+
+/// Groups connections by source and destination zone, busiest pair first.
+/// A connection between two nodes is in both nodes' tables, so each flow
+/// is counted once.
+pub fn zone_pairs(
+    nodes: &HashMap<String, Vec<Connection>>,
+    identities: &HashMap<String, Identity>,
+    topology: &HashMap<String, Topology>,
+) -> Vec<ZonePair> {
+    let mut seen: BTreeSet<Cow<str>> = BTreeSet::new();
+    let mut pairs: HashMap<(String, String), ZonePair> = HashMap::new();
+    for c in nodes.values().flatten() {
+        if !seen.insert(flow_key(c)) {
+            continue;
+        }
+        let Some((src, dst, locality)) = connection_zones(c, identities, topology) else { continue };
+        let (Some(src_zone), Some(dst_zone)) = (&src.zone, &dst.zone) else { continue };
+        let pair = pairs.entry((src_zone.clone(), dst_zone.clone())).or_insert_with(|| ZonePair {
+            src_zone: src_zone.clone(),
+            dst_zone: dst_zone.clone(),
+            locality,
+            connections: 0,
+            bytes: 0,
+            throughput_bytes_per_sec: 0,
+        });
+        pair.connections += 1;
+        pair.bytes += c.bytes;
+        pair.throughput_bytes_per_sec += c.throughput_bytes_per_sec;
+    }
+    let mut out: Vec<ZonePair> = pairs.into_values().collect();
+    out.sort_by(|a, b| {
+        b.throughput_bytes_per_sec
+            .cmp(&a.throughput_bytes_per_sec)
+            .then(b.bytes.cmp(&a.bytes))
+            .then_with(|| (&a.src_zone, &a.dst_zone).cmp(&(&b.src_zone, &b.dst_zone)))
+    });
+    out
+}
+
+/// Every zone that appears in `pairs`, sorted.
+pub fn zones(pairs: &[ZonePair]) -> Vec<String> {
+    let set: BTreeSet<&String> = pairs.iter().flat_map(|p| [&p.src_zone, &p.dst_zone]).collect();
+    set.into_iter().cloned().collect()
+}
+
+/// Bytes/s and bytes per locality, for the report's summary line.
+pub fn totals(pairs: &[ZonePair]) -> Vec<(Locality, u64, u64)> {
+    [Locality::SameZone, Locality::CrossZone, Locality::CrossRegion]
+        .into_iter()
+        .map(|l| {
+            let matching = pairs.iter().filter(|p| p.locality == l);
+            let (rate, bytes) = matching.fold((0, 0), |(r, b), p| (r + p.throughput_bytes_per_sec, b + p.bytes));
+            (l, rate, bytes)
+        })
+        .collect()
+}
+
+/// Prints the zone pairs and per-locality totals, or one JSON array with
+/// `json`.
+pub fn print_report(pairs: &[ZonePair], json: bool) -> anyhow::Result<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(pairs)?);
+        return Ok(());
+    }
+    println!("{:<24} {:<24} {:<13} {:>6} {:>14} {:>12}", "SRC ZONE", "DST ZONE", "LOCALITY", "CONNS", "BYTES", "BYTES/S");
+    for p in pairs {
+        println!("{:<24} {:<24} {:<13} {:>6} {:>14} {:>12}",
+            p.src_zone, p.dst_zone, p.locality.as_str(), p.connections, p.bytes, p.throughput_bytes_per_sec);
+    }
+    println!();
+    for (locality, rate, bytes) in totals(pairs) {
+        println!("{:<13} {:>12} bytes/s {:>14} bytes", locality.as_str(), rate, bytes);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn topo(zone: Option<&str>, region: Option<&str>) -> Topology {
+        Topology { zone: zone.map(String::from), region: region.map(String::from) }
+    }
+
+    fn conn(id: &str, src: &str, dst: &str, bytes: u64, reply_src: Option<&str>) -> Connection {
+        let reply = reply_src.map(|r| json!({ "src_ip": r, "src_port": 8080, "dst_ip": src, "dst_port": 40000 }));
+        serde_json::from_value(json!({
+            "id": id, "proto": "tcp", "src_ip": src, "src_port": 40000, "dst_ip": dst, "dst_port": 80,
+            "state": "ESTABLISHED", "bytes": bytes, "throughput_bytes_per_sec": bytes / 10, "reply": reply,
+        }))
+        .unwrap()
+    }
+
+    fn cluster() -> (HashMap<String, Identity>, HashMap<String, Topology>) {
+        let pod = |name: &str, node: &str| Identity::Pod {
+            namespace: "default".into(),
+            name: name.into(),
+            workload: None,
+            node: Some(node.into()),
+            services: vec![],
+        };
+        let identities = HashMap::from([
+            ("10.0.1.5".to_string(), pod("client", "node-a")),
+            ("10.0.2.5".to_string(), pod("server", "node-b")),
+            ("10.0.3.5".to_string(), pod("far", "node-c")),
+            ("10.0.4.5".to_string(), pod("unlabelled", "node-d")),
+            ("192.168.0.2".to_string(), Identity::Node { name: "node-b".into() }),
+            ("10.96.0.10".to_string(), Identity::Service { namespace: "default".into(), name: "server".into() }),
+        ]);
+        let topology = HashMap::from([
+            ("node-a".to_string(), topo(Some("eu-1a"), Some("eu-1"))),
+            ("node-b".to_string(), topo(Some("eu-1b"), Some("eu-1"))),
+            ("node-c".to_string(), topo(Some("us-1a"), Some("us-1"))),
+            ("node-d".to_string(), topo(Some("us-1b"), None)),
+        ]);
+        (identities, topology)
+    }
+
+    #[test]
+    fn locality_rules() {
+        let eu_a = topo(Some("eu-1a"), Some("eu-1"));
+        assert_eq!(locality(&eu_a, &eu_a), Some(Locality::SameZone));
+        assert_eq!(locality(&eu_a, &topo(Some("eu-1b"), Some("eu-1"))), Some(Locality::CrossZone));
+        assert_eq!(locality(&eu_a, &topo(Some("us-1a"), Some("us-1"))), Some(Locality::CrossRegion));
+        // Without a region on one side the zones are all there is to go on.
+        assert_eq!(locality(&eu_a, &topo(Some("us-1a"), None)), Some(Locality::CrossZone));
+        assert_eq!(locality(&topo(Some("eu-1a"), None), &topo(Some("eu-1a"), Some("eu-1"))), Some(Locality::SameZone));
+        assert_eq!(locality(&eu_a, &topo(None, Some("eu-1"))), None);
+    }
+
+    #[test]
+    fn dnat_destination_counts_as_the_backend() {
+        let (identities, topology) = cluster();
+        let via_service = conn("a", "10.0.1.5", "10.96.0.10", 100, Some("10.0.2.5"));
+        let (src, dst, l) = connection_zones(&via_service, &identities, &topology).unwrap();
+        assert_eq!((src.zone.as_deref(), dst.zone.as_deref(), l), (Some("eu-1a"), Some("eu-1b"), Locality::CrossZone));
+        // Without the reply tuple the ClusterIP has no node to be in.
+        assert!(connection_zones(&conn("b", "10.0.1.5", "10.96.0.10", 100, None), &identities, &topology).is_none());
+        let to_node = conn("c", "10.0.1.5", "192.168.0.2", 100, None);
+        assert_eq!(connection_zones(&to_node, &identities, &topology).unwrap().2, Locality::CrossZone);
+    }
+
+    #[test]
+    fn flows_seen_by_both_nodes_are_counted_once() {
+        let (identities, topology) = cluster();
+        let nodes = HashMap::from([
+            ("node-a".to_string(), vec![
+                conn("tcp-10.0.1.5-40000-10.0.2.5-80", "10.0.1.5", "10.0.2.5", 1000, None),
+                conn("", "10.0.1.5", "10.0.3.5", 500, None),
+                conn("", "10.0.1.5", "10.0.4.5", 300, None),
+            ]),
+            ("node-b".to_string(), vec![conn("tcp-10.0.1.5-40000-10.0.2.5-80", "10.0.1.5", "10.0.2.5", 1000, None)]),
+            ("node-c".to_string(), vec![conn("", "10.0.1.5", "10.0.3.5", 500, None)]),
+            ("node-d".to_string(), vec![conn("tcp-10.0.1.5-40000-10.0.4.5-80", "10.0.1.5", "10.0.4.5", 300, None)]),
+        ]);
+        let pairs = zone_pairs(&nodes, &identities, &topology);
+        let summary: Vec<(&str, &str, Locality, usize, u64)> = pairs
+            .iter()
+            .map(|p| (p.src_zone.as_str(), p.dst_zone.as_str(), p.locality, p.connections, p.bytes))
+            .collect();
+        assert_eq!(summary, [
+            ("eu-1a", "eu-1b", Locality::CrossZone, 1, 1000),
+            ("eu-1a", "us-1a", Locality::CrossRegion, 1, 500),
+            ("eu-1a", "us-1b", Locality::CrossZone, 1, 300),
+        ]);
+        assert_eq!(zones(&pairs), ["eu-1a", "eu-1b", "us-1a", "us-1b"]);
+        assert_eq!(totals(&pairs), [
+            (Locality::SameZone, 0, 0),
+            (Locality::CrossZone, 130, 1300),
+            (Locality::CrossRegion, 50, 500),
+        ]);
+    }
+}
@@ -0,0 +1,86 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+
+use axum::http::HeaderMap;
+use serde::Serialize;
+
+/// The opt-in admin API. It is enabled only when a bearer token is
+/// configured, via `KFLOW_ADMIN_TOKEN` or a file named by
+/// `KFLOW_ADMIN_TOKEN_FILE` (e.g. a mounted Secret). Every admin request,
+/// allowed or not, is audited to stdout and, if `KFLOW_ADMIN_AUDIT_LOG` is
+/// set, appended to that file as JSON Lines.
+#[derive(Debug, Clone)]
+pub struct AdminConfig {
+    token: String,
+    pub audit_log: Option<PathBuf>,
+}
+
+impl AdminConfig {
+    pub fn from_env() -> Option<Self> {
+        let token = match std::env::var("KFLOW_ADMIN_TOKEN_FILE") {
+            Ok(path) if !path.is_empty() => match std::fs::read_to_string(&path) {
+                Ok(t) => t,
+                Err(e) => {
+                    eprintln!("failed to read KFLOW_ADMIN_TOKEN_FILE {}: {}; admin API disabled", path, e);
+                    return None;
+                }
+            },
+            _ => std::env::var("KFLOW_ADMIN_TOKEN").ok()?,
+        };
+        let token = token.trim().to_string();
+        if token.is_empty() {
+            return None;
+        }
+        Some(AdminConfig {
+            token,
+            audit_log: std::env::var("KFLOW_ADMIN_AUDIT_LOG").ok().filter(|p| !p.is_empty()).map(PathBuf::from),
+        })
+    }
+
+    /// Whether the request carries `Authorization: Bearer <token>`.
+    pub fn authorized(&self, headers: &HeaderMap) -> bool {
+        let Some(presented) = headers
+            .get("authorization")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+        else {
+            return false;
+        };
+        // Compare without short-circuiting so timing doesn't leak a prefix.
+        let (a, b) = (presented.trim().as_bytes(), self.token.as_bytes());
+        a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+    }
+
+    pub fn audit(&self, record: &AuditRecord) {
+        let line = serde_json::to_string(record).unwrap_or_default();
+        println!("audit: {}", line);
+        if let Some(path) = &self.audit_log {
+            let written = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .and_then(|mut f| writeln!(f, "{}", line));
+            if let Err(e) = written {
+                eprintln!("failed to write admin audit log {}: {}", path.display(), e);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditRecord {
+    pub at: u64,
+    pub action: String,
+    pub target: String,
+    pub remote_addr: String,
+    /// Who the client says it is acting for (`X-Kflow-User`); informational,
+    /// since the token is shared.
+    pub user: Option<String>,
+    pub dry_run: bool,
+    /// `denied`, `not_found`, `ok` or `error`.
+    pub outcome: String,
+    pub matched: usize,
+    pub deleted: usize,
+    pub errors: Vec<String>,
+}
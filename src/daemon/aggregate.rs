@@ -0,0 +1,146 @@
+use std::collections::{BTreeSet, HashMap};
+use std::net::IpAddr;
+
+use serde::Serialize;
+
+use crate::daemon::conntrack::{Connection, Role};
+use crate::daemon::ipclass::IpClass;
+
+/// One side of an aggregated flow: the local pod when process attribution
+/// found it, the address otherwise.
+#[derive(Debug, Clone, Serialize, Hash, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum FlowEnd {
+    Pod { pod_uid: String },
+    Ip { ip: IpAddr },
+}
+
+/// Connections from one source to one destination port, whatever their
+/// ephemeral source ports.
+#[derive(Debug, Clone, Serialize)]
+pub struct FlowAggregate {
+    pub src: FlowEnd,
+    pub dst: FlowEnd,
+    pub dst_port: u16,
+    pub proto: String,
+    pub src_class: IpClass,
+    pub dst_class: IpClass,
+    pub connections: usize,
+    pub bytes: u64,
+    pub packets: u64,
+    pub throughput_bytes_per_sec: u64,
+    /// Addresses behind each side, so clients can name them.
+    pub src_ips: Vec<IpAddr>,
+    pub dst_ips: Vec<IpAddr>,
+    /// Connections per conntrack state.
+    pub states: HashMap<String, usize>,
+}
+
+type AggregateKey = (FlowEnd, FlowEnd, u16, String);
+
+/// The ends a flow is aggregated by: the local pod on the side the
+/// connection's role puts the attributed socket, the address otherwise. The
+/// TUI's grouping mode keys on this too, so its rows match `/v1/aggregates`.
+pub fn flow_ends(src_ip: IpAddr, dst_ip: IpAddr, pod_uid: Option<&str>, role: Option<Role>) -> (FlowEnd, FlowEnd) {
+    let pod = |side: Role| match (pod_uid, role) {
+        (Some(uid), Some(r)) if r == side => Some(FlowEnd::Pod { pod_uid: uid.to_string() }),
+        _ => None,
+    };
+    (
+        pod(Role::Client).unwrap_or(FlowEnd::Ip { ip: src_ip }),
+        pod(Role::Server).unwrap_or(FlowEnd::Ip { ip: dst_ip }),
+    )
+}
+
+/// Collapses connections into `(source) -> (destination, port, protocol)`
+/// rows, busiest first.
+pub fn aggregate(flows: &[Connection]) -> Vec<FlowAggregate> {
+    let mut rows: HashMap<AggregateKey, (FlowAggregate, BTreeSet<IpAddr>, BTreeSet<IpAddr>)> = HashMap::new();
+    for c in flows {
+        let (src, dst) = flow_ends(c.src_ip, c.dst_ip, c.pod_uid.as_deref(), c.role);
+        let key = (src.clone(), dst.clone(), c.dst_port, c.proto.clone());
+        let (row, src_ips, dst_ips) = rows.entry(key).or_insert_with(|| {
+            let row = FlowAggregate {
+                src,
+                dst,
+                dst_port: c.dst_port,
+                proto: c.proto.clone(),
+                src_class: c.src_class,
+                dst_class: c.dst_class,
+                connections: 0,
+                bytes: 0,
+                packets: 0,
+                throughput_bytes_per_sec: 0,
+                src_ips: Vec::new(),
+                dst_ips: Vec::new(),
+                states: HashMap::new(),
+            };
+            (row, BTreeSet::new(), BTreeSet::new())
+        });
+        row.connections += 1;
+        row.bytes += c.bytes;
+        row.packets += c.packets;
+        row.throughput_bytes_per_sec += c.throughput_bytes_per_sec;
+        *row.states.entry(c.state.clone()).or_default() += 1;
+        src_ips.insert(c.src_ip);
+        dst_ips.insert(c.dst_ip);
+    }
+
+    let mut out: Vec<FlowAggregate> = rows
+        .into_values()
+        .map(|(mut row, src_ips, dst_ips)| {
+            row.src_ips = src_ips.into_iter().collect();
+            row.dst_ips = dst_ips.into_iter().collect();
+            row
+        })
+        .collect();
+    out.sort_by(|a, b| {
+        b.throughput_bytes_per_sec
+            .cmp(&a.throughput_bytes_per_sec)
+            .then(b.bytes.cmp(&a.bytes))
+            .then(b.connections.cmp(&a.connections))
+            .then_with(|| (&a.src, &a.dst, a.dst_port).cmp(&(&b.src, &b.dst, b.dst_port)))
+    });
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::daemon::conntrack::parse_conntrack_line;
+
+    fn flow(sport: u16, dst: &str, dport: u16, bytes: u64, role: Option<Role>, pod_uid: Option<&str>) -> Connection {
+        let line = format!(
+            "ipv4 2 tcp 6 100 ESTABLISHED src=10.1.0.5 dst={dst} sport={sport} dport={dport} packets=2 bytes={bytes} src={dst} dst=10.1.0.5 sport={dport} dport={sport} packets=0 bytes=0 [ASSURED] mark=0 use=1"
+        );
+        let mut c = parse_conntrack_line(&line).unwrap();
+        c.role = role;
+        c.pod_uid = pod_uid.map(str::to_string);
+        c
+    }
+
+    #[test]
+    fn groups_ephemeral_ports_by_pod_or_address() {
+        let uid = "1b2c3d4e-5f60-4718-9a0b-1c2d3e4f5a6b";
+        let flows = vec![
+            flow(40000, "10.96.0.10", 80, 100, Some(Role::Client), Some(uid)),
+            flow(40001, "10.96.0.10", 80, 300, Some(Role::Client), Some(uid)),
+            // Same addresses, but no attributed socket: keyed by IP.
+            flow(40002, "10.96.0.10", 80, 50, None, None),
+            // The pod UID belongs to the server side here, so the source stays an address.
+            flow(40003, "10.96.0.10", 443, 10, Some(Role::Server), Some(uid)),
+        ];
+        let rows = aggregate(&flows);
+        assert_eq!(rows.len(), 3);
+
+        let pod = FlowEnd::Pod { pod_uid: uid.to_string() };
+        let ip = |s: &str| FlowEnd::Ip { ip: s.parse().unwrap() };
+        assert_eq!((&rows[0].src, &rows[0].dst, rows[0].dst_port), (&pod, &ip("10.96.0.10"), 80));
+        assert_eq!((rows[0].connections, rows[0].bytes, rows[0].packets), (2, 400, 4));
+        assert_eq!(rows[0].src_ips, ["10.1.0.5".parse::<IpAddr>().unwrap()]);
+        assert_eq!(rows[0].states["ESTABLISHED"], 2);
+
+        assert_eq!((&rows[1].src, rows[1].connections, rows[1].bytes), (&ip("10.1.0.5"), 1, 50));
+        assert_eq!((&rows[2].src, &rows[2].dst, rows[2].dst_port), (&ip("10.1.0.5"), &pod, 443));
+    }
+}
@@ -0,0 +1,420 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::sync::Semaphore;
+
+use crate::daemon::cidr::Cidr;
+use crate::daemon::conntrack::Connection;
+use crate::daemon::stats::ConntrackStats;
+
+/// Alert rules and where to send notifications, loaded from the JSON file
+/// named by `KFLOW_ALERT_RULES`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct AlertConfig {
+    #[serde(default)]
+    pub webhooks: Vec<Webhook>,
+    pub rules: Vec<Rule>,
+    /// Re-notify a rule that stays firing this often. Unset means notify once
+    /// per firing and once on resolve.
+    #[serde(default)]
+    pub repeat_secs: Option<u64>,
+}
+
+impl AlertConfig {
+    pub fn from_env() -> Option<Self> {
+        let path = std::env::var("KFLOW_ALERT_RULES").ok().filter(|p| !p.is_empty())?;
+        let parsed = std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|s| serde_json::from_str::<AlertConfig>(&s).map_err(|e| e.to_string()));
+        match parsed {
+            Ok(config) => Some(config),
+            Err(e) => {
+                eprintln!("failed to load alert rules from {}: {}; alerting disabled", path, e);
+                None
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Webhook {
+    pub url: String,
+    #[serde(default)]
+    pub format: WebhookFormat,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookFormat {
+    /// The `Notification` as JSON.
+    #[default]
+    Generic,
+    /// `{"text": ...}`, accepted by Slack incoming webhooks and most chat tools.
+    Slack,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rule {
+    pub name: String,
+    #[serde(flatten)]
+    pub condition: Condition,
+    /// How long the condition must hold before the rule fires.
+    #[serde(default)]
+    pub for_secs: u64,
+    /// Overrides the file-level `repeat_secs` for this rule.
+    #[serde(default)]
+    pub repeat_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Condition {
+    /// More than `above` connections with either end inside `cidr`.
+    Cidr {
+        cidr: Cidr,
+        #[serde(default)]
+        above: u64,
+    },
+    /// Combined throughput (bytes/s) of connections on `port` above `above`.
+    PortThroughput { port: u16, above: u64 },
+    /// More than `above` connections in conntrack state `state`.
+    StateCount { state: String, above: u64 },
+    /// Conntrack table fill above `above_percent`.
+    ConntrackFill { above_percent: f64 },
+}
+
+impl Condition {
+    /// The current value of the condition's metric, or `None` when it cannot
+    /// be measured on this node.
+    fn value(&self, flows: &[Connection], stats: &ConntrackStats) -> Option<f64> {
+        match self {
+            Condition::Cidr { cidr, .. } => Some(
+                flows.iter().filter(|c| cidr.contains(&c.src_ip) || cidr.contains(&c.dst_ip)).count() as f64,
+            ),
+            Condition::PortThroughput { port, .. } => Some(
+                flows
+                    .iter()
+                    .filter(|c| c.src_port == *port || c.dst_port == *port)
+                    .map(|c| c.throughput_bytes_per_sec)
+                    .sum::<u64>() as f64,
+            ),
+            Condition::StateCount { state, .. } => Some(
+                flows.iter().filter(|c| c.state.eq_ignore_ascii_case(state)).count() as f64,
+            ),
+            Condition::ConntrackFill { .. } => stats.fill_percent,
+        }
+    }
+
+    fn threshold(&self) -> f64 {
+        match self {
+            Condition::Cidr { above, .. }
+            | Condition::PortThroughput { above, .. }
+            | Condition::StateCount { above, .. } => *above as f64,
+            Condition::ConntrackFill { above_percent } => *above_percent,
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Condition::Cidr { cidr, .. } => format!("connections to {}", cidr),
+            Condition::PortThroughput { port, .. } => format!("throughput on port {} (B/s)", port),
+            Condition::StateCount { state, .. } => format!("{} connections", state),
+            Condition::ConntrackFill { .. } => "conntrack table fill (%)".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertState {
+    Ok,
+    /// The condition holds but not yet for `for_secs`.
+    Pending,
+    Firing,
+}
+
+/// A rule's current evaluation, served by `/v1/alerts`.
+#[derive(Debug, Clone, Serialize)]
+pub struct AlertStatus {
+    pub rule: Rule,
+    pub state: AlertState,
+    pub value: Option<f64>,
+    /// When the condition started holding, if it currently does.
+    pub active_since: Option<u64>,
+    pub last_notified: Option<u64>,
+}
+
+/// The generic webhook payload.
+#[derive(Debug, Clone, Serialize)]
+pub struct Notification {
+    pub rule: String,
+    /// `firing` or `resolved`.
+    pub status: String,
+    pub node_name: Option<String>,
+    pub cluster_name: Option<String>,
+    pub value: Option<f64>,
+    pub threshold: f64,
+    pub since: Option<u64>,
+    pub at: u64,
+    pub summary: String,
+}
+
+/// A webhook that hasn't answered within this long is abandoned.
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Webhook requests allowed in flight at once; notifications beyond this
+/// are dropped rather than queued behind an unresponsive endpoint.
+const MAX_IN_FLIGHT: usize = 16;
+
+/// Evaluates alert rules once per sampling tick and notifies webhooks on
+/// every firing/resolved transition.
+pub struct AlertEngine {
+    config: AlertConfig,
+    statuses: Vec<AlertStatus>,
+    node_name: Option<String>,
+    cluster_name: Option<String>,
+    client: reqwest::Client,
+    in_flight: Arc<Semaphore>,
+}
+
+impl AlertEngine {
+    pub fn new(config: AlertConfig, node_name: Option<String>) -> Self {
+        let statuses = config
+            .rules
+            .iter()
+            .map(|rule| AlertStatus { rule: rule.clone(), state: AlertState::Ok, value: None, active_since: None, last_notified: None })
+            .collect();
+        AlertEngine {
+            config,
+            statuses,
+            node_name,
+            cluster_name: std::env::var("KFLOW_CLUSTER_NAME").ok().filter(|c| !c.is_empty()),
+            client: reqwest::Client::builder().timeout(WEBHOOK_TIMEOUT).build().unwrap_or_default(),
+            in_flight: Arc::new(Semaphore::new(MAX_IN_FLIGHT)),
+        }
+    }
+
+    pub fn statuses(&self) -> Vec<AlertStatus> {
+        self.statuses.clone()
+    }
+
+    /// Updates every rule and sends the resulting notifications in the
+    /// background.
+    pub fn evaluate(&mut self, now: u64, flows: &[Connection], stats: &ConntrackStats) {
+        let mut notifications = Vec::new();
+        for s in self.statuses.iter_mut() {
+            let value = s.rule.condition.value(flows, stats);
+            let threshold = s.rule.condition.threshold();
+            s.value = value;
+
+            let holds = value.map(|v| v > threshold).unwrap_or(false);
+            if !holds {
+                if s.state == AlertState::Firing {
+                    notifications.push(notification(s, "resolved", now, &self.node_name, &self.cluster_name));
+                }
+                s.state = AlertState::Ok;
+                s.active_since = None;
+                s.last_notified = None;
+                continue;
+            }
+
+            let since = *s.active_since.get_or_insert(now);
+            let repeat = s.rule.repeat_secs.or(self.config.repeat_secs);
+            match s.state {
+                AlertState::Firing => {
+                    if let (Some(repeat), Some(last)) = (repeat, s.last_notified)
+                        && now.saturating_sub(last) >= repeat {
+                        s.last_notified = Some(now);
+                        notifications.push(notification(s, "firing", now, &self.node_name, &self.cluster_name));
+                    }
+                }
+                _ if now.saturating_sub(since) >= s.rule.for_secs => {
+                    s.state = AlertState::Firing;
+                    s.last_notified = Some(now);
+                    notifications.push(notification(s, "firing", now, &self.node_name, &self.cluster_name));
+                }
+                _ => s.state = AlertState::Pending,
+            }
+        }
+
+        for n in notifications {
+            println!("alert {}: {}", n.status, n.summary);
+            for hook in &self.config.webhooks {
+                let body = match hook.format {
+                    WebhookFormat::Generic => serde_json::to_value(&n).unwrap_or_default(),
+                    WebhookFormat::Slack => json!({ "text": slack_text(&n) }),
+                };
+                let Ok(permit) = self.in_flight.clone().try_acquire_owned() else {
+                    eprintln!("alert webhook {} skipped: {} requests still in flight", hook.url, MAX_IN_FLIGHT);
+                    continue;
+                };
+                let req = self.client.post(&hook.url).json(&body);
+                let url = hook.url.clone();
+                tokio::spawn(async move {
+                    let _permit = permit;
+                    match req.send().await {
+                        Ok(resp) if !resp.status().is_success() => {
+                            eprintln!("alert webhook {} returned HTTP {}", url, resp.status());
+                        }
+                        Ok(_) => {}
+                        Err(e) => eprintln!("alert webhook {} failed: {}", url, e),
+                    }
+                });
+            }
+        }
+    }
+}
+
+fn notification(s: &AlertStatus, status: &str, now: u64, node_name: &Option<String>, cluster_name: &Option<String>) -> Notification {
+    let threshold = s.rule.condition.threshold();
+    let value = s.value.map(|v| format!("{:.1}", v)).unwrap_or_else(|| "n/a".into());
+    Notification {
+        rule: s.rule.name.clone(),
+        status: status.to_string(),
+        node_name: node_name.clone(),
+        cluster_name: cluster_name.clone(),
+        value: s.value,
+        threshold,
+        since: s.active_since,
+        at: now,
+        summary: format!("{} is {} (threshold {})", s.rule.condition.describe(), value, threshold),
+    }
+}
+
+fn slack_text(n: &Notification) -> String {
+    let icon = if n.status == "firing" { ":rotating_light:" } else { ":white_check_mark:" };
+    let node = n.node_name.as_deref().unwrap_or("unknown node");
+    let node = match &n.cluster_name {
+        Some(cluster) => format!("{}/{}", cluster, node),
+        None => node.to_string(),
+    };
+    format!("{} *{}* {} on {}: {}", icon, n.rule, n.status.to_uppercase(), node, n.summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{extract::Path, routing::post, Json, Router};
+    use serde_json::Value;
+    use tokio::sync::mpsc;
+
+    use super::*;
+
+    /// A local webhook receiver; every POST to `/<name>` arrives on the
+    /// channel as `(name, body)`.
+    async fn receiver() -> (String, mpsc::UnboundedReceiver<(String, Value)>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let app = Router::new().route("/:name", post(move |Path(name): Path<String>, Json(body): Json<Value>| {
+            let tx = tx.clone();
+            async move {
+                let _ = tx.send((name, body));
+            }
+        }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (base, rx)
+    }
+
+    /// The next notification to each of the two webhooks, by name.
+    async fn next_pair(rx: &mut mpsc::UnboundedReceiver<(String, Value)>) -> (Value, Value) {
+        let mut generic = None;
+        let mut slack = None;
+        while generic.is_none() || slack.is_none() {
+            let (name, body) = tokio::time::timeout(Duration::from_secs(5), rx.recv()).await.expect("no notification").unwrap();
+            match name.as_str() {
+                "generic" => generic = Some(body),
+                "slack" => slack = Some(body),
+                other => panic!("unexpected webhook {}", other),
+            }
+        }
+        (generic.unwrap(), slack.unwrap())
+    }
+
+    async fn assert_quiet(rx: &mut mpsc::UnboundedReceiver<(String, Value)>) {
+        if let Ok(Some((name, body))) = tokio::time::timeout(Duration::from_millis(300), rx.recv()).await {
+            panic!("unexpected notification to {}: {}", name, body);
+        }
+    }
+
+    fn fill(percent: f64) -> ConntrackStats {
+        ConntrackStats { fill_percent: Some(percent), ..Default::default() }
+    }
+
+    fn engine(base: &str) -> AlertEngine {
+        let config: AlertConfig = serde_json::from_value(json!({
+            "webhooks": [
+                { "url": format!("{}/generic", base) },
+                { "url": format!("{}/slack", base), "format": "slack" },
+            ],
+            "rules": [{ "name": "table-full", "kind": "conntrack_fill", "above_percent": 50.0, "for_secs": 10, "repeat_secs": 30 }],
+        }))
+        .unwrap();
+        AlertEngine::new(config, Some("node-a".into()))
+    }
+
+    #[tokio::test]
+    async fn fires_after_for_secs_repeats_and_resolves() {
+        let (base, mut rx) = receiver().await;
+        let mut engine = engine(&base);
+
+        // Holding, but not yet for for_secs.
+        engine.evaluate(1000, &[], &fill(60.0));
+        assert_eq!(engine.statuses()[0].state, AlertState::Pending);
+        engine.evaluate(1005, &[], &fill(70.0));
+        assert_eq!(engine.statuses()[0].state, AlertState::Pending);
+        assert_quiet(&mut rx).await;
+
+        engine.evaluate(1010, &[], &fill(75.0));
+        assert_eq!(engine.statuses()[0].state, AlertState::Firing);
+        let (generic, slack) = next_pair(&mut rx).await;
+        assert_eq!(generic["rule"], "table-full");
+        assert_eq!(generic["status"], "firing");
+        assert_eq!(generic["node_name"], "node-a");
+        assert_eq!(generic["value"], 75.0);
+        assert_eq!(generic["threshold"], 50.0);
+        assert_eq!(generic["since"], 1000);
+        assert_eq!(generic["at"], 1010);
+        assert_eq!(generic["summary"], "conntrack table fill (%) is 75.0 (threshold 50)");
+        let text = slack["text"].as_str().unwrap();
+        assert_eq!(slack.as_object().unwrap().len(), 1, "slack payload is just text");
+        assert!(text.contains("*table-full* FIRING on node-a"), "{}", text);
+
+        // Still firing inside repeat_secs: no duplicate.
+        engine.evaluate(1020, &[], &fill(75.0));
+        assert_quiet(&mut rx).await;
+
+        // repeat_secs after the last notification.
+        engine.evaluate(1040, &[], &fill(80.0));
+        let (generic, _) = next_pair(&mut rx).await;
+        assert_eq!(generic["status"], "firing");
+        assert_eq!(generic["at"], 1040);
+        assert_eq!(generic["since"], 1000);
+
+        engine.evaluate(1042, &[], &fill(10.0));
+        assert_eq!(engine.statuses()[0].state, AlertState::Ok);
+        let (generic, slack) = next_pair(&mut rx).await;
+        assert_eq!(generic["status"], "resolved");
+        assert_eq!(generic["value"], 10.0);
+        assert!(slack["text"].as_str().unwrap().contains("RESOLVED"));
+
+        // Resolved stays quiet.
+        engine.evaluate(1044, &[], &fill(10.0));
+        assert_quiet(&mut rx).await;
+    }
+
+    #[tokio::test]
+    async fn condition_that_stops_holding_while_pending_never_notifies() {
+        let (base, mut rx) = receiver().await;
+        let mut engine = engine(&base);
+        engine.evaluate(1000, &[], &fill(60.0));
+        engine.evaluate(1005, &[], &fill(40.0));
+        assert_eq!(engine.statuses()[0].state, AlertState::Ok);
+        // The hold restarts from scratch.
+        engine.evaluate(1012, &[], &fill(60.0));
+        assert_eq!(engine.statuses()[0].state, AlertState::Pending);
+        assert_eq!(engine.statuses()[0].active_since, Some(1012));
+        assert_quiet(&mut rx).await;
+    }
+}
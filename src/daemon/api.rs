@@ -0,0 +1,1114 @@
+use std::net::SocketAddr;
+
+use axum::{extract::{ConnectInfo, Path, Query, State}, http::{HeaderMap, StatusCode}, routing::get, Json, Router};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::daemon::{unix_now, AppState};
+use crate::daemon::admin::AuditRecord;
+use crate::daemon::aggregate::{aggregate, FlowAggregate};
+use crate::daemon::alerts::AlertStatus;
+use crate::daemon::conntrack::{protocol_number, Connection};
+use crate::daemon::expect::Expectation;
+use crate::daemon::history::Snapshot;
+use crate::daemon::info::{accounting_status, collect_node_info, Accounting, NodeInfo};
+use crate::daemon::ipvs::{count_flows, read_virtual_services, VirtualService};
+use crate::daemon::netlink::{delete_conntrack, Tuple};
+use crate::daemon::sockets::Listener;
+use crate::daemon::stats::ConntrackStats;
+
+/// The API version served under `/v1`. Bump this (and keep the old routes
+/// mounted) whenever a response shape changes incompatibly.
+pub const API_VERSION: &str = "v1";
+pub const SUPPORTED_API_VERSIONS: &[&str] = &[API_VERSION];
+
+/// Optional features this daemon build serves, reported by `/info` so the
+/// CLI can tell a missing feature apart from a broken one.
+pub const CAPABILITIES: &[&str] = &["connections", "info", "stats", "expectations", "history", "alerts", "listeners", "ip_class", "aggregates", "mesh"];
+
+pub fn router(state: AppState) -> Router {
+    let v1 = Router::new()
+        .route("/connections", get(list_connections))
+        .route("/connections/:id", get(connection_history).delete(delete_connection))
+        .route("/aggregates", get(list_aggregates))
+        .route("/history", get(history))
+        .route("/info", get(info))
+        .route("/stats", get(stats))
+        .route("/expectations", get(expectations))
+        .route("/alerts", get(alerts))
+        .route("/listeners", get(listeners))
+        .route("/ipvs", get(ipvs))
+        .route("/openapi.json", get(openapi));
+
+    Router::new()
+        .route("/version", get(version))
+        // Unversioned alias so CLIs that predate /v1 keep working.
+        .route("/connections", get(list_connections))
+        .nest(&format!("/{}", API_VERSION), v1)
+        .with_state(state)
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConnectionsResponse {
+    pub node_name: Option<String>,
+    pub accounting: Accounting,
+    pub connections: Vec<Connection>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct VersionResponse {
+    pub daemon_version: String,
+    pub api_versions: Vec<String>,
+    pub target: String,
+    pub node_name: Option<String>,
+}
+
+async fn list_connections(
+    State(state): State<AppState>,
+) -> Json<ConnectionsResponse> {
+    let snapshot = state.connections.read().await;
+    Json(ConnectionsResponse {
+        node_name: state.node_name.clone(),
+        accounting: accounting_status(),
+        connections: snapshot.clone(),
+    })
+}
+
+#[derive(Debug, Serialize)]
+pub struct AggregatesResponse {
+    pub node_name: Option<String>,
+    pub accounting: Accounting,
+    pub aggregates: Vec<FlowAggregate>,
+}
+
+async fn list_aggregates(State(state): State<AppState>) -> Json<AggregatesResponse> {
+    let snapshot = state.connections.read().await;
+    Json(AggregatesResponse {
+        node_name: state.node_name.clone(),
+        accounting: accounting_status(),
+        aggregates: aggregate(&snapshot),
+    })
+}
+
+/// Time window for history queries, in unix seconds. `from` defaults to the
+/// start of retained history, `to` to now, and `step` to every sample.
+/// `/history` returns at most `limit` snapshots per response.
+#[derive(Debug, Deserialize)]
+pub struct HistoryQuery {
+    pub from: Option<u64>,
+    pub to: Option<u64>,
+    pub step: Option<u64>,
+    pub limit: Option<usize>,
+}
+
+/// Snapshots per `/history` response when `limit` isn't given, and the most
+/// a client may ask for. A snapshot holds a whole connection table.
+const HISTORY_PAGE: usize = 10;
+const HISTORY_PAGE_MAX: usize = 100;
+
+impl HistoryQuery {
+    fn window(&self) -> (u64, u64, u64) {
+        (self.from.unwrap_or(0), self.to.unwrap_or_else(unix_now), self.step.unwrap_or(0))
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct HistoryResponse {
+    pub node_name: Option<String>,
+    pub snapshots: Vec<Snapshot>,
+    /// Pass as `from` to fetch the next page; absent on the last one.
+    pub next_from: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConnectionHistory {
+    pub id: String,
+    pub node_name: Option<String>,
+    pub current: Option<Connection>,
+    pub samples: Vec<ConnectionSample>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConnectionSample {
+    pub at: u64,
+    pub state: String,
+    pub bytes: u64,
+    pub throughput_bytes_per_sec: u64,
+}
+
+async fn history(
+    State(state): State<AppState>,
+    Query(q): Query<HistoryQuery>,
+) -> Json<HistoryResponse> {
+    let (from, to, step) = q.window();
+    let limit = q.limit.unwrap_or(HISTORY_PAGE).clamp(1, HISTORY_PAGE_MAX);
+    let mut snapshots = state.history.read().await.range(from, to, step);
+    let next_from = snapshots.get(limit).map(|s| s.at);
+    snapshots.truncate(limit);
+    Json(HistoryResponse { node_name: state.node_name.clone(), snapshots, next_from })
+}
+
+async fn connection_history(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(q): Query<HistoryQuery>,
+) -> Result<Json<ConnectionHistory>, StatusCode> {
+    let (from, to, step) = q.window();
+    let current = state.connections.read().await.iter().find(|c| c.id == id).cloned();
+    let samples: Vec<ConnectionSample> = state.history.read().await
+        .range(from, to, step)
+        .iter()
+        .filter_map(|s| {
+            let c = s.connections.iter().find(|c| c.id == id)?;
+            Some(ConnectionSample {
+                at: s.at,
+                state: c.state.clone(),
+                bytes: c.bytes,
+                throughput_bytes_per_sec: c.throughput_bytes_per_sec,
+            })
+        })
+        .collect();
+
+    if current.is_none() && samples.is_empty() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    Ok(Json(ConnectionHistory { id, node_name: state.node_name.clone(), current, samples }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeleteQuery {
+    /// Report what would be deleted without touching the conntrack table.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DeleteResponse {
+    pub node_name: Option<String>,
+    pub dry_run: bool,
+    pub matched: Vec<Connection>,
+    pub deleted: usize,
+    pub errors: Vec<String>,
+}
+
+/// Deletes the conntrack entry behind a connection via netlink. Part of the
+/// opt-in admin API: refused unless an admin token is configured and
+/// presented, and audited either way.
+async fn delete_connection(
+    State(state): State<AppState>,
+    ConnectInfo(remote): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Query(q): Query<DeleteQuery>,
+) -> Result<(StatusCode, Json<DeleteResponse>), (StatusCode, String)> {
+    let Some(admin) = state.admin.as_deref() else {
+        return Err((StatusCode::FORBIDDEN, "admin API is disabled on this daemon; set KFLOW_ADMIN_TOKEN to enable it".into()));
+    };
+    let mut audit = AuditRecord {
+        at: unix_now(),
+        action: "delete_connection".into(),
+        target: id.clone(),
+        remote_addr: remote.to_string(),
+        user: headers.get("x-kflow-user").and_then(|v| v.to_str().ok()).map(|u| u.to_string()),
+        dry_run: q.dry_run,
+        outcome: "denied".into(),
+        matched: 0,
+        deleted: 0,
+        errors: Vec::new(),
+    };
+    if !admin.authorized(&headers) {
+        admin.audit(&audit);
+        return Err((StatusCode::UNAUTHORIZED, "missing or invalid admin token".into()));
+    }
+
+    let matched: Vec<Connection> = state.connections.read().await.iter().filter(|c| c.id == id).cloned().collect();
+    audit.matched = matched.len();
+    if matched.is_empty() {
+        audit.outcome = "not_found".into();
+        admin.audit(&audit);
+        return Err((StatusCode::NOT_FOUND, format!("no current connection with id {}", id)));
+    }
+
+    let mut resp = DeleteResponse { node_name: state.node_name.clone(), dry_run: q.dry_run, matched, deleted: 0, errors: Vec::new() };
+    if !q.dry_run {
+        // Netlink requests act on the daemon's own namespace only.
+        let own = crate::daemon::netns::own_netns();
+        let (local, foreign): (Vec<&Connection>, Vec<&Connection>) =
+            resp.matched.iter().partition(|c| c.netns.is_none() || c.netns == own);
+        for c in foreign {
+            resp.errors.push(format!("{} is in network namespace {}; only the daemon's own namespace can be changed", c.id, c.netns.unwrap_or(0)));
+        }
+        let tuples: Vec<Tuple> = local.into_iter().map(|c| Tuple {
+            proto: protocol_number(&c.proto),
+            src_ip: c.src_ip,
+            src_port: c.src_port,
+            dst_ip: c.dst_ip,
+            dst_port: c.dst_port,
+            zone: c.zone,
+        }).collect();
+        let results = tokio::task::spawn_blocking(move || tuples.iter().map(delete_conntrack).collect::<Vec<_>>())
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        for r in results {
+            match r {
+                Ok(true) => resp.deleted += 1,
+                // Already gone: nothing left to flush.
+                Ok(false) => {}
+                Err(e) => resp.errors.push(e.to_string()),
+            }
+        }
+    }
+
+    audit.deleted = resp.deleted;
+    audit.errors = resp.errors.clone();
+    audit.outcome = if resp.errors.is_empty() { "ok" } else { "error" }.into();
+    admin.audit(&audit);
+    let status = if resp.errors.is_empty() { StatusCode::OK } else { StatusCode::INTERNAL_SERVER_ERROR };
+    Ok((status, Json(resp)))
+}
+
+async fn version(State(state): State<AppState>) -> Json<VersionResponse> {
+    Json(VersionResponse {
+        daemon_version: env!("CARGO_PKG_VERSION").to_string(),
+        api_versions: SUPPORTED_API_VERSIONS.iter().map(|v| v.to_string()).collect(),
+        target: format!("{}/{}", std::env::consts::OS, std::env::consts::ARCH),
+        node_name: state.node_name.clone(),
+    })
+}
+
+async fn info(State(state): State<AppState>) -> Json<NodeInfo> {
+    Json(collect_node_info(&state))
+}
+
+async fn stats(State(state): State<AppState>) -> Json<ConntrackStats> {
+    Json(state.stats.read().await.clone())
+}
+
+async fn expectations(State(state): State<AppState>) -> Json<Vec<Expectation>> {
+    Json(state.expectations.read().await.clone())
+}
+
+async fn alerts(State(state): State<AppState>) -> Json<Vec<AlertStatus>> {
+    Json(state.alerts.read().await.clone())
+}
+
+async fn listeners(State(state): State<AppState>) -> Json<Vec<Listener>> {
+    Json(state.listeners.read().await.clone())
+}
+
+#[derive(Debug, Serialize)]
+pub struct IpvsResponse {
+    pub node_name: Option<String>,
+    pub virtual_services: Vec<VirtualService>,
+}
+
+/// The node's IPVS virtual services with their real servers; empty unless
+/// the `ipvs` capability is present.
+async fn ipvs(State(state): State<AppState>) -> Json<IpvsResponse> {
+    let mut virtual_services = read_virtual_services();
+    count_flows(&mut virtual_services, &state.connections.read().await);
+    Json(IpvsResponse { node_name: state.node_name.clone(), virtual_services })
+}
+
+async fn openapi() -> Json<Value> {
+    Json(openapi_document())
+}
+
+/// Builds the OpenAPI 3 description of the API. It is written by hand, not
+/// derived from the handlers: a route added to `router()` must be added here
+/// too, which `every_route_is_documented` checks, and a changed response
+/// type must be mirrored in its schema, which the `*_match_schema` tests
+/// check.
+pub fn openapi_document() -> Value {
+    let base = format!("/{}", API_VERSION);
+    let mut paths = serde_json::Map::new();
+    paths.insert("/version".into(), json!({
+        "get": {
+            "summary": "Daemon build and supported API versions",
+            "responses": { "200": ok_ref("VersionResponse") }
+        }
+    }));
+    paths.insert(format!("{base}/connections"), json!({
+        "get": {
+            "summary": "Current conntrack snapshot for this node",
+            "responses": { "200": ok_ref("ConnectionsResponse") }
+        }
+    }));
+    paths.insert("/connections".into(), json!({
+        "get": {
+            "summary": "Unversioned alias of /v1/connections for CLIs that predate /v1",
+            "deprecated": true,
+            "responses": { "200": ok_ref("ConnectionsResponse") }
+        }
+    }));
+    paths.insert(format!("{base}/aggregates"), json!({
+        "get": {
+            "summary": "Current connections collapsed into source -> destination, port and protocol",
+            "responses": { "200": ok_ref("AggregatesResponse") }
+        }
+    }));
+    let window = json!([
+        { "name": "from", "in": "query", "required": false, "schema": { "type": "integer", "format": "int64" }, "description": "Unix seconds; defaults to the oldest retained snapshot" },
+        { "name": "to", "in": "query", "required": false, "schema": { "type": "integer", "format": "int64" }, "description": "Unix seconds; defaults to now" },
+        { "name": "step", "in": "query", "required": false, "schema": { "type": "integer", "format": "int64" }, "description": "Minimum seconds between returned snapshots" },
+    ]);
+    let mut paged = window.as_array().cloned().unwrap_or_default();
+    paged.push(json!({ "name": "limit", "in": "query", "required": false, "schema": { "type": "integer", "minimum": 1, "maximum": HISTORY_PAGE_MAX, "default": HISTORY_PAGE }, "description": "Most snapshots to return; follow next_from for the rest" }));
+    paths.insert(format!("{base}/history"), json!({
+        "get": {
+            "summary": "Retained connection snapshots, downsampled with age, oldest first",
+            "parameters": paged,
+            "responses": { "200": ok_ref("HistoryResponse") }
+        }
+    }));
+    let mut with_id = window.as_array().cloned().unwrap_or_default();
+    with_id.insert(0, json!({ "name": "id", "in": "path", "required": true, "schema": { "type": "string" } }));
+    paths.insert(format!("{base}/connections/{{id}}"), json!({
+        "get": {
+            "summary": "One connection's current state and its samples over time",
+            "parameters": with_id,
+            "responses": {
+                "200": ok_ref("ConnectionHistory"),
+                "404": { "description": "No such connection in current state or retained history" }
+            }
+        },
+        "delete": {
+            "summary": "Delete the conntrack entry behind a current connection (admin API, opt-in)",
+            "parameters": [
+                { "name": "id", "in": "path", "required": true, "schema": { "type": "string" } },
+                { "name": "dry_run", "in": "query", "schema": { "type": "boolean" }, "description": "Only report what would be deleted" },
+                { "name": "X-Kflow-User", "in": "header", "schema": { "type": "string" }, "description": "Recorded in the audit log" },
+            ],
+            "security": [{ "adminToken": [] }],
+            "responses": {
+                "200": ok_ref("DeleteResponse"),
+                "401": { "description": "Missing or invalid admin token" },
+                "403": { "description": "Admin API disabled on this daemon" },
+                "404": { "description": "No such current connection" },
+                "500": ok_ref("DeleteResponse"),
+            }
+        }
+    }));
+    paths.insert(format!("{base}/info"), json!({
+        "get": {
+            "summary": "Kernel, conntrack configuration and daemon capabilities for this node",
+            "responses": { "200": ok_ref("NodeInfo") }
+        }
+    }));
+    paths.insert(format!("{base}/stats"), json!({
+        "get": {
+            "summary": "Conntrack table fill and kernel conntrack counters with per-second rates",
+            "responses": { "200": ok_ref("ConntrackStats") }
+        }
+    }));
+    paths.insert(format!("{base}/expectations"), json!({
+        "get": {
+            "summary": "Conntrack expectation table, linked to master connections where possible",
+            "responses": { "200": ok_array("Expectation") }
+        }
+    }));
+    paths.insert(format!("{base}/listeners"), json!({
+        "get": {
+            "summary": "Listening TCP and unconnected UDP sockets on this node, with owning process where resolvable",
+            "responses": { "200": ok_array("Listener") }
+        }
+    }));
+    paths.insert(format!("{base}/ipvs"), json!({
+        "get": {
+            "summary": "IPVS virtual services (kube-proxy IPVS mode) with real servers, IPVS connection counts and joined conntrack flows",
+            "responses": { "200": ok_ref("IpvsResponse") }
+        }
+    }));
+    paths.insert(format!("{base}/alerts"), json!({
+        "get": {
+            "summary": "Alert rules configured on this node and their current state",
+            "responses": { "200": ok_array("AlertStatus") }
+        }
+    }));
+    paths.insert(format!("{base}/openapi.json"), json!({
+        "get": {
+            "summary": "This document",
+            "responses": { "200": { "description": "OpenAPI 3 document" } }
+        }
+    }));
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "kflow daemon API",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "paths": paths,
+        "components": {
+            "schemas": schemas(),
+            "securitySchemes": { "adminToken": { "type": "http", "scheme": "bearer" } },
+        },
+    })
+}
+
+fn ok_ref(schema: &str) -> Value {
+    json!({
+        "description": "OK",
+        "content": { "application/json": { "schema": { "$ref": format!("#/components/schemas/{schema}") } } }
+    })
+}
+
+fn ok_array(schema: &str) -> Value {
+    json!({
+        "description": "OK",
+        "content": { "application/json": { "schema": {
+            "type": "array",
+            "items": { "$ref": format!("#/components/schemas/{schema}") }
+        } } }
+    })
+}
+
+fn schemas() -> Value {
+    let mut schemas = json!({
+        "VersionResponse": {
+            "type": "object",
+            "required": ["daemon_version", "api_versions", "target"],
+            "properties": {
+                "daemon_version": { "type": "string" },
+                "api_versions": { "type": "array", "items": { "type": "string" } },
+                "target": { "type": "string" },
+                "node_name": { "type": "string", "nullable": true },
+            }
+        },
+        "ConnectionsResponse": {
+            "type": "object",
+            "required": ["accounting", "connections"],
+            "properties": {
+                "node_name": { "type": "string", "nullable": true },
+                "accounting": { "type": "string", "enum": ["enabled", "disabled", "unknown"] },
+                "connections": { "type": "array", "items": { "$ref": "#/components/schemas/Connection" } },
+            }
+        },
+        "NodeInfo": {
+            "type": "object",
+            "required": ["daemon_version", "capabilities", "conntrack"],
+            "properties": {
+                "node_name": { "type": "string", "nullable": true },
+                "kernel_version": { "type": "string", "nullable": true },
+                "daemon_version": { "type": "string" },
+                "capabilities": { "type": "array", "items": { "type": "string" } },
+                "conntrack": { "$ref": "#/components/schemas/ConntrackInfo" },
+            }
+        },
+        "ConntrackInfo": {
+            "type": "object",
+            "required": ["requested_path", "candidates"],
+            "properties": {
+                "requested_path": { "type": "string" },
+                "resolved_path": { "type": "string", "nullable": true },
+                "candidates": { "type": "array", "items": { "$ref": "#/components/schemas/CandidateProbe" } },
+                "acct": { "type": "boolean", "nullable": true },
+                "timestamp": { "type": "boolean", "nullable": true },
+                "max": { "type": "integer", "format": "int64", "nullable": true },
+                "count": { "type": "integer", "format": "int64", "nullable": true },
+            }
+        },
+        "CandidateProbe": {
+            "type": "object",
+            "required": ["path", "exists", "has_content"],
+            "properties": {
+                "path": { "type": "string" },
+                "exists": { "type": "boolean" },
+                "has_content": { "type": "boolean" },
+                "error": { "type": "string", "nullable": true },
+            }
+        },
+        "ConntrackStats": {
+            "type": "object",
+            "required": ["sampled_at", "counters", "rates_per_sec"],
+            "properties": {
+                "sampled_at": { "type": "integer", "format": "int64", "description": "Unix seconds" },
+                "count": { "type": "integer", "format": "int64", "nullable": true },
+                "max": { "type": "integer", "format": "int64", "nullable": true },
+                "fill_percent": { "type": "number", "nullable": true },
+                "counters": { "type": "object", "additionalProperties": { "type": "integer", "format": "int64" } },
+                "rates_per_sec": { "type": "object", "additionalProperties": { "type": "number" } },
+            }
+        },
+        "HistoryResponse": {
+            "type": "object",
+            "required": ["snapshots"],
+            "properties": {
+                "node_name": { "type": "string", "nullable": true },
+                "snapshots": { "type": "array", "items": {
+                    "type": "object",
+                    "required": ["at", "connections"],
+                    "properties": {
+                        "at": { "type": "integer", "format": "int64", "description": "Unix seconds" },
+                        "connections": { "type": "array", "items": { "$ref": "#/components/schemas/Connection" } },
+                    }
+                } },
+                "next_from": { "type": "integer", "format": "int64", "nullable": true, "description": "Pass as from to fetch the next page; absent on the last one" },
+            }
+        },
+        "ConnectionHistory": {
+            "type": "object",
+            "required": ["id", "samples"],
+            "properties": {
+                "id": { "type": "string" },
+                "node_name": { "type": "string", "nullable": true },
+                "current": { "allOf": [{ "$ref": "#/components/schemas/Connection" }], "nullable": true },
+                "samples": { "type": "array", "items": {
+                    "type": "object",
+                    "required": ["at", "state", "bytes", "throughput_bytes_per_sec"],
+                    "properties": {
+                        "at": { "type": "integer", "format": "int64" },
+                        "state": { "type": "string" },
+                        "bytes": { "type": "integer", "format": "int64" },
+                        "throughput_bytes_per_sec": { "type": "integer", "format": "int64" },
+                    }
+                } },
+            }
+        },
+        "Expectation": {
+            "type": "object",
+            "required": ["proto", "src_ip", "src_port", "dst_ip", "dst_port", "flags"],
+            "properties": {
+                "timeout_secs": { "type": "integer", "format": "int64", "nullable": true },
+                "proto": { "type": "string" },
+                "src_ip": { "type": "string" },
+                "src_port": { "type": "integer", "format": "int32" },
+                "dst_ip": { "type": "string" },
+                "dst_port": { "type": "integer", "format": "int32" },
+                "flags": { "type": "array", "items": { "type": "string" } },
+                "helper": { "type": "string", "nullable": true },
+                "class": { "type": "string", "nullable": true },
+                "master": { "$ref": "#/components/schemas/FlowRef" },
+            }
+        },
+        "FlowRef": {
+            "type": "object",
+            "nullable": true,
+            "required": ["proto", "src_ip", "src_port", "dst_ip", "dst_port"],
+            "properties": {
+                "proto": { "type": "string" },
+                "src_ip": { "type": "string" },
+                "src_port": { "type": "integer", "format": "int32" },
+                "dst_ip": { "type": "string" },
+                "dst_port": { "type": "integer", "format": "int32" },
+            }
+        },
+        "Listener": {
+            "type": "object",
+            "required": ["proto", "address", "port", "inode", "uid"],
+            "properties": {
+                "proto": { "type": "string" },
+                "address": { "type": "string" },
+                "port": { "type": "integer", "format": "int32" },
+                "inode": { "type": "integer", "format": "int64" },
+                "uid": { "type": "integer", "format": "int32" },
+                "process": { "allOf": [{ "$ref": "#/components/schemas/ProcessRef" }], "nullable": true },
+            }
+        },
+        "IpClass": {
+            "type": "string",
+            "enum": ["pod", "service", "node", "loopback", "link_local", "multicast", "private", "public"],
+            "description": "Where an address sits relative to the cluster; cluster ranges come from KFLOW_POD_CIDRS, KFLOW_SERVICE_CIDRS and KFLOW_NODE_IPS",
+        },
+        "ProcessRef": {
+            "type": "object",
+            "required": ["pid", "comm", "cmdline"],
+            "properties": {
+                "pid": { "type": "integer", "format": "int32" },
+                "comm": { "type": "string" },
+                "cmdline": { "type": "string", "description": "Arguments joined with spaces" },
+            }
+        },
+        "TcpInfo": {
+            "type": "object",
+            "required": ["rtt_us", "rttvar_us", "retrans", "total_retrans", "cwnd", "unacked"],
+            "properties": {
+                "rtt_us": { "type": "integer", "format": "int32", "description": "Smoothed round-trip time, microseconds" },
+                "rttvar_us": { "type": "integer", "format": "int32" },
+                "retrans": { "type": "integer", "format": "int32", "description": "Segments retransmitted and not yet acknowledged" },
+                "total_retrans": { "type": "integer", "format": "int32" },
+                "cwnd": { "type": "integer", "format": "int32", "description": "Congestion window, segments" },
+                "unacked": { "type": "integer", "format": "int32" },
+                "pacing_rate": { "type": "integer", "format": "int64", "nullable": true, "description": "Bytes per second" },
+            }
+        },
+        "DeleteResponse": {
+            "type": "object",
+            "required": ["dry_run", "matched", "deleted", "errors"],
+            "properties": {
+                "node_name": { "type": "string", "nullable": true },
+                "dry_run": { "type": "boolean" },
+                "matched": { "type": "array", "items": { "$ref": "#/components/schemas/Connection" } },
+                "deleted": { "type": "integer", "format": "int32" },
+                "errors": { "type": "array", "items": { "type": "string" } },
+            }
+        },
+        "AlertStatus": {
+            "type": "object",
+            "required": ["rule", "state"],
+            "properties": {
+                "rule": { "$ref": "#/components/schemas/AlertRule" },
+                "state": { "type": "string", "enum": ["ok", "pending", "firing"] },
+                "value": { "type": "number", "nullable": true },
+                "active_since": { "type": "integer", "format": "int64", "nullable": true, "description": "Unix seconds" },
+                "last_notified": { "type": "integer", "format": "int64", "nullable": true, "description": "Unix seconds" },
+            }
+        },
+        "AlertRule": {
+            "type": "object",
+            "required": ["name", "kind", "for_secs"],
+            "properties": {
+                "name": { "type": "string" },
+                "kind": { "type": "string", "enum": ["cidr", "port_throughput", "state_count", "conntrack_fill"] },
+                "cidr": { "type": "string" },
+                "port": { "type": "integer", "format": "int32" },
+                "state": { "type": "string" },
+                "above": { "type": "integer", "format": "int64" },
+                "above_percent": { "type": "number" },
+                "for_secs": { "type": "integer", "format": "int64" },
+                "repeat_secs": { "type": "integer", "format": "int64", "nullable": true },
+            }
+        },
+    });
+    schemas["Connection"] = connection_schema();
+    schemas["IpvsResponse"] = json!({
+        "type": "object",
+        "required": ["virtual_services"],
+        "properties": {
+            "node_name": { "type": "string", "nullable": true },
+            "virtual_services": { "type": "array", "items": { "$ref": "#/components/schemas/VirtualService" } },
+        }
+    });
+    schemas["VirtualService"] = json!({
+        "type": "object",
+        "required": ["proto", "scheduler", "real_servers"],
+        "properties": {
+            "proto": { "type": "string", "enum": ["tcp", "udp", "sctp", "fwm"] },
+            "address": { "type": "string", "nullable": true },
+            "port": { "type": "integer", "format": "int32", "nullable": true },
+            "fwmark": { "type": "integer", "format": "int64", "nullable": true, "description": "Firewall mark, for fwm services" },
+            "scheduler": { "type": "string" },
+            "flags": { "type": "string", "nullable": true, "description": "e.g. persistent 10800" },
+            "real_servers": { "type": "array", "items": { "$ref": "#/components/schemas/RealServer" } },
+        }
+    });
+    schemas["RealServer"] = json!({
+        "type": "object",
+        "required": ["address", "port", "forward", "weight", "active_conns", "inactive_conns", "flows"],
+        "properties": {
+            "address": { "type": "string" },
+            "port": { "type": "integer", "format": "int32" },
+            "forward": { "type": "string", "description": "Masq, Route, Tunnel or Local" },
+            "weight": { "type": "integer", "format": "int64" },
+            "active_conns": { "type": "integer", "format": "int64" },
+            "inactive_conns": { "type": "integer", "format": "int64" },
+            "flows": { "type": "integer", "format": "int32", "description": "Current conntrack flows joined to this real server" },
+        }
+    });
+    schemas["IpvsRef"] = json!({
+        "type": "object",
+        "required": ["real_ip", "real_port", "state"],
+        "properties": {
+            "real_ip": { "type": "string" },
+            "real_port": { "type": "integer", "format": "int32" },
+            "state": { "type": "string", "description": "IPVS connection state, e.g. ESTABLISHED, FIN_WAIT, UDP" },
+        }
+    });
+    schemas["MeshInfo"] = json!({
+        "type": "object",
+        "required": ["mesh", "leg"],
+        "properties": {
+            "mesh": { "type": "string", "enum": ["istio", "linkerd"] },
+            "leg": {
+                "type": "string",
+                "enum": ["outbound", "inbound", "upstream", "loopback"],
+                "description": "outbound: the app's connection redirected into the sidecar; inbound: a peer's connection redirected into the sidecar; upstream: the sidecar's own connection to its chosen destination; loopback: a hop between sidecar and app",
+            },
+        }
+    });
+    schemas["ReplyTuple"] = json!({
+        "type": "object",
+        "required": ["src_ip", "src_port", "dst_ip", "dst_port"],
+        "properties": {
+            "src_ip": { "type": "string" },
+            "src_port": { "type": "integer", "format": "int32" },
+            "dst_ip": { "type": "string" },
+            "dst_port": { "type": "integer", "format": "int32" },
+        }
+    });
+    schemas["AggregatesResponse"] = json!({
+        "type": "object",
+        "required": ["accounting", "aggregates"],
+        "properties": {
+            "node_name": { "type": "string", "nullable": true },
+            "accounting": { "type": "string", "enum": ["enabled", "disabled", "unknown"] },
+            "aggregates": { "type": "array", "items": { "$ref": "#/components/schemas/FlowAggregate" } },
+        }
+    });
+    schemas["FlowEnd"] = json!({
+        "type": "object",
+        "required": ["kind"],
+        "description": "The local pod when process attribution found it, the address otherwise",
+        "properties": {
+            "kind": { "type": "string", "enum": ["pod", "ip"] },
+            "pod_uid": { "type": "string" },
+            "ip": { "type": "string" },
+        }
+    });
+    schemas["FlowAggregate"] = json!({
+        "type": "object",
+        "required": ["src", "dst", "dst_port", "proto", "src_class", "dst_class", "connections", "bytes", "packets", "throughput_bytes_per_sec", "src_ips", "dst_ips", "states"],
+        "properties": {
+            "src": { "$ref": "#/components/schemas/FlowEnd" },
+            "dst": { "$ref": "#/components/schemas/FlowEnd" },
+            "dst_port": { "type": "integer", "format": "int32" },
+            "proto": { "type": "string" },
+            "src_class": { "$ref": "#/components/schemas/IpClass" },
+            "dst_class": { "$ref": "#/components/schemas/IpClass" },
+            "connections": { "type": "integer", "format": "int32" },
+            "bytes": { "type": "integer", "format": "int64" },
+            "packets": { "type": "integer", "format": "int64" },
+            "throughput_bytes_per_sec": { "type": "integer", "format": "int64" },
+            "src_ips": { "type": "array", "items": { "type": "string" } },
+            "dst_ips": { "type": "array", "items": { "type": "string" } },
+            "states": { "type": "object", "additionalProperties": { "type": "integer", "format": "int32" }, "description": "Connections per conntrack state" },
+        }
+    });
+    schemas
+}
+
+/// The `Connection` schema, kept apart from `schemas()` so that one `json!`
+/// literal stays within the macro recursion limit.
+fn connection_schema() -> Value {
+    json!({
+        "type": "object",
+        "required": ["id", "proto", "src_ip", "src_port", "dst_ip", "dst_port", "state", "bytes", "packets", "throughput_bytes_per_sec", "start_source", "src_class", "dst_class", "mark_labels"],
+        "properties": {
+            "id": { "type": "string" },
+            "proto": { "type": "string" },
+            "src_ip": { "type": "string" },
+            "src_port": { "type": "integer", "format": "int32" },
+            "dst_ip": { "type": "string" },
+            "dst_port": { "type": "integer", "format": "int32" },
+            "state": { "type": "string" },
+            "bytes": { "type": "integer", "format": "int64" },
+            "packets": { "type": "integer", "format": "int64" },
+            "throughput_bytes_per_sec": { "type": "integer", "format": "int64" },
+            "age_secs": { "type": "integer", "format": "int64", "nullable": true },
+            "started_at": { "type": "integer", "format": "int64", "nullable": true, "description": "Unix seconds" },
+            "start_source": { "type": "string", "enum": ["kernel", "first_seen"] },
+            "role": { "type": "string", "enum": ["server", "client"], "nullable": true, "description": "This node's side of the flow, when a local socket explains it" },
+            "tcp_info": { "allOf": [{ "$ref": "#/components/schemas/TcpInfo" }], "nullable": true, "description": "Kernel TCP diagnostics for the local socket; only with the tcp_info capability" },
+            "process": { "allOf": [{ "$ref": "#/components/schemas/ProcessRef" }], "nullable": true, "description": "Local process holding the flow's socket; only with the processes capability" },
+            "container_id": { "type": "string", "nullable": true, "description": "Container of that process (containerd, CRI-O or Docker cgroup layouts)" },
+            "pod_uid": { "type": "string", "nullable": true, "description": "Kubernetes pod UID of that process" },
+            "netns": { "type": "integer", "format": "int64", "nullable": true, "description": "Inode of the network namespace the flow was read from; only with the netns capability" },
+            "src_class": { "$ref": "#/components/schemas/IpClass" },
+            "dst_class": { "$ref": "#/components/schemas/IpClass" },
+            "reply": { "allOf": [{ "$ref": "#/components/schemas/ReplyTuple" }], "nullable": true, "description": "Reply direction, present when the flow was NATed; after a Service DNAT, src_ip is the backend" },
+            "ipvs": { "allOf": [{ "$ref": "#/components/schemas/IpvsRef" }], "nullable": true, "description": "IPVS real server the flow was scheduled to; only with the ipvs capability" },
+            "mesh": { "allOf": [{ "$ref": "#/components/schemas/MeshInfo" }], "nullable": true, "description": "Sidecar proxy hop this flow is, in an Istio or Linkerd meshed pod" },
+            "mark": { "type": "integer", "format": "int64", "nullable": true, "description": "Connection mark (ctmark)" },
+            "zone": { "type": "integer", "format": "int32", "nullable": true, "description": "Conntrack zone" },
+            "mark_labels": { "type": "array", "items": { "type": "string" }, "description": "Labels from the mark decoders (KFLOW_CNI, KFLOW_MARK_DECODERS) that match mark" },
+            "zone_label": { "type": "string", "nullable": true, "description": "Label the mark decoders give zone" },
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::net::IpAddr;
+
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::daemon::alerts::{AlertState, Rule};
+    use crate::daemon::conntrack::{parse_conntrack_line, CandidateProbe, ReplyTuple, Role};
+    use crate::daemon::expect::MasterFlow;
+    use crate::daemon::history::{History, HistoryConfig};
+    use crate::daemon::info::ConntrackInfo;
+    use crate::daemon::ipvs::{IpvsRef, RealServer};
+    use crate::daemon::mesh::{Mesh, MeshInfo, MeshLeg};
+    use crate::daemon::sockets::ProcessRef;
+    use crate::daemon::tcpinfo::TcpInfo;
+
+    /// Checks `value` against `schema`, following `$ref`s into the document's
+    /// components, and collects every place they disagree, including fields
+    /// the schema doesn't declare.
+    fn check(doc: &Value, schema: &Value, value: &Value, path: &str, errors: &mut Vec<String>) {
+        if let Some(r) = schema["$ref"].as_str() {
+            let name = r.trim_start_matches("#/components/schemas/");
+            match doc["components"]["schemas"].get(name) {
+                Some(target) => check(doc, target, value, path, errors),
+                None => errors.push(format!("{path}: unknown schema {name}")),
+            }
+            return;
+        }
+        if value.is_null() {
+            if schema["nullable"] != json!(true) {
+                errors.push(format!("{path}: null but not nullable"));
+            }
+            return;
+        }
+        if let Some(all) = schema["allOf"].as_array() {
+            for s in all {
+                check(doc, s, value, path, errors);
+            }
+            return;
+        }
+        if let Some(allowed) = schema["enum"].as_array()
+            && !allowed.contains(value)
+        {
+            errors.push(format!("{path}: {value} not in {schema}", schema = schema["enum"]));
+        }
+        let type_ok = match schema["type"].as_str() {
+            Some("string") => value.is_string(),
+            Some("integer") => value.is_i64() || value.is_u64(),
+            Some("number") => value.is_number(),
+            Some("boolean") => value.is_boolean(),
+            Some("array") => {
+                for (i, item) in value.as_array().into_iter().flatten().enumerate() {
+                    check(doc, &schema["items"], item, &format!("{path}[{i}]"), errors);
+                }
+                value.is_array()
+            }
+            Some("object") => {
+                let fields = value.as_object();
+                if let Some(props) = schema["properties"].as_object() {
+                    for (k, v) in fields.into_iter().flatten() {
+                        match props.get(k) {
+                            Some(s) => check(doc, s, v, &format!("{path}.{k}"), errors),
+                            None => errors.push(format!("{path}.{k}: not in the schema")),
+                        }
+                    }
+                }
+                if let Some(extra) = schema.get("additionalProperties") {
+                    for (k, v) in fields.into_iter().flatten() {
+                        check(doc, extra, v, &format!("{path}.{k}"), errors);
+                    }
+                }
+                for req in schema["required"].as_array().into_iter().flatten() {
+                    let req = req.as_str().unwrap_or_default();
+                    if !fields.is_some_and(|f| f.contains_key(req)) {
+                        errors.push(format!("{path}.{req}: required but missing"));
+                    }
+                }
+                value.is_object()
+            }
+            _ => true,
+        };
+        if !type_ok {
+            errors.push(format!("{path}: {value} is not a {}", schema["type"]));
+        }
+    }
+
+    fn assert_matches<T: Serialize>(schema: &str, value: &T) {
+        let doc = openapi_document();
+        let value = serde_json::to_value(value).unwrap();
+        let mut errors = Vec::new();
+        check(&doc, &json!({ "$ref": format!("#/components/schemas/{schema}") }), &value, schema, &mut errors);
+        assert!(errors.is_empty(), "{schema} does not match the OpenAPI document:\n{}", errors.join("\n"));
+    }
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    /// A bare connection as parsed from conntrack.
+    fn bare() -> Connection {
+        parse_conntrack_line("ipv4 2 tcp 6 431999 ESTABLISHED src=10.0.0.1 dst=10.0.0.2 sport=40000 dport=80 packets=3 bytes=180 src=10.0.0.2 dst=10.0.0.1 sport=80 dport=40000 packets=2 bytes=120 [ASSURED] mark=0 use=1").unwrap()
+    }
+
+    /// A connection with every optional field set.
+    fn full() -> Connection {
+        let mut c = parse_conntrack_line("ipv4 2 tcp 6 431999 ESTABLISHED src=10.0.0.1 dst=10.96.0.10 sport=40000 dport=443 packets=3 bytes=180 src=10.244.1.5 dst=10.0.0.1 sport=8443 dport=40000 packets=2 bytes=120 [ASSURED] mark=3840 zone=1 delta-time=12 use=1").unwrap();
+        c.started_at = Some(1_700_000_000);
+        c.role = Some(Role::Client);
+        c.tcp_info = Some(TcpInfo { rtt_us: 250, rttvar_us: 50, retrans: 0, total_retrans: 1, cwnd: 10, unacked: 0, pacing_rate: Some(1_000_000) });
+        c.process = Some(ProcessRef { pid: 42, comm: "curl".into(), cmdline: "curl https://example".into() });
+        c.container_id = Some("abc123".into());
+        c.pod_uid = Some("0f0e0d0c-0000-4000-8000-000000000001".into());
+        c.netns = Some(4026531992);
+        c.reply = Some(ReplyTuple { src_ip: ip("10.244.1.5"), src_port: 8443, dst_ip: ip("10.0.0.1"), dst_port: 40000 });
+        c.ipvs = Some(IpvsRef { real_ip: ip("10.244.1.5"), real_port: 8443, state: "ESTABLISHED".into() });
+        c.mesh = Some(MeshInfo { mesh: Mesh::Istio, leg: MeshLeg::Upstream });
+        c.mark_labels = vec!["cilium: identity 0".into()];
+        c.zone_label = Some("tenant-a".into());
+        c
+    }
+
+    /// `(path, methods)` of every `.route(...)` in `router()`, read from this
+    /// file's source since axum can't list a router's routes.
+    fn registered_routes() -> Vec<(String, Vec<&'static str>)> {
+        let source = include_str!("api.rs");
+        let start = source.find("pub fn router(").unwrap();
+        let body = &source[start..start + source[start..].find("\n}\n").unwrap()];
+        let (v1, root) = body.split_once(';').unwrap();
+        let mut routes = Vec::new();
+        for (prefix, block) in [(format!("/{}", API_VERSION), v1), (String::new(), root)] {
+            for line in block.lines().filter_map(|l| l.trim().strip_prefix(".route(\"")) {
+                let (path, handlers) = line.split_once('"').unwrap();
+                let path = path.split('/').map(|seg| match seg.strip_prefix(':') {
+                    Some(param) => format!("{{{param}}}"),
+                    None => seg.to_string(),
+                });
+                let methods = ["get", "post", "put", "patch", "delete"]
+                    .into_iter()
+                    .filter(|m| handlers.contains(&format!(" {m}(")) || handlers.contains(&format!(".{m}(")))
+                    .collect();
+                routes.push((format!("{prefix}{}", path.collect::<Vec<_>>().join("/")), methods));
+            }
+        }
+        routes
+    }
+
+    #[test]
+    fn every_route_is_documented() {
+        let doc = openapi_document();
+        let routes = registered_routes();
+        assert!(routes.len() >= 12, "{routes:?}");
+        for (path, methods) in &routes {
+            assert!(!methods.is_empty(), "{path}: no handler found");
+            for method in methods {
+                assert!(doc["paths"][path].get(*method).is_some(), "{} {path} is routed but not in the OpenAPI document", method.to_uppercase());
+            }
+        }
+        for path in doc["paths"].as_object().unwrap().keys() {
+            assert!(routes.iter().any(|(p, _)| p == path), "{path} is documented but not routed");
+        }
+    }
+
+    #[test]
+    fn every_referenced_schema_exists() {
+        let doc = openapi_document();
+        let mut errors = Vec::new();
+        let mut stack = vec![("paths".to_string(), &doc["paths"]), ("components".to_string(), &doc["components"])];
+        while let Some((path, v)) = stack.pop() {
+            if let Some(r) = v.get("$ref").and_then(Value::as_str) {
+                let name = r.trim_start_matches("#/components/schemas/");
+                if doc["components"]["schemas"].get(name).is_none() {
+                    errors.push(format!("{path}: unknown schema {name}"));
+                }
+            }
+            match v {
+                Value::Object(m) => stack.extend(m.iter().map(|(k, v)| (format!("{path}.{k}"), v))),
+                Value::Array(a) => stack.extend(a.iter().map(|v| (path.clone(), v))),
+                _ => {}
+            }
+        }
+        assert!(errors.is_empty(), "{}", errors.join("\n"));
+    }
+
+    #[test]
+    fn connection_responses_match_schema() {
+        let connections = vec![bare(), full()];
+        assert_matches("ConnectionsResponse", &ConnectionsResponse {
+            node_name: Some("node-a".into()),
+            accounting: Accounting::Enabled,
+            connections: connections.clone(),
+        });
+        assert_matches("AggregatesResponse", &AggregatesResponse {
+            node_name: None,
+            accounting: Accounting::Unknown,
+            aggregates: aggregate(&connections),
+        });
+        assert_matches("DeleteResponse", &DeleteResponse {
+            node_name: None,
+            dry_run: true,
+            matched: connections.clone(),
+            deleted: 0,
+            errors: vec!["boom".into()],
+        });
+
+        let mut history = History::new(HistoryConfig { retention_secs: 60, full_res_secs: 60, downsample_secs: 1, max_bytes: usize::MAX });
+        history.push(100, connections.clone());
+        let snapshots = history.range(0, 200, 0);
+        assert_matches("HistoryResponse", &HistoryResponse { node_name: None, snapshots, next_from: Some(102) });
+        for current in [None, Some(full())] {
+            assert_matches("ConnectionHistory", &ConnectionHistory {
+                id: full().id,
+                node_name: Some("node-a".into()),
+                current,
+                samples: vec![ConnectionSample { at: 100, state: "ESTABLISHED".into(), bytes: 300, throughput_bytes_per_sec: 10 }],
+            });
+        }
+    }
+
+    #[test]
+    fn node_responses_match_schema() {
+        assert_matches("VersionResponse", &VersionResponse {
+            daemon_version: "0.1.0".into(),
+            api_versions: vec!["v1".into()],
+            target: "linux/x86_64".into(),
+            node_name: None,
+        });
+        assert_matches("NodeInfo", &NodeInfo {
+            node_name: Some("node-a".into()),
+            kernel_version: None,
+            daemon_version: "0.1.0".into(),
+            capabilities: vec!["connections".into()],
+            conntrack: ConntrackInfo {
+                requested_path: "auto".into(),
+                resolved_path: None,
+                candidates: vec![CandidateProbe { path: "/proc/net/nf_conntrack".into(), exists: true, has_content: false, error: Some("read: denied".into()) }],
+                acct: Some(true),
+                timestamp: None,
+                max: Some(262144),
+                count: Some(12),
+            },
+        });
+        assert_matches("ConntrackStats", &ConntrackStats {
+            sampled_at: 100,
+            count: Some(12),
+            max: None,
+            fill_percent: Some(0.5),
+            counters: BTreeMap::from([("drop".to_string(), 3)]),
+            rates_per_sec: BTreeMap::from([("drop".to_string(), 0.5)]),
+        });
+        for master in [None, Some(MasterFlow { proto: "tcp".into(), src_ip: ip("10.0.0.1"), src_port: 40000, dst_ip: ip("10.0.0.2"), dst_port: 21 })] {
+            assert_matches("Expectation", &Expectation {
+                timeout_secs: Some(30),
+                proto: "tcp".into(),
+                src_ip: ip("10.0.0.1"),
+                src_port: 0,
+                dst_ip: ip("10.0.0.2"),
+                dst_port: 50000,
+                flags: vec!["PERMANENT".into()],
+                helper: Some("ftp".into()),
+                class: None,
+                master,
+            });
+        }
+        for process in [None, Some(ProcessRef { pid: 1, comm: "nginx".into(), cmdline: String::new() })] {
+            assert_matches("Listener", &Listener { proto: "tcp".into(), address: ip("::"), port: 80, inode: 1234, uid: 0, process });
+        }
+        assert_matches("IpvsResponse", &IpvsResponse {
+            node_name: None,
+            virtual_services: vec![VirtualService {
+                proto: "tcp".into(),
+                address: Some(ip("10.96.0.10")),
+                port: Some(443),
+                fwmark: None,
+                scheduler: "rr".into(),
+                flags: Some("persistent 10800".into()),
+                real_servers: vec![RealServer { address: ip("10.244.1.5"), port: 8443, forward: "Masq".into(), weight: 1, active_conns: 2, inactive_conns: 0, flows: 1 }],
+            }],
+        });
+    }
+
+    #[test]
+    fn alert_statuses_match_schema() {
+        let rules: Vec<Rule> = serde_json::from_value(json!([
+            { "name": "a", "kind": "cidr", "cidr": "10.0.0.0/8", "above": 10 },
+            { "name": "b", "kind": "port_throughput", "port": 443, "above": 1000, "for_secs": 30, "repeat_secs": 300 },
+            { "name": "c", "kind": "state_count", "state": "SYN_SENT", "above": 5 },
+            { "name": "d", "kind": "conntrack_fill", "above_percent": 80.0 },
+        ]))
+        .unwrap();
+        for rule in rules {
+            assert_matches("AlertStatus", &AlertStatus { rule, state: AlertState::Firing, value: Some(1.5), active_since: Some(100), last_notified: None });
+        }
+    }
+}
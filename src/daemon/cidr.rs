@@ -0,0 +1,128 @@
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+/// An IPv4 or IPv6 network such as `10.0.0.0/8`. A bare address is a
+/// single-host network.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Cidr {
+    pub addr: IpAddr,
+    pub prefix: u8,
+}
+
+impl Cidr {
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        match (self.addr, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(net) & mask == u32::from(*ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(net) & mask == u128::from(*ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix) = match s.split_once('/') {
+            Some((a, p)) => (a, Some(p)),
+            None => (s, None),
+        };
+        let addr: IpAddr = addr.trim().parse().map_err(|e| format!("invalid CIDR '{}': {}", s, e))?;
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(p) => p.trim().parse::<u8>().ok().filter(|p| *p <= max)
+                .ok_or_else(|| format!("invalid CIDR '{}': prefix must be 0-{}", s, max))?,
+            None => max,
+        };
+        Ok(Cidr { addr, prefix })
+    }
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Cidr {
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(d)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl serde::Serialize for Cidr {
+    fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.collect_str(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cidr(s: &str) -> Cidr {
+        s.parse().unwrap()
+    }
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_and_displays() {
+        assert_eq!(cidr("10.244.0.0/16"), Cidr { addr: ip("10.244.0.0"), prefix: 16 });
+        assert_eq!(cidr(" 10.0.0.7 "), Cidr { addr: ip("10.0.0.7"), prefix: 32 });
+        assert_eq!(cidr("fd00::1"), Cidr { addr: ip("fd00::1"), prefix: 128 });
+        assert_eq!(cidr("fd00:10:96::/112").to_string(), "fd00:10:96::/112");
+        for bad in ["10.0.0.0/33", "fd00::/129", "10.0.0.0/", "10.0.0.0/-1", "10.0.0/8", "example.com/8", ""] {
+            assert!(bad.parse::<Cidr>().is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn contains_v4() {
+        let net = cidr("10.96.0.0/12");
+        assert!(net.contains(&ip("10.96.0.1")));
+        assert!(net.contains(&ip("10.111.255.255")));
+        assert!(!net.contains(&ip("10.112.0.0")));
+        assert!(!net.contains(&ip("10.95.255.255")));
+        // Host bits in the network address are ignored.
+        assert!(cidr("10.96.7.7/12").contains(&ip("10.100.0.1")));
+        // The other family never matches, not even a mapped address.
+        assert!(!net.contains(&ip("::ffff:10.96.0.1")));
+    }
+
+    #[test]
+    fn contains_v6() {
+        let net = cidr("fd00:10:244::/56");
+        assert!(net.contains(&ip("fd00:10:244:ff::1")));
+        assert!(!net.contains(&ip("fd00:10:244:100::1")));
+        assert!(!net.contains(&ip("10.244.0.1")));
+    }
+
+    #[test]
+    fn edge_prefixes() {
+        let any4 = cidr("0.0.0.0/0");
+        assert!(any4.contains(&ip("0.0.0.0")));
+        assert!(any4.contains(&ip("255.255.255.255")));
+        assert!(!any4.contains(&ip("::1")));
+        let any6 = cidr("::/0");
+        assert!(any6.contains(&ip("ffff::1")));
+        assert!(!any6.contains(&ip("127.0.0.1")));
+
+        let host4 = cidr("192.168.1.10/32");
+        assert!(host4.contains(&ip("192.168.1.10")));
+        assert!(!host4.contains(&ip("192.168.1.11")));
+        let host6 = cidr("2001:db8::10/128");
+        assert!(host6.contains(&ip("2001:db8::10")));
+        assert!(!host6.contains(&ip("2001:db8::11")));
+    }
+}
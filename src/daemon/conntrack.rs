@@ -0,0 +1,484 @@
+use std::net::IpAddr;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+use serde::Serialize;
+
+use crate::daemon::ipclass::{base_class, IpClass};
+use crate::daemon::ipvs::IpvsRef;
+use crate::daemon::mesh::MeshInfo;
+use crate::daemon::sockets::ProcessRef;
+use crate::daemon::tcpinfo::TcpInfo;
+
+#[derive(Debug, Clone, Serialize, Hash, PartialEq, Eq)]
+pub struct Connection {
+    /// Stable identifier derived from the 5-tuple, usable in `/v1/connections/{id}`.
+    pub id: String,
+    pub proto: String,
+    pub src_ip: IpAddr,
+    pub src_port: u16,
+    pub dst_ip: IpAddr,
+    pub dst_port: u16,
+    pub state: String,
+    pub bytes: u64,
+    pub packets: u64,
+    pub throughput_bytes_per_sec: u64,
+    /// Seconds since the flow started. Exact when `start_source` is `kernel`,
+    /// a lower bound when it is `first_seen`.
+    pub age_secs: Option<u64>,
+    /// Unix time (seconds) the flow started.
+    pub started_at: Option<u64>,
+    pub start_source: StartSource,
+    /// This node's side of the flow, when a local socket explains it.
+    pub role: Option<Role>,
+    /// Kernel TCP diagnostics for the local socket behind the flow, when
+    /// `KFLOW_TCP_INFO` is enabled and this node is an endpoint.
+    pub tcp_info: Option<TcpInfo>,
+    /// The local process holding the flow's socket.
+    pub process: Option<ProcessRef>,
+    /// Container and pod of that process, from its cgroup path.
+    pub container_id: Option<String>,
+    pub pod_uid: Option<String>,
+    /// Inode of the network namespace whose conntrack table the flow came
+    /// from; set when `KFLOW_ALL_NETNS` is enabled.
+    pub netns: Option<u64>,
+    pub src_class: IpClass,
+    pub dst_class: IpClass,
+    /// The reply direction, present only when it isn't the mirror of the
+    /// original, i.e. when the flow was NATed. After kube-proxy's DNAT to a
+    /// Service backend, `reply.src_ip` is the backend pod.
+    pub reply: Option<ReplyTuple>,
+    /// The IPVS real server the flow was scheduled to, when kube-proxy runs
+    /// in IPVS mode.
+    pub ipvs: Option<IpvsRef>,
+    /// The sidecar proxy hop this flow is, in an Istio or Linkerd meshed pod.
+    pub mesh: Option<MeshInfo>,
+    /// Connection mark (`ctmark`), which CNIs such as Cilium and Calico
+    /// use for identities and policy verdicts.
+    pub mark: Option<u32>,
+    /// Conntrack zone the entry lives in.
+    pub zone: Option<u16>,
+    /// `mark` as decoded by the configured mark decoders.
+    pub mark_labels: Vec<String>,
+    pub zone_label: Option<String>,
+}
+
+/// Addresses and ports of a conntrack entry's reply direction.
+#[derive(Debug, Clone, Copy, Serialize, Hash, PartialEq, Eq)]
+pub struct ReplyTuple {
+    pub src_ip: IpAddr,
+    pub src_port: u16,
+    pub dst_ip: IpAddr,
+    pub dst_port: u16,
+}
+
+/// Where a connection's start time came from.
+#[derive(Debug, Clone, Copy, Serialize, Hash, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StartSource {
+    /// The kernel's `delta-time=` field (requires `nf_conntrack_timestamp=1`).
+    Kernel,
+    /// The first sample in which the daemon saw the flow.
+    FirstSeen,
+}
+
+/// Whether this node accepted the flow or opened it.
+#[derive(Debug, Clone, Copy, Serialize, Hash, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// A local socket listens on the flow's destination port.
+    Server,
+    /// A local socket owns the flow's source address and port.
+    Client,
+}
+
+/// Identifies a flow across samples: protocol plus the original 5-tuple, and
+/// the network namespace when several are read.
+pub type FlowKey = (String, String, u16, String, u16, Option<u64>);
+
+impl Connection {
+    pub fn key(&self) -> FlowKey {
+        (
+            self.proto.clone(),
+            self.src_ip.to_string(),
+            self.src_port,
+            self.dst_ip.to_string(),
+            self.dst_port,
+            self.netns,
+        )
+    }
+}
+
+pub fn flow_id(proto: &str, src_ip: &IpAddr, src_port: u16, dst_ip: &IpAddr, dst_port: u16) -> String {
+    format!("{}-{}-{}-{}-{}", proto, src_ip, src_port, dst_ip, dst_port)
+}
+
+/// IANA protocol number for a conntrack protocol name.
+pub fn protocol_number(proto: &str) -> u8 {
+    match proto {
+        "icmp" => 1,
+        "tcp" => 6,
+        "udp" => 17,
+        "icmpv6" => 58,
+        "sctp" => 132,
+        other => other.parse().unwrap_or(0),
+    }
+}
+
+pub fn resolve_conntrack_path(requested: &str) -> String {
+    use std::path::Path;
+
+    if requested == "auto" {
+        if let Some(found) = detect_conntrack_candidate() {
+            if std::env::var("KFLOW_DEBUG").is_ok() {
+                eprintln!("auto-detected conntrack path: {}", found);
+            }
+            return found;
+        }
+        return String::new();
+    }
+
+    if Path::new(requested).exists() {
+        return requested.to_string();
+    }
+
+    let alt_candidates = [
+        format!("/host{}", requested),
+        "/host/proc/net/nf_conntrack".into(),
+        "/host/proc/net/ip_conntrack".into(),
+        "/proc/net/nf_conntrack".into(),
+        "/proc/net/ip_conntrack".into(),
+    ];
+    for c in &alt_candidates {
+        if Path::new(c).exists() {
+            if std::env::var("KFLOW_DEBUG").is_ok() {
+                eprintln!("resolved conntrack path '{}' -> '{}'", requested, c);
+            }
+            return c.to_string();
+        }
+    }
+
+    eprintln!("conntrack path '{}' does not exist and no alternatives found; will retry detection periodically", requested);
+    String::new()
+}
+
+const CANDIDATES: &[&str] = &[
+    "/host/proc/net/nf_conntrack",
+    "/host/proc/net/ip_conntrack",
+    "/host/proc/net/nf_conntrack6",
+    "/proc/net/nf_conntrack",
+    "/proc/net/ip_conntrack",
+    "/proc/net/nf_conntrack6",
+    "/proc/net/ip_conntrack6",
+];
+
+/// The outcome of checking one candidate conntrack file, reported by `/info`
+/// so path problems can be diagnosed without `kubectl exec`.
+#[derive(Debug, Clone, Serialize)]
+pub struct CandidateProbe {
+    pub path: String,
+    pub exists: bool,
+    pub has_content: bool,
+    pub error: Option<String>,
+}
+
+pub fn probe_candidates() -> Vec<CandidateProbe> {
+    CANDIDATES.iter().map(|c| probe_candidate(c)).collect()
+}
+
+fn probe_candidate(c: &str) -> CandidateProbe {
+    use std::path::Path;
+
+    let mut probe = CandidateProbe { path: c.to_string(), exists: false, has_content: false, error: None };
+    let p = Path::new(c);
+    if !p.exists() {
+        return probe;
+    }
+    probe.exists = true;
+
+    match File::open(p) {
+        Ok(f) => {
+            let mut reader = BufReader::new(f);
+            let mut line = String::new();
+            match reader.read_line(&mut line) {
+                Ok(n) => {
+                    probe.has_content = n > 0 && !line.trim().is_empty();
+                    if std::env::var("KFLOW_DEBUG").is_ok() {
+                        if probe.has_content {
+                            eprintln!("candidate {} exists and has content", c);
+                        } else {
+                            eprintln!("candidate {} exists but was empty; skipping", c);
+                        }
+                    }
+                }
+                Err(e) => {
+                    if std::env::var("KFLOW_DEBUG").is_ok() {
+                        eprintln!("failed reading {}: {}", c, e);
+                    }
+                    probe.error = Some(format!("read: {}", e));
+                }
+            }
+        }
+        Err(e) => {
+            if std::env::var("KFLOW_DEBUG").is_ok() {
+                eprintln!("failed opening {}: {}", c, e);
+            }
+            probe.error = Some(format!("open: {}", e));
+        }
+    }
+    probe
+}
+
+pub fn detect_conntrack_candidate() -> Option<String> {
+    CANDIDATES.iter()
+        .map(|c| probe_candidate(c))
+        .find(|p| p.has_content)
+        .map(|p| p.path)
+}
+
+pub fn read_conntrack(path: &str) -> Vec<Connection> {
+    read_conntrack_file(path)
+}
+
+fn read_conntrack_file(path: &str) -> Vec<Connection> {
+    let mut path_to_use = path.to_string();
+    if path_to_use.is_empty() {
+        if let Some(detected) = detect_conntrack_candidate() {
+            path_to_use = detected;
+            if std::env::var("KFLOW_DEBUG").is_ok() {
+                eprintln!("detected conntrack path during read: {}", path_to_use);
+            }
+        } else {
+            return vec![];
+        }
+    }
+
+    let file = match File::open(&path_to_use) {
+        Ok(f) => f,
+        Err(e) => {
+            if std::env::var("KFLOW_DEBUG").is_ok() {
+                eprintln!("Failed to open conntrack file {}: {}", path_to_use, e);
+            }
+            return vec![];
+        }
+    };
+    let reader = BufReader::new(file);
+
+    let lines: Vec<String> = reader.lines().map_while(Result::ok).collect();
+
+    if std::env::var("KFLOW_DEBUG").is_ok() {
+        eprintln!("read_conntrack_file: read {} lines from {}", lines.len(), path_to_use);
+    }
+
+    lines
+        .into_iter()
+        .filter_map(|l| parse_conntrack_line(&l))
+        .collect()
+}
+
+pub fn parse_conntrack_line(line: &str) -> Option<Connection> {
+    let parts: Vec<&str> = line.split_whitespace().collect();
+    if parts.len() < 5 {
+        return None;
+    }
+
+    let debug = std::env::var("KFLOW_DEBUG").is_ok();
+    
+    static SAMPLE_PRINTED: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+    if debug && !SAMPLE_PRINTED.swap(true, std::sync::atomic::Ordering::Relaxed) {
+        eprintln!("Sample conntrack line: {}", line);
+    }
+    
+    let mut proto: Option<String> = None;
+    let mut state: Option<String> = None;
+    let mut src_ip: Option<IpAddr> = None;
+    let mut dst_ip: Option<IpAddr> = None;
+    let mut src_port: Option<u16> = None;
+    let mut dst_port: Option<u16> = None;
+    let mut bytes = 0u64;
+    let mut packets = 0u64;
+    let mut age_secs: Option<u64> = None;
+    let mut mark: Option<u32> = None;
+    let mut zone: Option<u16> = None;
+    // The second src=/dst=/sport=/dport= group is the reply direction.
+    let mut reply_src_ip: Option<IpAddr> = None;
+    let mut reply_dst_ip: Option<IpAddr> = None;
+    let mut reply_src_port: Option<u16> = None;
+    let mut reply_dst_port: Option<u16> = None;
+
+    for p in &parts {
+        if proto.is_none() && (*p == "tcp" || *p == "udp") {
+            proto = Some((*p).to_string());
+        } else if p.starts_with("src=") && src_ip.is_none() {
+            src_ip = p["src=".len()..].parse().ok();
+        } else if p.starts_with("dst=") && dst_ip.is_none() {
+            dst_ip = p["dst=".len()..].parse().ok();
+        } else if p.starts_with("sport=") && src_port.is_none() {
+            src_port = p["sport=".len()..].parse().ok();
+        } else if p.starts_with("dport=") && dst_port.is_none() {
+            dst_port = p["dport=".len()..].parse().ok();
+        } else if let Some(v) = p.strip_prefix("src=") && reply_src_ip.is_none() {
+            reply_src_ip = v.parse().ok();
+        } else if let Some(v) = p.strip_prefix("dst=") && reply_dst_ip.is_none() {
+            reply_dst_ip = v.parse().ok();
+        } else if let Some(v) = p.strip_prefix("sport=") && reply_src_port.is_none() {
+            reply_src_port = v.parse().ok();
+        } else if let Some(v) = p.strip_prefix("dport=") && reply_dst_port.is_none() {
+            reply_dst_port = v.parse().ok();
+        } else if let Some(b) = p.strip_prefix("bytes=").and_then(|v| v.parse::<u64>().ok()) {
+            bytes += b;
+            if debug && bytes > 0 {
+                eprintln!("Found bytes={} in conntrack line", b);
+            }
+        } else if let Some(n) = p.strip_prefix("packets=").and_then(|v| v.parse::<u64>().ok()) {
+            packets += n;
+        } else if let Some(d) = p.strip_prefix("delta-time=") {
+            age_secs = d.parse().ok();
+        } else if let Some(m) = p.strip_prefix("mark=") {
+            mark = m.parse().ok();
+        } else if let Some(z) = p.strip_prefix("zone=") {
+            zone = z.parse().ok();
+        } else if state.is_none()
+            && (*p == "ESTABLISHED"
+                || *p == "SYN_SENT"
+                || *p == "SYN_RECV"
+                || *p == "FIN_WAIT"
+                || *p == "TIME_WAIT")
+        {
+            state = Some((*p).to_string());
+        }
+    }
+
+    let (proto, src_ip, src_port, dst_ip, dst_port) = (proto?, src_ip?, src_port?, dst_ip?, dst_port?);
+    let reply = match (reply_src_ip, reply_src_port, reply_dst_ip, reply_dst_port) {
+        (Some(rs), Some(rsp), Some(rd), Some(rdp)) if (rs, rsp, rd, rdp) != (dst_ip, dst_port, src_ip, src_port) => {
+            Some(ReplyTuple { src_ip: rs, src_port: rsp, dst_ip: rd, dst_port: rdp })
+        }
+        _ => None,
+    };
+    Some(Connection {
+        id: flow_id(&proto, &src_ip, src_port, &dst_ip, dst_port),
+        proto,
+        src_ip,
+        src_port,
+        dst_ip,
+        dst_port,
+        state: state.unwrap_or_else(|| "UNKNOWN".into()),
+        bytes,
+        packets,
+        throughput_bytes_per_sec: 0,
+        age_secs,
+        started_at: None,
+        start_source: if age_secs.is_some() { StartSource::Kernel } else { StartSource::FirstSeen },
+        role: None,
+        tcp_info: None,
+        process: None,
+        container_id: None,
+        pod_uid: None,
+        netns: None,
+        src_class: base_class(&src_ip),
+        dst_class: base_class(&dst_ip),
+        reply,
+        ipvs: None,
+        mesh: None,
+        mark,
+        zone,
+        mark_labels: Vec::new(),
+        zone_label: None,
+    })
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_ipv4_dnat_with_accounting_and_timestamps() {
+        let c = parse_conntrack_line(
+            "ipv4     2 tcp      6 431999 ESTABLISHED src=10.244.1.5 dst=10.96.0.1 sport=51234 dport=443 \
+             packets=12 bytes=2345 src=10.0.0.10 dst=10.244.1.5 sport=6443 dport=51234 packets=10 bytes=6789 \
+             [ASSURED] mark=1024 zone=3 delta-time=187 use=2",
+        )
+        .unwrap();
+        assert_eq!(c.id, "tcp-10.244.1.5-51234-10.96.0.1-443");
+        assert_eq!((c.proto.as_str(), c.state.as_str()), ("tcp", "ESTABLISHED"));
+        assert_eq!((c.src_ip, c.src_port, c.dst_ip, c.dst_port), (ip("10.244.1.5"), 51234, ip("10.96.0.1"), 443));
+        // Both directions are summed.
+        assert_eq!((c.packets, c.bytes), (22, 9134));
+        assert_eq!((c.mark, c.zone), (Some(1024), Some(3)));
+        assert_eq!(c.age_secs, Some(187));
+        assert_eq!(c.start_source, StartSource::Kernel);
+        assert_eq!(
+            c.reply,
+            Some(ReplyTuple { src_ip: ip("10.0.0.10"), src_port: 6443, dst_ip: ip("10.244.1.5"), dst_port: 51234 })
+        );
+    }
+
+    #[test]
+    fn parses_lines_without_accounting_or_nat() {
+        let c = parse_conntrack_line(
+            "ipv4     2 tcp      6 86397 ESTABLISHED src=192.168.1.20 dst=192.168.1.30 sport=38512 dport=22 \
+             src=192.168.1.30 dst=192.168.1.20 sport=22 dport=38512 [ASSURED] mark=0 use=1",
+        )
+        .unwrap();
+        assert_eq!((c.packets, c.bytes), (0, 0));
+        assert_eq!((c.mark, c.zone), (Some(0), None));
+        assert_eq!(c.age_secs, None);
+        assert_eq!(c.start_source, StartSource::FirstSeen);
+        // The reply is only kept when it differs from the swapped original.
+        assert_eq!(c.reply, None);
+    }
+
+    #[test]
+    fn parses_unreplied_entries() {
+        let c = parse_conntrack_line(
+            "ipv4     2 tcp      6 118 SYN_SENT src=10.244.2.7 dst=10.244.3.9 sport=44120 dport=5432 packets=3 bytes=180 \
+             [UNREPLIED] src=10.244.3.9 dst=10.244.2.7 sport=5432 dport=44120 packets=0 bytes=0 mark=0 use=1",
+        )
+        .unwrap();
+        assert_eq!(c.state, "SYN_SENT");
+        assert_eq!((c.packets, c.bytes), (3, 180));
+        assert_eq!(c.reply, None);
+
+        // UDP has no state column.
+        let c = parse_conntrack_line(
+            "ipv4     2 udp      17 28 src=10.244.1.5 dst=10.96.0.10 sport=40123 dport=53 [UNREPLIED] \
+             src=10.244.0.3 dst=10.244.1.5 sport=53 dport=40123 mark=0 use=1",
+        )
+        .unwrap();
+        assert_eq!((c.proto.as_str(), c.state.as_str()), ("udp", "UNKNOWN"));
+        assert_eq!(c.reply.map(|r| (r.src_ip, r.src_port)), Some((ip("10.244.0.3"), 53)));
+    }
+
+    #[test]
+    fn parses_ipv6_entries() {
+        let c = parse_conntrack_line(
+            "ipv6     10 tcp      6 299 TIME_WAIT src=fd00:10:244:1::5 dst=fd00:10:96::1 sport=50000 dport=443 \
+             packets=8 bytes=1200 src=fd00:10:244:2::8 dst=fd00:10:244:1::5 sport=8443 dport=50000 packets=6 bytes=3400 \
+             [ASSURED] mark=0 zone=1 use=1",
+        )
+        .unwrap();
+        assert_eq!(c.id, "tcp-fd00:10:244:1::5-50000-fd00:10:96::1-443");
+        assert_eq!(c.state, "TIME_WAIT");
+        assert_eq!((c.src_ip, c.dst_ip), (ip("fd00:10:244:1::5"), ip("fd00:10:96::1")));
+        assert_eq!((c.packets, c.bytes, c.zone), (14, 4600, Some(1)));
+        assert_eq!(c.reply.map(|r| (r.src_ip, r.src_port)), Some((ip("fd00:10:244:2::8"), 8443)));
+    }
+
+    #[test]
+    fn skips_entries_without_ports() {
+        let lines = [
+            "ipv4     2 icmp     1 29 src=10.0.0.1 dst=10.0.0.2 type=8 code=0 id=4321 packets=1 bytes=84 \
+             src=10.0.0.2 dst=10.0.0.1 type=0 code=0 id=4321 packets=1 bytes=84 mark=0 use=1",
+            "ipv4     2 unknown  47 599 src=10.0.0.1 dst=10.0.0.2 src=10.0.0.2 dst=10.0.0.1 mark=0 use=1",
+            "ipv4     2 tcp",
+            "",
+        ];
+        for line in lines {
+            assert!(parse_conntrack_line(line).is_none(), "{}", line);
+        }
+    }
+}
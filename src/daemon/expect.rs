@@ -0,0 +1,231 @@
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::daemon::host_proc;
+use crate::daemon::conntrack::Connection;
+
+/// An entry from the conntrack expectation table: a flow a helper (FTP, SIP,
+/// TFTP, H.323, ...) has announced in advance so it can pass NAT/firewalling.
+#[derive(Debug, Clone, Serialize)]
+pub struct Expectation {
+    pub timeout_secs: Option<u64>,
+    pub proto: String,
+    pub src_ip: IpAddr,
+    pub src_port: u16,
+    pub dst_ip: IpAddr,
+    pub dst_port: u16,
+    pub flags: Vec<String>,
+    pub helper: Option<String>,
+    /// The helper's expectation class, e.g. `ftp` or `rtp` for SIP media.
+    pub class: Option<String>,
+    /// The control connection that created this expectation, when the
+    /// helper's well-known port makes it identifiable.
+    pub master: Option<MasterFlow>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MasterFlow {
+    pub proto: String,
+    pub src_ip: IpAddr,
+    pub src_port: u16,
+    pub dst_ip: IpAddr,
+    pub dst_port: u16,
+}
+
+const EXPECT_FLAGS: &[&str] = &["PERMANENT", "INACTIVE", "USERSPACE"];
+
+/// Control ports of the in-kernel conntrack helpers, keyed by the helper
+/// name the kernel prints, used to find an expectation's master connection.
+/// `H.245` is missing on purpose: its channel is negotiated over Q.931 on a
+/// dynamic port, so its expectations' master can't be told by port.
+const HELPER_PORTS: &[(&str, &[u16])] = &[
+    ("ftp", &[21]),
+    ("tftp", &[69]),
+    ("sip", &[5060]),
+    ("Q.931", &[1720]),
+    ("RAS", &[1719]),
+    ("irc", &[6667]),
+    ("amanda", &[10080]),
+    ("pptp", &[1723]),
+    ("sane", &[6566]),
+    ("snmp", &[161]),
+    ("netbios-ns", &[137]),
+];
+
+/// Finds the expectation table next to the conntrack file being read, falling
+/// back to the usual procfs locations.
+pub fn expect_path(conntrack_path: &str) -> Option<PathBuf> {
+    let sibling = Path::new(conntrack_path).parent().and_then(|dir| {
+        let name = Path::new(conntrack_path).file_name()?.to_str()?;
+        let expect = if name.starts_with("ip_") { "ip_conntrack_expect" } else { "nf_conntrack_expect" };
+        Some(dir.join(expect))
+    });
+    sibling
+        .filter(|p| p.exists())
+        .or_else(|| host_proc("net/nf_conntrack_expect"))
+        .or_else(|| host_proc("net/ip_conntrack_expect"))
+}
+
+pub fn read_expectations(conntrack_path: &str, flows: &[Connection]) -> Vec<Expectation> {
+    let Some(path) = expect_path(conntrack_path) else { return vec![] };
+    let content = match std::fs::read_to_string(&path) {
+        Ok(c) => c,
+        Err(e) => {
+            if std::env::var("KFLOW_DEBUG").is_ok() {
+                eprintln!("Failed to read expectation table {}: {}", path.display(), e);
+            }
+            return vec![];
+        }
+    };
+
+    content
+        .lines()
+        .filter_map(parse_expect_line)
+        .map(|mut e| {
+            e.master = find_master(&e, flows);
+            e
+        })
+        .collect()
+}
+
+/// Parses one line of `/proc/net/nf_conntrack_expect`, e.g.
+/// `297 l3proto = 2 proto=6 src=10.0.0.1 dst=10.0.0.2 sport=0 dport=50000 ftp`.
+pub fn parse_expect_line(line: &str) -> Option<Expectation> {
+    let parts: Vec<&str> = line.split_whitespace().collect();
+    if parts.len() < 6 {
+        return None;
+    }
+
+    let timeout_secs = parts[0].parse().ok();
+    let mut proto: Option<String> = None;
+    let mut src_ip: Option<IpAddr> = None;
+    let mut dst_ip: Option<IpAddr> = None;
+    let mut src_port: Option<u16> = None;
+    let mut dst_port: Option<u16> = None;
+    let mut flags = Vec::new();
+    let mut helper: Option<String> = None;
+    let mut class: Option<String> = None;
+
+    for p in &parts[1..] {
+        if let Some(v) = p.strip_prefix("proto=") {
+            proto = Some(match v {
+                "6" => "tcp".to_string(),
+                "17" => "udp".to_string(),
+                "132" => "sctp".to_string(),
+                other => other.to_string(),
+            });
+        } else if let Some(v) = p.strip_prefix("src=") {
+            src_ip = v.parse().ok();
+        } else if let Some(v) = p.strip_prefix("dst=") {
+            dst_ip = v.parse().ok();
+        } else if let Some(v) = p.strip_prefix("sport=") {
+            src_port = v.parse().ok();
+        } else if let Some(v) = p.strip_prefix("dport=") {
+            dst_port = v.parse().ok();
+        } else if p.contains('=') || *p == "l3proto" || p.chars().all(|c| c.is_ascii_digit()) {
+            continue;
+        } else if p.split(',').all(|f| EXPECT_FLAGS.contains(&f)) {
+            flags.extend(p.split(',').map(|f| f.to_string()));
+        } else {
+            // The helper is printed last, optionally as `helper/class`.
+            let mut it = p.splitn(2, '/');
+            helper = it.next().map(|h| h.to_string());
+            class = it.next().map(|c| c.to_string());
+        }
+    }
+
+    Some(Expectation {
+        timeout_secs,
+        proto: proto?,
+        src_ip: src_ip?,
+        src_port: src_port.unwrap_or(0),
+        dst_ip: dst_ip?,
+        dst_port: dst_port?,
+        flags,
+        helper,
+        class,
+        master: None,
+    })
+}
+
+fn find_master(e: &Expectation, flows: &[Connection]) -> Option<MasterFlow> {
+    let helper = e.helper.as_deref()?;
+    let ports = HELPER_PORTS
+        .iter()
+        .find(|(name, _)| *name == helper)
+        .map(|(_, ports)| *ports)?;
+
+    flows
+        .iter()
+        .find(|c| {
+            let same_hosts = (c.src_ip == e.src_ip && c.dst_ip == e.dst_ip)
+                || (c.src_ip == e.dst_ip && c.dst_ip == e.src_ip);
+            same_hosts && (ports.contains(&c.dst_port) || ports.contains(&c.src_port))
+        })
+        .map(|c| MasterFlow {
+            proto: c.proto.clone(),
+            src_ip: c.src_ip,
+            src_port: c.src_port,
+            dst_ip: c.dst_ip,
+            dst_port: c.dst_port,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::daemon::conntrack::parse_conntrack_line;
+
+    #[test]
+    fn parses_kernel_expectation_lines() {
+        let ftp = parse_expect_line("297 l3proto = 2 proto=6 src=10.0.0.1 dst=10.0.0.2 sport=0 dport=50000 ftp").unwrap();
+        assert_eq!(ftp.timeout_secs, Some(297));
+        assert_eq!((ftp.proto.as_str(), ftp.src_port, ftp.dst_port), ("tcp", 0, 50000));
+        assert_eq!(ftp.dst_ip, "10.0.0.2".parse::<IpAddr>().unwrap());
+        assert_eq!((ftp.helper.as_deref(), ftp.class.as_deref()), (Some("ftp"), None));
+        assert!(ftp.flags.is_empty());
+
+        let sip = parse_expect_line("178 l3proto = 2 proto=17 src=192.168.1.10 dst=192.168.1.20 sport=0 dport=10002 PERMANENT,INACTIVE sip/audio").unwrap();
+        assert_eq!(sip.proto, "udp");
+        assert_eq!(sip.flags, ["PERMANENT", "INACTIVE"]);
+        assert_eq!((sip.helper.as_deref(), sip.class.as_deref()), (Some("sip"), Some("audio")));
+
+        let v6 = parse_expect_line("295 l3proto = 10 proto=6 src=2001:db8::1 dst=2001:db8::2 sport=0 dport=41234 ftp").unwrap();
+        assert_eq!(v6.src_ip, "2001:db8::1".parse::<IpAddr>().unwrap());
+
+        // Expectations without a timer print `-`.
+        let untimed = parse_expect_line("- l3proto = 2 proto=6 src=10.0.0.1 dst=10.0.0.2 sport=0 dport=1503 H.245").unwrap();
+        assert_eq!(untimed.timeout_secs, None);
+        assert_eq!(untimed.helper.as_deref(), Some("H.245"));
+
+        assert!(parse_expect_line("").is_none());
+        assert!(parse_expect_line("297 l3proto = 2 proto=6 ftp").is_none());
+    }
+
+    #[test]
+    fn finds_the_control_connection_by_helper_port() {
+        let flows: Vec<Connection> = [
+            "ipv4 2 tcp 6 431999 ESTABLISHED src=10.0.0.1 dst=10.0.0.2 sport=40000 dport=21 src=10.0.0.2 dst=10.0.0.1 sport=21 dport=40000 [ASSURED] mark=0 use=2",
+            "ipv4 2 tcp 6 431999 ESTABLISHED src=10.0.0.1 dst=10.0.0.2 sport=40001 dport=1720 src=10.0.0.2 dst=10.0.0.1 sport=1720 dport=40001 [ASSURED] mark=0 use=2",
+        ]
+        .iter()
+        .filter_map(|l| parse_conntrack_line(l))
+        .collect();
+
+        let ftp = parse_expect_line("297 l3proto = 2 proto=6 src=10.0.0.1 dst=10.0.0.2 sport=0 dport=50000 ftp").unwrap();
+        let master = find_master(&ftp, &flows).unwrap();
+        assert_eq!((master.src_port, master.dst_port), (40000, 21));
+
+        let q931 = parse_expect_line("240 l3proto = 2 proto=6 src=10.0.0.2 dst=10.0.0.1 sport=0 dport=1503 Q.931").unwrap();
+        assert_eq!(find_master(&q931, &flows).map(|m| m.dst_port), Some(1720));
+
+        // H.245 channels have no fixed port to find their master by.
+        let h245 = parse_expect_line("240 l3proto = 2 proto=17 src=10.0.0.2 dst=10.0.0.1 sport=0 dport=5004 H.245").unwrap();
+        assert!(find_master(&h245, &flows).is_none());
+
+        let tftp = parse_expect_line("3 l3proto = 2 proto=17 src=10.0.0.9 dst=10.0.0.1 sport=0 dport=3000 tftp").unwrap();
+        assert!(find_master(&tftp, &flows).is_none());
+    }
+}
@@ -0,0 +1,208 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::daemon::flows::FlowRecord;
+
+/// Append-only JSON Lines log of finished flows, enabled by setting
+/// `KFLOW_FLOW_LOG` to a file path. The active file is rotated to
+/// `<path>.<unix-time>` (`<path>.<unix-time>.<n>` for further rotations in
+/// the same second) when it grows past `KFLOW_FLOW_LOG_MAX_BYTES`
+/// (default 64MiB) or gets older than `KFLOW_FLOW_LOG_ROTATE_SECS` (default
+/// 1h). At most `KFLOW_FLOW_LOG_MAX_FILES` rotated files are kept (default
+/// 10), and rotated files older than `KFLOW_FLOW_LOG_MAX_AGE_SECS` are
+/// removed when that is set.
+#[derive(Debug, Clone)]
+pub struct FlowLogConfig {
+    pub path: PathBuf,
+    pub max_bytes: u64,
+    pub rotate_secs: u64,
+    pub max_files: usize,
+    pub max_age_secs: Option<u64>,
+}
+
+impl FlowLogConfig {
+    pub fn from_env() -> Option<Self> {
+        let path = std::env::var("KFLOW_FLOW_LOG").ok().filter(|p| !p.is_empty())?;
+        let num = |name: &str| std::env::var(name).ok().and_then(|v| v.parse::<u64>().ok());
+        Some(FlowLogConfig {
+            path: PathBuf::from(path),
+            max_bytes: num("KFLOW_FLOW_LOG_MAX_BYTES").unwrap_or(64 * 1024 * 1024),
+            rotate_secs: num("KFLOW_FLOW_LOG_ROTATE_SECS").unwrap_or(3600),
+            max_files: num("KFLOW_FLOW_LOG_MAX_FILES").unwrap_or(10) as usize,
+            max_age_secs: num("KFLOW_FLOW_LOG_MAX_AGE_SECS"),
+        })
+    }
+}
+
+pub struct FlowLog {
+    config: FlowLogConfig,
+    file: Option<File>,
+    size: u64,
+    opened_at: u64,
+}
+
+impl FlowLog {
+    pub fn new(config: FlowLogConfig) -> Self {
+        FlowLog { config, file: None, size: 0, opened_at: 0 }
+    }
+
+    pub fn write(&mut self, now: u64, records: &[FlowRecord]) -> std::io::Result<()> {
+        if records.is_empty() {
+            return Ok(());
+        }
+        if self.file.is_none() {
+            self.open(now)?;
+        }
+        // Also checked right after opening, so a file left oversized or stale
+        // by a previous run is rotated before it is appended to.
+        if self.size > 0
+            && (self.size >= self.config.max_bytes || now.saturating_sub(self.opened_at) >= self.config.rotate_secs) {
+            self.rotate(now)?;
+            self.open(now)?;
+        }
+
+        let mut buf = Vec::new();
+        for r in records {
+            serde_json::to_writer(&mut buf, r)?;
+            buf.push(b'\n');
+        }
+        if let Some(f) = self.file.as_mut() {
+            f.write_all(&buf)?;
+            f.flush()?;
+        }
+        self.size += buf.len() as u64;
+        Ok(())
+    }
+
+    fn open(&mut self, now: u64) -> std::io::Result<()> {
+        if let Some(dir) = self.config.path.parent()
+            && !dir.as_os_str().is_empty() {
+            fs::create_dir_all(dir)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&self.config.path)?;
+        let meta = file.metadata()?;
+        self.size = meta.len();
+        // An existing file keeps its age across daemon restarts.
+        self.opened_at = meta.created().or_else(|_| meta.modified()).ok()
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|d| d.as_secs())
+            .filter(|_| self.size > 0)
+            .unwrap_or(now);
+        self.file = Some(file);
+        Ok(())
+    }
+
+    fn rotate(&mut self, now: u64) -> std::io::Result<()> {
+        self.file = None;
+        let base = format!("{}.{}", self.config.path.display(), now);
+        let rotated = (0u32..)
+            .map(|n| PathBuf::from(if n == 0 { base.clone() } else { format!("{}.{}", base, n) }))
+            .find(|p| !p.exists())
+            .unwrap_or_else(|| PathBuf::from(&base));
+        fs::rename(&self.config.path, &rotated)?;
+        if std::env::var("KFLOW_DEBUG").is_ok() {
+            eprintln!("rotated flow log to {}", rotated.display());
+        }
+        self.prune(now);
+        Ok(())
+    }
+
+    fn prune(&self, now: u64) {
+        let mut rotated = rotated_files(&self.config.path);
+        rotated.sort_by_key(|(key, _)| std::cmp::Reverse(*key));
+        for (i, ((ts, _), path)) in rotated.iter().enumerate() {
+            let too_many = i >= self.config.max_files;
+            let too_old = self.config.max_age_secs.map(|age| now.saturating_sub(*ts) > age).unwrap_or(false);
+            if (too_many || too_old)
+                && let Err(e) = fs::remove_file(path) {
+                eprintln!("failed to remove old flow log {}: {}", path.display(), e);
+            }
+        }
+    }
+}
+
+/// Rotated siblings of `path` (`<path>.<unix-time>[.<n>]`), keyed by
+/// timestamp and sequence number so they sort oldest first.
+pub fn rotated_files(path: &Path) -> Vec<((u64, u32), PathBuf)> {
+    let Some(name) = path.file_name().and_then(|n| n.to_str()) else { return vec![] };
+    let dir = match path.parent() {
+        Some(d) if !d.as_os_str().is_empty() => d,
+        _ => Path::new("."),
+    };
+    let prefix = format!("{}.", name);
+    let Ok(entries) = fs::read_dir(dir) else { return vec![] };
+    entries
+        .filter_map(|e| e.ok())
+        .filter_map(|e| {
+            let file_name = e.file_name();
+            let suffix = file_name.to_str()?.strip_prefix(&prefix)?;
+            let (ts, seq) = suffix.split_once('.').unwrap_or((suffix, "0"));
+            Some(((ts.parse().ok()?, seq.parse().ok()?), e.path()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::daemon::conntrack::StartSource;
+
+    fn record() -> FlowRecord {
+        FlowRecord {
+            node_name: None,
+            id: "tcp-10.0.0.1-40000-10.0.0.2-80".into(),
+            proto: "tcp".into(),
+            src_ip: "10.0.0.1".parse().unwrap(),
+            src_port: 40000,
+            dst_ip: "10.0.0.2".parse().unwrap(),
+            dst_port: 80,
+            netns: None,
+            start: 100,
+            end: 110,
+            start_source: StartSource::FirstSeen,
+            bytes: 300,
+            packets: 5,
+            states: Vec::new(),
+        }
+    }
+
+    fn config(dir: &Path, max_bytes: u64) -> FlowLogConfig {
+        FlowLogConfig { path: dir.join("flows.jsonl"), max_bytes, rotate_secs: 3600, max_files: 10, max_age_secs: None }
+    }
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("kflow-flowlog-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn rotations_in_the_same_second_keep_every_file() {
+        let dir = scratch("same-second");
+        let mut log = FlowLog::new(config(&dir, 1));
+        for _ in 0..4 {
+            log.write(1000, &[record()]).unwrap();
+        }
+        let mut rotated = rotated_files(&dir.join("flows.jsonl"));
+        rotated.sort_by_key(|(key, _)| *key);
+        let keys: Vec<(u64, u32)> = rotated.iter().map(|(key, _)| *key).collect();
+        assert_eq!(keys, vec![(1000, 0), (1000, 1), (1000, 2)]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn oversized_file_is_rotated_before_appending() {
+        let dir = scratch("oversized");
+        let path = dir.join("flows.jsonl");
+        fs::write(&path, "x".repeat(100)).unwrap();
+        let mut log = FlowLog::new(config(&dir, 50));
+        log.write(1000, &[record()]).unwrap();
+        let rotated = rotated_files(&path);
+        assert_eq!(rotated.len(), 1);
+        assert_eq!(fs::read_to_string(&rotated[0].1).unwrap().len(), 100);
+        assert!(!fs::read_to_string(&path).unwrap().starts_with('x'));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
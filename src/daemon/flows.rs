@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+
+use serde::Serialize;
+
+use crate::daemon::conntrack::{Connection, FlowKey, StartSource};
+
+/// A finished flow: everything known about a connection from the first to
+/// the last sample it appeared in.
+#[derive(Debug, Clone, Serialize)]
+pub struct FlowRecord {
+    pub node_name: Option<String>,
+    pub id: String,
+    pub proto: String,
+    pub src_ip: IpAddr,
+    pub src_port: u16,
+    pub dst_ip: IpAddr,
+    pub dst_port: u16,
+    pub netns: Option<u64>,
+    /// Unix seconds. Exact when `start_source` is `kernel`.
+    pub start: u64,
+    /// Unix seconds of the last sample the flow was seen in.
+    pub end: u64,
+    pub start_source: StartSource,
+    pub bytes: u64,
+    pub packets: u64,
+    pub states: Vec<StateChange>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StateChange {
+    pub at: u64,
+    pub state: String,
+}
+
+struct Tracked {
+    conn: Connection,
+    last_seen: u64,
+    states: Vec<StateChange>,
+}
+
+/// Follows flows across samples: fills in throughput and start times, logs
+/// additions and removals, and hands back a record for every flow that ended.
+pub struct FlowTracker {
+    node_name: Option<String>,
+    sample_interval: u64,
+    open: HashMap<FlowKey, Tracked>,
+    first_seen: HashMap<FlowKey, u64>,
+}
+
+impl FlowTracker {
+    pub fn new(node_name: Option<String>, sample_interval: u64) -> Self {
+        FlowTracker { node_name, sample_interval, open: HashMap::new(), first_seen: HashMap::new() }
+    }
+
+    pub fn update(&mut self, now: u64, flows: &mut [Connection]) -> Vec<FlowRecord> {
+        let mut ended = Vec::new();
+        let mut current: HashMap<FlowKey, Tracked> = HashMap::with_capacity(flows.len());
+
+        for flow in flows.iter_mut() {
+            let key = flow.key();
+            stamp_start(flow, now, *self.first_seen.entry(key.clone()).or_insert(now));
+
+            let mut tracked = match self.open.remove(&key) {
+                Some(p) if same_flow(&p.conn, flow, self.sample_interval) => {
+                    let byte_delta = flow.bytes.saturating_sub(p.conn.bytes);
+                    flow.throughput_bytes_per_sec = byte_delta / self.sample_interval;
+                    p
+                }
+                Some(p) => {
+                    // The 5-tuple was reused by a new flow between samples.
+                    self.first_seen.insert(key.clone(), now);
+                    stamp_start(flow, now, now);
+                    println!("Removed connection: {:?}", p.conn);
+                    ended.push(self.record(&p));
+                    println!("Added connection: {:?}", flow);
+                    Tracked { conn: flow.clone(), last_seen: now, states: Vec::new() }
+                }
+                None => {
+                    println!("Added connection: {:?}", flow);
+                    Tracked { conn: flow.clone(), last_seen: now, states: Vec::new() }
+                }
+            };
+
+            if tracked.states.last().map(|s| s.state != flow.state).unwrap_or(true) {
+                tracked.states.push(StateChange { at: now, state: flow.state.clone() });
+            }
+            tracked.conn = flow.clone();
+            tracked.last_seen = now;
+            current.insert(key, tracked);
+        }
+
+        for (_, removed) in std::mem::take(&mut self.open) {
+            println!("Removed connection: {:?} (lived {}s)", removed.conn, removed.conn.age_secs.unwrap_or(0));
+            ended.push(self.record(&removed));
+        }
+        self.first_seen.retain(|k, _| current.contains_key(k));
+        self.open = current;
+        ended
+    }
+
+    /// Records for the flows still open, as of the last update.
+    pub fn open_records(&self) -> Vec<FlowRecord> {
+        self.open.values().map(|t| self.record(t)).collect()
+    }
+
+    fn record(&self, t: &Tracked) -> FlowRecord {
+        let c = &t.conn;
+        FlowRecord {
+            node_name: self.node_name.clone(),
+            id: c.id.clone(),
+            proto: c.proto.clone(),
+            src_ip: c.src_ip,
+            src_port: c.src_port,
+            dst_ip: c.dst_ip,
+            dst_port: c.dst_port,
+            netns: c.netns,
+            start: c.started_at.unwrap_or(t.last_seen),
+            end: t.last_seen,
+            start_source: c.start_source,
+            bytes: c.bytes,
+            packets: c.packets,
+            states: t.states.clone(),
+        }
+    }
+}
+
+/// Fills in `started_at`/`age_secs`, preferring the kernel's own flow age and
+/// falling back to when the daemon first saw the flow.
+fn stamp_start(flow: &mut Connection, now: u64, first_seen: u64) {
+    match flow.age_secs {
+        Some(age) if flow.start_source == StartSource::Kernel => {
+            flow.started_at = Some(now.saturating_sub(age));
+        }
+        _ => {
+            flow.started_at = Some(first_seen);
+            flow.age_secs = Some(now.saturating_sub(first_seen));
+        }
+    }
+}
+
+/// Whether two samples with the same 5-tuple are the same kernel flow. Only
+/// kernel timestamps can tell a reused tuple apart; otherwise assume it is.
+fn same_flow(prev: &Connection, cur: &Connection, sample_interval: u64) -> bool {
+    match (prev.start_source, prev.started_at, cur.start_source, cur.started_at) {
+        (StartSource::Kernel, Some(a), StartSource::Kernel, Some(b)) => a.abs_diff(b) <= sample_interval,
+        _ => cur.bytes >= prev.bytes,
+    }
+}
@@ -0,0 +1,143 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use serde::{Serialize, Serializer};
+
+use crate::daemon::conntrack::Connection;
+
+/// How much history the daemon keeps, read from the environment:
+///
+/// - `KFLOW_HISTORY_RETENTION_SECS`: drop snapshots older than this (default 1h)
+/// - `KFLOW_HISTORY_FULL_RES_SECS`: keep every sample this recent (default 5m)
+/// - `KFLOW_HISTORY_DOWNSAMPLE_SECS`: older samples are thinned to one per this many seconds (default 30s)
+/// - `KFLOW_HISTORY_MAX_BYTES`: approximate memory ceiling; oldest snapshots go first (default 32MiB)
+#[derive(Debug, Clone)]
+pub struct HistoryConfig {
+    pub retention_secs: u64,
+    pub full_res_secs: u64,
+    pub downsample_secs: u64,
+    pub max_bytes: usize,
+}
+
+impl HistoryConfig {
+    pub fn from_env() -> Self {
+        let num = |name: &str, default: u64| {
+            std::env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+        };
+        HistoryConfig {
+            retention_secs: num("KFLOW_HISTORY_RETENTION_SECS", 3600),
+            full_res_secs: num("KFLOW_HISTORY_FULL_RES_SECS", 300),
+            downsample_secs: num("KFLOW_HISTORY_DOWNSAMPLE_SECS", 30).max(1),
+            max_bytes: num("KFLOW_HISTORY_MAX_BYTES", 32 * 1024 * 1024) as usize,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Snapshot {
+    pub at: u64,
+    #[serde(serialize_with = "serialize_shared")]
+    pub connections: Arc<Vec<Connection>>,
+    #[serde(skip)]
+    size: usize,
+}
+
+pub struct History {
+    config: HistoryConfig,
+    snapshots: VecDeque<Snapshot>,
+    bytes: usize,
+}
+
+impl History {
+    pub fn new(config: HistoryConfig) -> Self {
+        History { config, snapshots: VecDeque::new(), bytes: 0 }
+    }
+
+    pub fn push(&mut self, at: u64, mut connections: Vec<Connection>) {
+        // Held for up to the retention window, so don't keep spare capacity.
+        connections.shrink_to_fit();
+        let size = estimate_size(&connections);
+        self.snapshots.push_back(Snapshot { at, connections: Arc::new(connections), size });
+        self.bytes += size;
+        self.compact(at);
+    }
+
+    /// Thins samples older than the full-resolution window to one per
+    /// downsample bucket, then enforces the retention and memory limits.
+    fn compact(&mut self, now: u64) {
+        let full_res_from = now.saturating_sub(self.config.full_res_secs);
+        let step = self.config.downsample_secs;
+        let mut last_bucket: Option<u64> = None;
+        let mut freed = 0usize;
+        self.snapshots.retain(|s| {
+            if s.at >= full_res_from {
+                return true;
+            }
+            let bucket = s.at / step;
+            if last_bucket == Some(bucket) {
+                freed += s.size;
+                false
+            } else {
+                last_bucket = Some(bucket);
+                true
+            }
+        });
+        self.bytes -= freed;
+
+        let retain_from = now.saturating_sub(self.config.retention_secs);
+        while let Some(front) = self.snapshots.front() {
+            if front.at >= retain_from && self.bytes <= self.config.max_bytes {
+                break;
+            }
+            self.bytes -= front.size;
+            self.snapshots.pop_front();
+        }
+    }
+
+    /// Snapshots in `[from, to]`, at most one per `step` seconds.
+    pub fn range(&self, from: u64, to: u64, step: u64) -> Vec<Snapshot> {
+        let mut out: Vec<Snapshot> = Vec::new();
+        for s in self.snapshots.iter().filter(|s| s.at >= from && s.at <= to) {
+            if let Some(last) = out.last()
+                && step > 0 && s.at < last.at + step {
+                continue;
+            }
+            out.push(s.clone());
+        }
+        out
+    }
+}
+
+fn serialize_shared<S: Serializer>(v: &Arc<Vec<Connection>>, s: S) -> Result<S::Ok, S::Error> {
+    v.as_slice().serialize(s)
+}
+
+/// Approximate memory held by a snapshot: the connection array plus every
+/// heap allocation a connection owns.
+fn estimate_size(connections: &[Connection]) -> usize {
+    std::mem::size_of::<Snapshot>()
+        + std::mem::size_of_val(connections)
+        + connections.iter().map(heap_size).sum::<usize>()
+}
+
+/// Bytes a connection owns outside its own struct. Destructured without
+/// `..` so a new field can't be added without deciding what it costs.
+fn heap_size(c: &Connection) -> usize {
+    let Connection {
+        id, proto, src_ip: _, src_port: _, dst_ip: _, dst_port: _, state, bytes: _, packets: _,
+        throughput_bytes_per_sec: _, age_secs: _, started_at: _, start_source: _, role: _, tcp_info: _,
+        process, container_id, pod_uid, netns: _, src_class: _, dst_class: _, reply: _, ipvs, mesh: _,
+        mark: _, zone: _, mark_labels, zone_label,
+    } = c;
+    let opt = |s: &Option<String>| s.as_ref().map_or(0, String::capacity);
+    id.capacity()
+        + proto.capacity()
+        + state.capacity()
+        + process.as_ref().map_or(0, |p| p.comm.capacity() + p.cmdline.capacity())
+        + opt(container_id)
+        + opt(pod_uid)
+        + ipvs.as_ref().map_or(0, |i| i.state.capacity())
+        + mark_labels.capacity() * std::mem::size_of::<String>()
+        + mark_labels.iter().map(String::capacity).sum::<usize>()
+        + opt(zone_label)
+}
@@ -0,0 +1,89 @@
+use serde::Serialize;
+
+use crate::daemon::{host_proc, ipvs, AppState};
+use crate::daemon::api::CAPABILITIES;
+use crate::daemon::conntrack::{detect_conntrack_candidate, probe_candidates, CandidateProbe};
+
+/// Whether the kernel is recording per-flow byte/packet counters. When it
+/// isn't, every flow reports zero bytes and throughput can't be computed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Accounting {
+    Enabled,
+    Disabled,
+    Unknown,
+}
+
+pub fn accounting_status() -> Accounting {
+    match read_sysctl_u64("net/netfilter/nf_conntrack_acct") {
+        Some(0) => Accounting::Disabled,
+        Some(_) => Accounting::Enabled,
+        None => Accounting::Unknown,
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct NodeInfo {
+    pub node_name: Option<String>,
+    pub kernel_version: Option<String>,
+    pub daemon_version: String,
+    pub capabilities: Vec<String>,
+    pub conntrack: ConntrackInfo,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConntrackInfo {
+    /// The `CONNTRACK_PATH` the daemon was started with (`auto` by default).
+    pub requested_path: String,
+    /// The file currently being read, if any candidate was usable.
+    pub resolved_path: Option<String>,
+    pub candidates: Vec<CandidateProbe>,
+    pub acct: Option<bool>,
+    pub timestamp: Option<bool>,
+    pub max: Option<u64>,
+    pub count: Option<u64>,
+}
+
+pub fn collect_node_info(state: &AppState) -> NodeInfo {
+    let resolved_path = if state.conntrack_path.is_empty() {
+        detect_conntrack_candidate()
+    } else {
+        Some(state.conntrack_path.clone())
+    };
+
+    NodeInfo {
+        node_name: state.node_name.clone(),
+        kernel_version: read_proc_string("sys/kernel/osrelease"),
+        daemon_version: env!("CARGO_PKG_VERSION").to_string(),
+        capabilities: CAPABILITIES.iter().copied()
+            .chain(state.admin.as_ref().map(|_| "admin"))
+            .chain(state.tcp_info.then_some("tcp_info"))
+            .chain(state.process_attribution.then_some("processes"))
+            .chain(state.all_netns.then_some("netns"))
+            .chain(ipvs::available().then_some("ipvs"))
+            .chain(state.mark_decoding.then_some("mark_decoding"))
+            .map(|c| c.to_string())
+            .collect(),
+        conntrack: ConntrackInfo {
+            requested_path: state.conntrack_requested.clone(),
+            resolved_path,
+            candidates: probe_candidates(),
+            acct: read_sysctl_u64("net/netfilter/nf_conntrack_acct").map(|v| v != 0),
+            timestamp: read_sysctl_u64("net/netfilter/nf_conntrack_timestamp").map(|v| v != 0),
+            max: read_sysctl_u64("net/netfilter/nf_conntrack_max"),
+            count: read_sysctl_u64("net/netfilter/nf_conntrack_count"),
+        },
+    }
+}
+
+fn read_proc_string(rel: &str) -> Option<String> {
+    let path = host_proc(rel)?;
+    let s = std::fs::read_to_string(path).ok()?;
+    let s = s.trim();
+    if s.is_empty() { None } else { Some(s.to_string()) }
+}
+
+/// Reads a numeric sysctl, given relative to `/proc/sys`.
+pub fn read_sysctl_u64(name: &str) -> Option<u64> {
+    read_proc_string(&format!("sys/{}", name))?.parse().ok()
+}
@@ -0,0 +1,223 @@
+use std::collections::HashSet;
+use std::net::IpAddr;
+
+use serde::Serialize;
+
+use crate::daemon::cidr::Cidr;
+use crate::daemon::conntrack::Connection;
+
+/// Where an address sits relative to the cluster.
+#[derive(Debug, Clone, Copy, Serialize, Hash, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum IpClass {
+    Pod,
+    Service,
+    Node,
+    Loopback,
+    LinkLocal,
+    Multicast,
+    /// RFC 1918, RFC 6598 shared space or IPv6 ULA, outside the cluster's
+    /// ranges.
+    Private,
+    Public,
+}
+
+impl IpClass {
+    pub const ALL: [IpClass; 8] = [
+        IpClass::Pod,
+        IpClass::Service,
+        IpClass::Node,
+        IpClass::Loopback,
+        IpClass::LinkLocal,
+        IpClass::Multicast,
+        IpClass::Private,
+        IpClass::Public,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IpClass::Pod => "pod",
+            IpClass::Service => "service",
+            IpClass::Node => "node",
+            IpClass::Loopback => "loopback",
+            IpClass::LinkLocal => "link_local",
+            IpClass::Multicast => "multicast",
+            IpClass::Private => "private",
+            IpClass::Public => "public",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<IpClass> {
+        IpClass::ALL.into_iter().find(|c| c.as_str() == s)
+    }
+
+    /// Pod, Service and node addresses.
+    pub fn in_cluster(&self) -> bool {
+        matches!(self, IpClass::Pod | IpClass::Service | IpClass::Node)
+    }
+}
+
+/// The cluster's address ranges. The daemon reads them from
+/// `KFLOW_POD_CIDRS`, `KFLOW_SERVICE_CIDRS` and `KFLOW_NODE_IPS` (comma
+/// separated); the CLI adds what it learns from the Kubernetes API.
+#[derive(Debug, Clone, Default)]
+pub struct ClusterNetworks {
+    pub pod_cidrs: Vec<Cidr>,
+    pub service_cidrs: Vec<Cidr>,
+    pub node_ips: HashSet<IpAddr>,
+    /// Single pod and endpoint addresses, for CNIs whose ranges are unknown.
+    pub pod_ips: HashSet<IpAddr>,
+    /// Single Service addresses: ClusterIPs, external IPs, load balancers.
+    pub service_ips: HashSet<IpAddr>,
+}
+
+impl ClusterNetworks {
+    pub fn from_env() -> Self {
+        ClusterNetworks {
+            pod_cidrs: cidrs_from_env("KFLOW_POD_CIDRS"),
+            service_cidrs: cidrs_from_env("KFLOW_SERVICE_CIDRS"),
+            node_ips: cidrs_from_env("KFLOW_NODE_IPS").into_iter().map(|c| c.addr).collect(),
+            ..Default::default()
+        }
+    }
+
+    pub fn classify(&self, ip: &IpAddr) -> IpClass {
+        let base = base_class(ip);
+        if matches!(base, IpClass::Loopback | IpClass::LinkLocal | IpClass::Multicast) {
+            return base;
+        }
+        if self.node_ips.contains(ip) {
+            IpClass::Node
+        } else if self.service_ips.contains(ip) || self.service_cidrs.iter().any(|c| c.contains(ip)) {
+            IpClass::Service
+        } else if self.pod_ips.contains(ip) || self.pod_cidrs.iter().any(|c| c.contains(ip)) {
+            IpClass::Pod
+        } else {
+            base
+        }
+    }
+}
+
+/// Classifies both ends of every connection.
+pub fn classify_all(flows: &mut [Connection], networks: &ClusterNetworks) {
+    for c in flows.iter_mut() {
+        c.src_class = networks.classify(&c.src_ip);
+        c.dst_class = networks.classify(&c.dst_ip);
+    }
+}
+
+/// Classification from the address alone, without cluster knowledge.
+pub fn base_class(ip: &IpAddr) -> IpClass {
+    match ip {
+        IpAddr::V4(v4) if v4.is_loopback() => IpClass::Loopback,
+        IpAddr::V4(v4) if v4.is_link_local() => IpClass::LinkLocal,
+        IpAddr::V4(v4) if v4.is_multicast() || v4.is_broadcast() => IpClass::Multicast,
+        IpAddr::V4(v4) if v4.is_private() => IpClass::Private,
+        // 100.64.0.0/10, used by some CNIs and cloud NATs.
+        IpAddr::V4(v4) if v4.octets()[0] == 100 && (v4.octets()[1] & 0xc0) == 64 => IpClass::Private,
+        IpAddr::V6(v6) if v6.is_loopback() => IpClass::Loopback,
+        IpAddr::V6(v6) if (v6.segments()[0] & 0xffc0) == 0xfe80 => IpClass::LinkLocal,
+        IpAddr::V6(v6) if v6.is_multicast() => IpClass::Multicast,
+        IpAddr::V6(v6) if (v6.segments()[0] & 0xfe00) == 0xfc00 => IpClass::Private,
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => base_class(&IpAddr::V4(v4)),
+            None => IpClass::Public,
+        },
+        _ => IpClass::Public,
+    }
+}
+
+fn cidrs_from_env(var: &str) -> Vec<Cidr> {
+    let Ok(raw) = std::env::var(var) else { return Vec::new() };
+    raw.split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .filter_map(|s| match s.parse() {
+            Ok(c) => Some(c),
+            Err(e) => {
+                eprintln!("ignoring {} entry: {}", var, e);
+                None
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn base_classes() {
+        let cases = [
+            ("127.0.0.1", IpClass::Loopback),
+            ("169.254.169.254", IpClass::LinkLocal),
+            ("224.0.0.251", IpClass::Multicast),
+            ("255.255.255.255", IpClass::Multicast),
+            ("10.1.2.3", IpClass::Private),
+            ("172.16.0.1", IpClass::Private),
+            ("192.168.0.1", IpClass::Private),
+            ("100.64.0.1", IpClass::Private),
+            ("100.127.255.255", IpClass::Private),
+            ("100.128.0.1", IpClass::Public),
+            ("8.8.8.8", IpClass::Public),
+            ("::1", IpClass::Loopback),
+            ("fe80::1", IpClass::LinkLocal),
+            ("ff02::fb", IpClass::Multicast),
+            ("fd00::1", IpClass::Private),
+            ("::ffff:10.0.0.1", IpClass::Private),
+            ("::ffff:1.1.1.1", IpClass::Public),
+            ("2606:4700::1111", IpClass::Public),
+        ];
+        for (addr, class) in cases {
+            assert_eq!(base_class(&ip(addr)), class, "{}", addr);
+        }
+    }
+
+    #[test]
+    fn cluster_ranges_take_precedence_in_order() {
+        // Overlapping ranges: the Service range sits inside the pod range,
+        // and a node address inside both.
+        let networks = ClusterNetworks {
+            pod_cidrs: vec!["10.0.0.0/8".parse().unwrap(), "fd00:10::/32".parse().unwrap()],
+            service_cidrs: vec!["10.96.0.0/12".parse().unwrap(), "fd00:10:96::/112".parse().unwrap()],
+            node_ips: HashSet::from([ip("10.96.0.50")]),
+            pod_ips: HashSet::from([ip("172.20.0.9")]),
+            service_ips: HashSet::from([ip("203.0.113.10"), ip("10.244.0.99")]),
+        };
+        let cases = [
+            ("10.96.0.50", IpClass::Node),
+            ("10.96.0.1", IpClass::Service),
+            ("10.244.0.99", IpClass::Service),
+            ("10.244.1.5", IpClass::Pod),
+            ("fd00:10:96::a", IpClass::Service),
+            ("fd00:10:244::5", IpClass::Pod),
+            ("172.20.0.9", IpClass::Pod),
+            ("203.0.113.10", IpClass::Service),
+            ("172.20.0.10", IpClass::Private),
+            ("203.0.113.11", IpClass::Public),
+            ("127.0.0.1", IpClass::Loopback),
+            ("169.254.1.1", IpClass::LinkLocal),
+        ];
+        for (addr, class) in cases {
+            assert_eq!(networks.classify(&ip(addr)), class, "{}", addr);
+        }
+        // Address-scoped classes win even over a range that covers them.
+        let everything = ClusterNetworks { pod_cidrs: vec!["0.0.0.0/0".parse().unwrap()], ..Default::default() };
+        assert_eq!(everything.classify(&ip("127.0.0.1")), IpClass::Loopback);
+        assert_eq!(everything.classify(&ip("224.0.0.1")), IpClass::Multicast);
+        assert_eq!(everything.classify(&ip("8.8.8.8")), IpClass::Pod);
+    }
+
+    #[test]
+    fn names_round_trip() {
+        for class in IpClass::ALL {
+            assert_eq!(IpClass::parse(class.as_str()), Some(class));
+            assert_eq!(serde_json::to_value(class).unwrap(), class.as_str());
+        }
+        assert_eq!(IpClass::parse("external"), None);
+    }
+}
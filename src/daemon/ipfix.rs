@@ -0,0 +1,703 @@
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+use tokio::net::{lookup_host, UdpSocket};
+use tokio::sync::mpsc;
+
+use crate::daemon::conntrack::protocol_number;
+use crate::daemon::flows::FlowRecord;
+
+/// Exports flow records over UDP as IPFIX (RFC 7011) or NetFlow v9 (RFC
+/// 3954), enabled by setting `KFLOW_IPFIX_COLLECTOR` to `host:port`:
+///
+/// - `KFLOW_IPFIX_PROTOCOL`: `ipfix` (default) or `netflow9`
+/// - `KFLOW_IPFIX_ACTIVE_TIMEOUT_SECS`: long-lived flows are reported at least this often (default 60s)
+/// - `KFLOW_IPFIX_TEMPLATE_REFRESH_SECS`: how often templates are resent (default 60s)
+/// - `KFLOW_IPFIX_DOMAIN_ID`: observation domain / source id (default 0)
+#[derive(Debug, Clone)]
+pub struct IpfixConfig {
+    pub collector: String,
+    pub protocol: ExportProtocol,
+    pub active_timeout_secs: u64,
+    pub template_refresh_secs: u64,
+    pub domain_id: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportProtocol {
+    Ipfix,
+    NetflowV9,
+}
+
+impl IpfixConfig {
+    pub fn from_env() -> Option<Self> {
+        let collector = std::env::var("KFLOW_IPFIX_COLLECTOR").ok().filter(|c| !c.is_empty())?;
+        let num = |name: &str| std::env::var(name).ok().and_then(|v| v.parse::<u64>().ok());
+        let protocol = match std::env::var("KFLOW_IPFIX_PROTOCOL").unwrap_or_default().to_ascii_lowercase().as_str() {
+            "netflow9" | "netflow-v9" | "v9" | "9" => ExportProtocol::NetflowV9,
+            "" | "ipfix" | "10" => ExportProtocol::Ipfix,
+            other => {
+                eprintln!("unknown KFLOW_IPFIX_PROTOCOL '{}'; using ipfix", other);
+                ExportProtocol::Ipfix
+            }
+        };
+        Some(IpfixConfig {
+            collector,
+            protocol,
+            active_timeout_secs: num("KFLOW_IPFIX_ACTIVE_TIMEOUT_SECS").unwrap_or(60).max(1),
+            template_refresh_secs: num("KFLOW_IPFIX_TEMPLATE_REFRESH_SECS").unwrap_or(60),
+            domain_id: num("KFLOW_IPFIX_DOMAIN_ID").unwrap_or(0) as u32,
+        })
+    }
+}
+
+const TEMPLATE_V4: u16 = 256;
+const TEMPLATE_V6: u16 = 257;
+
+// Information element ids shared by IPFIX and NetFlow v9.
+const OCTET_DELTA_COUNT: u16 = 1;
+const PACKET_DELTA_COUNT: u16 = 2;
+const PROTOCOL_IDENTIFIER: u16 = 4;
+const SOURCE_TRANSPORT_PORT: u16 = 7;
+const SOURCE_IPV4_ADDRESS: u16 = 8;
+const DESTINATION_TRANSPORT_PORT: u16 = 11;
+const DESTINATION_IPV4_ADDRESS: u16 = 12;
+const SOURCE_IPV6_ADDRESS: u16 = 27;
+const DESTINATION_IPV6_ADDRESS: u16 = 28;
+// NetFlow v9 timestamps are milliseconds of exporter uptime.
+const LAST_SWITCHED: u16 = 21;
+const FIRST_SWITCHED: u16 = 22;
+// IPFIX-only elements.
+const FLOW_END_REASON: u16 = 136;
+const FLOW_START_SECONDS: u16 = 150;
+const FLOW_END_SECONDS: u16 = 151;
+
+const END_REASON_ACTIVE_TIMEOUT: u8 = 2;
+const END_REASON_END_OF_FLOW: u8 = 3;
+
+/// Keeps each UDP datagram comfortably below a 1500-byte MTU.
+const MAX_MESSAGE_BYTES: usize = 1400;
+
+/// Sampling ticks that may queue up for the exporter task before new ones
+/// are dropped.
+const QUEUE_TICKS: usize = 32;
+
+/// Final records kept for retry while the collector is unreachable.
+const MAX_UNSENT: usize = 10_000;
+
+/// Resolving and connecting to the collector gives up after this long.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Octets and packets already reported for a flow, so each record carries
+/// only the delta since the last one.
+struct Reported {
+    start: u64,
+    bytes: u64,
+    packets: u64,
+    at: u64,
+}
+
+/// A record ready for encoding.
+struct Export<'a> {
+    flow: &'a FlowRecord,
+    bytes: u64,
+    packets: u64,
+    end_reason: u8,
+}
+
+/// One encoded datagram and what sending it commits.
+struct Encoded {
+    bytes: Vec<u8>,
+    /// Indexes into the records passed to `encode`.
+    records: Vec<usize>,
+    templates: bool,
+    next_sequence: u32,
+}
+
+/// The flows of one sampling tick, handed to the exporter task.
+pub struct Batch {
+    pub now: u64,
+    pub ended: Vec<FlowRecord>,
+    pub open: Vec<FlowRecord>,
+}
+
+/// Runs an exporter on its own task so resolving and sending never hold up
+/// sampling. Batches that arrive while `QUEUE_TICKS` are already waiting
+/// are dropped.
+pub fn spawn(config: IpfixConfig, now: u64) -> mpsc::Sender<Batch> {
+    let (tx, mut rx) = mpsc::channel::<Batch>(QUEUE_TICKS);
+    let mut exporter = IpfixExporter::new(config, now);
+    tokio::spawn(async move {
+        while let Some(batch) = rx.recv().await {
+            if let Err(e) = exporter.export(batch.now, batch.ended, &batch.open).await {
+                eprintln!("failed to export flows: {}", e);
+            }
+        }
+    });
+    tx
+}
+
+pub struct IpfixExporter {
+    config: IpfixConfig,
+    socket: Option<UdpSocket>,
+    reported: HashMap<String, Reported>,
+    /// Ended flows whose final record hasn't reached the collector yet.
+    unsent: Vec<FlowRecord>,
+    sequence: u32,
+    templates_sent_at: Option<u64>,
+    started_at: u64,
+}
+
+impl IpfixExporter {
+    pub fn new(config: IpfixConfig, now: u64) -> Self {
+        IpfixExporter {
+            config,
+            socket: None,
+            reported: HashMap::new(),
+            unsent: Vec::new(),
+            sequence: 0,
+            templates_sent_at: None,
+            started_at: now,
+        }
+    }
+
+    /// Sends a final record for every flow in `ended` and an interim record
+    /// for every flow in `open` that has not been reported for the active
+    /// timeout. Counters only count as reported once the message carrying
+    /// them is sent; final records that couldn't be sent are retried on the
+    /// next call.
+    pub async fn export(&mut self, now: u64, ended: Vec<FlowRecord>, open: &[FlowRecord]) -> std::io::Result<()> {
+        let mut ended_all = std::mem::take(&mut self.unsent);
+        ended_all.extend(ended);
+
+        let mut records = Vec::new();
+        for flow in &ended_all {
+            let (bytes, packets) = self.delta(flow);
+            records.push(Export { flow, bytes, packets, end_reason: END_REASON_END_OF_FLOW });
+        }
+        for flow in open {
+            let last = match self.reported.get(&flow.id) {
+                Some(r) if r.start == flow.start => r.at,
+                _ => flow.start,
+            };
+            if now.saturating_sub(last) >= self.config.active_timeout_secs {
+                let (bytes, packets) = self.delta(flow);
+                records.push(Export { flow, bytes, packets, end_reason: END_REASON_ACTIVE_TIMEOUT });
+            }
+        }
+        let live: HashSet<&str> = open.iter().chain(&ended_all).map(|f| f.id.as_str()).collect();
+        self.reported.retain(|id, _| live.contains(id.as_str()));
+
+        if records.is_empty() && !self.templates_due(now) {
+            return Ok(());
+        }
+
+        let mut sent = vec![false; records.len()];
+        let result = self.send(now, &records, &mut sent).await;
+        drop(records);
+
+        let mut unsent: Vec<FlowRecord> = ended_all.into_iter().zip(sent).filter(|(_, sent)| !sent).map(|(f, _)| f).collect();
+        if unsent.len() > MAX_UNSENT {
+            eprintln!("flow collector unreachable; dropping {} final flow records", unsent.len() - MAX_UNSENT);
+            unsent.drain(..unsent.len() - MAX_UNSENT);
+        }
+        self.unsent = unsent;
+        result
+    }
+
+    /// Encodes `records` and sends them, committing each message's records,
+    /// templates and sequence number as soon as it is sent. A failed send
+    /// drops the socket so the next call reconnects.
+    async fn send(&mut self, now: u64, records: &[Export<'_>], sent: &mut [bool]) -> std::io::Result<()> {
+        let socket = match self.socket.take() {
+            Some(s) => s,
+            None => {
+                let s = connect(&self.config.collector).await?;
+                // A new socket may mean a restarted collector; resend templates.
+                self.templates_sent_at = None;
+                s
+            }
+        };
+        let messages = self.encode(now, records);
+        for message in &messages {
+            socket.send(&message.bytes).await?;
+            self.sequence = message.next_sequence;
+            if message.templates {
+                self.templates_sent_at = Some(now);
+            }
+            for &i in &message.records {
+                self.commit(&records[i], now);
+                sent[i] = true;
+            }
+        }
+        self.socket = Some(socket);
+        if std::env::var("KFLOW_DEBUG").is_ok() {
+            eprintln!("exported {} flow records to {} in {} messages", records.len(), self.config.collector, messages.len());
+        }
+        Ok(())
+    }
+
+    /// Templates go out with the first message and then every
+    /// `template_refresh_secs`, since a UDP collector may miss or forget them.
+    fn templates_due(&self, now: u64) -> bool {
+        self.templates_sent_at
+            .map(|at| now.saturating_sub(at) >= self.config.template_refresh_secs)
+            .unwrap_or(true)
+    }
+
+    /// Counters for `flow` since it was last reported. A changed start time
+    /// means the 5-tuple was reused, so the earlier counters no longer apply.
+    fn delta(&self, flow: &FlowRecord) -> (u64, u64) {
+        match self.reported.get(&flow.id) {
+            Some(r) if r.start == flow.start => (flow.bytes.saturating_sub(r.bytes), flow.packets.saturating_sub(r.packets)),
+            _ => (flow.bytes, flow.packets),
+        }
+    }
+
+    /// Records a sent record's counters as reported; a final record ends
+    /// the flow's tracking.
+    fn commit(&mut self, r: &Export, now: u64) {
+        let f = r.flow;
+        if r.end_reason == END_REASON_END_OF_FLOW {
+            self.reported.remove(&f.id);
+        } else {
+            self.reported.insert(f.id.clone(), Reported { start: f.start, bytes: f.bytes, packets: f.packets, at: now });
+        }
+    }
+
+    /// Packs templates (when due) and records into as few messages as fit
+    /// under `MAX_MESSAGE_BYTES`.
+    fn encode(&self, now: u64, records: &[Export]) -> Vec<Encoded> {
+        let proto = self.config.protocol;
+        let mut sequence = self.sequence;
+        let mut messages = Vec::new();
+        let mut msg = Message::new(proto);
+        let mut indexes = Vec::new();
+
+        let templates = self.templates_due(now);
+        if templates {
+            msg.add_templates();
+        }
+
+        let (v4, v6): (Vec<_>, Vec<_>) =
+            records.iter().enumerate().partition(|(_, r)| r.flow.src_ip.is_ipv4() && r.flow.dst_ip.is_ipv4());
+        for (template_id, group) in [(TEMPLATE_V4, v4), (TEMPLATE_V6, v6)] {
+            for (i, r) in group {
+                let record = self.encode_record(r);
+                if !msg.fits(template_id, record.len()) {
+                    messages.push(self.finish(msg, now, &mut sequence, std::mem::take(&mut indexes), templates && messages.is_empty()));
+                    msg = Message::new(proto);
+                }
+                msg.add_record(template_id, &record);
+                indexes.push(i);
+            }
+        }
+        if !msg.is_empty() {
+            messages.push(self.finish(msg, now, &mut sequence, indexes, templates && messages.is_empty()));
+        }
+        messages
+    }
+
+    fn encode_record(&self, r: &Export) -> Vec<u8> {
+        let f = r.flow;
+        let mut out = Vec::with_capacity(64);
+        put_ip(&mut out, f.src_ip);
+        put_ip(&mut out, f.dst_ip);
+        out.extend_from_slice(&f.src_port.to_be_bytes());
+        out.extend_from_slice(&f.dst_port.to_be_bytes());
+        out.push(protocol_number(&f.proto));
+        out.extend_from_slice(&r.bytes.to_be_bytes());
+        out.extend_from_slice(&r.packets.to_be_bytes());
+        match self.config.protocol {
+            ExportProtocol::Ipfix => {
+                out.extend_from_slice(&(f.start as u32).to_be_bytes());
+                out.extend_from_slice(&(f.end as u32).to_be_bytes());
+                out.push(r.end_reason);
+            }
+            ExportProtocol::NetflowV9 => {
+                out.extend_from_slice(&self.uptime_ms(f.start).to_be_bytes());
+                out.extend_from_slice(&self.uptime_ms(f.end).to_be_bytes());
+            }
+        }
+        out
+    }
+
+    /// Milliseconds between exporter start and `at`, as NetFlow v9 expects.
+    /// Flows that began before the daemon started are clamped to zero.
+    fn uptime_ms(&self, at: u64) -> u32 {
+        (at.saturating_sub(self.started_at) * 1000) as u32
+    }
+
+    fn finish(&self, msg: Message, now: u64, sequence: &mut u32, records: Vec<usize>, templates: bool) -> Encoded {
+        let (bytes, data_records) = msg.finish(now, self.uptime_ms(now), *sequence, self.config.domain_id);
+        // IPFIX counts data records; NetFlow v9 counts export packets.
+        *sequence = match self.config.protocol {
+            ExportProtocol::Ipfix => sequence.wrapping_add(data_records),
+            ExportProtocol::NetflowV9 => sequence.wrapping_add(1),
+        };
+        Encoded { bytes, records, templates, next_sequence: *sequence }
+    }
+}
+
+async fn connect(collector: &str) -> std::io::Result<UdpSocket> {
+    let timed_out = || std::io::Error::new(std::io::ErrorKind::TimedOut, format!("timed out connecting to {}", collector));
+    tokio::time::timeout(CONNECT_TIMEOUT, async {
+        let addr: SocketAddr = lookup_host(collector).await?.next().ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::NotFound, format!("could not resolve {}", collector))
+        })?;
+        let bind = if addr.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+        let socket = UdpSocket::bind(bind).await?;
+        socket.connect(addr).await?;
+        Ok(socket)
+    })
+    .await
+    .map_err(|_| timed_out())?
+}
+
+fn put_ip(out: &mut Vec<u8>, ip: IpAddr) {
+    match ip {
+        IpAddr::V4(v4) => out.extend_from_slice(&v4.octets()),
+        IpAddr::V6(v6) => out.extend_from_slice(&v6.octets()),
+    }
+}
+
+/// Template fields for one address family, as (element id, length) pairs.
+fn template_fields(protocol: ExportProtocol, v6: bool) -> Vec<(u16, u16)> {
+    let mut fields = if v6 {
+        vec![(SOURCE_IPV6_ADDRESS, 16), (DESTINATION_IPV6_ADDRESS, 16)]
+    } else {
+        vec![(SOURCE_IPV4_ADDRESS, 4), (DESTINATION_IPV4_ADDRESS, 4)]
+    };
+    fields.extend([
+        (SOURCE_TRANSPORT_PORT, 2),
+        (DESTINATION_TRANSPORT_PORT, 2),
+        (PROTOCOL_IDENTIFIER, 1),
+        (OCTET_DELTA_COUNT, 8),
+        (PACKET_DELTA_COUNT, 8),
+    ]);
+    match protocol {
+        ExportProtocol::Ipfix => fields.extend([(FLOW_START_SECONDS, 4), (FLOW_END_SECONDS, 4), (FLOW_END_REASON, 1)]),
+        ExportProtocol::NetflowV9 => fields.extend([(FIRST_SWITCHED, 4), (LAST_SWITCHED, 4)]),
+    }
+    fields
+}
+
+/// One export message under construction: a list of sets, each a set id
+/// and its encoded body.
+struct Message {
+    protocol: ExportProtocol,
+    sets: Vec<(u16, Vec<u8>)>,
+    records: u32,
+    data_records: u32,
+}
+
+impl Message {
+    fn new(protocol: ExportProtocol) -> Self {
+        Message { protocol, sets: Vec::new(), records: 0, data_records: 0 }
+    }
+
+    fn header_len(&self) -> usize {
+        match self.protocol {
+            ExportProtocol::Ipfix => 16,
+            ExportProtocol::NetflowV9 => 20,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.header_len() + self.sets.iter().map(|(_, body)| 4 + body.len() + 3).sum::<usize>()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.sets.is_empty()
+    }
+
+    fn add_templates(&mut self) {
+        let mut body = Vec::new();
+        for (id, v6) in [(TEMPLATE_V4, false), (TEMPLATE_V6, true)] {
+            let fields = template_fields(self.protocol, v6);
+            body.extend_from_slice(&id.to_be_bytes());
+            body.extend_from_slice(&(fields.len() as u16).to_be_bytes());
+            for (element, length) in fields {
+                body.extend_from_slice(&element.to_be_bytes());
+                body.extend_from_slice(&length.to_be_bytes());
+            }
+            self.records += 1;
+        }
+        let set_id = match self.protocol {
+            ExportProtocol::Ipfix => 2,
+            ExportProtocol::NetflowV9 => 0,
+        };
+        self.sets.push((set_id, body));
+    }
+
+    fn fits(&self, template_id: u16, record_len: usize) -> bool {
+        if self.is_empty() {
+            return true;
+        }
+        let extra = match self.sets.last() {
+            Some((id, _)) if *id == template_id => record_len,
+            _ => 4 + record_len + 3,
+        };
+        self.len() + extra <= MAX_MESSAGE_BYTES
+    }
+
+    fn add_record(&mut self, template_id: u16, record: &[u8]) {
+        match self.sets.last_mut() {
+            Some((id, body)) if *id == template_id => body.extend_from_slice(record),
+            _ => self.sets.push((template_id, record.to_vec())),
+        }
+        self.records += 1;
+        self.data_records += 1;
+    }
+
+    /// Serializes the message, returning it with its number of data records.
+    fn finish(self, now: u64, uptime_ms: u32, sequence: u32, domain_id: u32) -> (Vec<u8>, u32) {
+        let mut sets = Vec::new();
+        for (id, body) in &self.sets {
+            // NetFlow v9 requires sets padded to 4 bytes; IPFIX allows it.
+            let padding = (4 - (4 + body.len()) % 4) % 4;
+            sets.extend_from_slice(&id.to_be_bytes());
+            sets.extend_from_slice(&((4 + body.len() + padding) as u16).to_be_bytes());
+            sets.extend_from_slice(body);
+            sets.extend(std::iter::repeat_n(0u8, padding));
+        }
+
+        let mut out = Vec::with_capacity(self.header_len() + sets.len());
+        match self.protocol {
+            ExportProtocol::Ipfix => {
+                out.extend_from_slice(&10u16.to_be_bytes());
+                out.extend_from_slice(&((16 + sets.len()) as u16).to_be_bytes());
+                out.extend_from_slice(&(now as u32).to_be_bytes());
+                out.extend_from_slice(&sequence.to_be_bytes());
+                out.extend_from_slice(&domain_id.to_be_bytes());
+            }
+            ExportProtocol::NetflowV9 => {
+                out.extend_from_slice(&9u16.to_be_bytes());
+                out.extend_from_slice(&(self.records as u16).to_be_bytes());
+                out.extend_from_slice(&uptime_ms.to_be_bytes());
+                out.extend_from_slice(&(now as u32).to_be_bytes());
+                out.extend_from_slice(&sequence.to_be_bytes());
+                out.extend_from_slice(&domain_id.to_be_bytes());
+            }
+        }
+        out.extend_from_slice(&sets);
+        (out, self.data_records)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::daemon::conntrack::StartSource;
+
+    fn flow(src: &str, dst: &str, start: u64, bytes: u64) -> FlowRecord {
+        FlowRecord {
+            node_name: None,
+            id: format!("tcp-{}-40000-{}-443", src, dst),
+            proto: "tcp".into(),
+            src_ip: src.parse().unwrap(),
+            src_port: 40000,
+            dst_ip: dst.parse().unwrap(),
+            dst_port: 443,
+            netns: None,
+            start,
+            end: start + 5,
+            start_source: StartSource::Kernel,
+            bytes,
+            packets: bytes / 100,
+            states: Vec::new(),
+        }
+    }
+
+    fn config(collector: String, protocol: ExportProtocol) -> IpfixConfig {
+        IpfixConfig { collector, protocol, active_timeout_secs: 10, template_refresh_secs: 60, domain_id: 7 }
+    }
+
+    async fn collector() -> (UdpSocket, String) {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = socket.local_addr().unwrap().to_string();
+        (socket, addr)
+    }
+
+    async fn receive(socket: &UdpSocket) -> Vec<u8> {
+        let mut buf = vec![0u8; 65536];
+        let n = tokio::time::timeout(Duration::from_secs(2), socket.recv(&mut buf)).await.expect("no datagram").unwrap();
+        buf.truncate(n);
+        buf
+    }
+
+    fn u16_at(b: &[u8], at: usize) -> u16 {
+        u16::from_be_bytes([b[at], b[at + 1]])
+    }
+
+    fn u32_at(b: &[u8], at: usize) -> u32 {
+        u32::from_be_bytes(b[at..at + 4].try_into().unwrap())
+    }
+
+    fn u64_at(b: &[u8], at: usize) -> u64 {
+        u64::from_be_bytes(b[at..at + 8].try_into().unwrap())
+    }
+
+    /// A decoded message: version, sequence and its sets as (id, body).
+    struct Decoded {
+        version: u16,
+        count: u16,
+        sequence: u32,
+        domain: u32,
+        sets: Vec<(u16, Vec<u8>)>,
+    }
+
+    fn decode(b: &[u8]) -> Decoded {
+        let version = u16_at(b, 0);
+        let (header, sequence, domain) = match version {
+            10 => {
+                assert_eq!(u16_at(b, 2) as usize, b.len(), "IPFIX length field");
+                (16, u32_at(b, 8), u32_at(b, 12))
+            }
+            9 => (20, u32_at(b, 12), u32_at(b, 16)),
+            v => panic!("unexpected version {}", v),
+        };
+        let mut sets = Vec::new();
+        let mut at = header;
+        while at < b.len() {
+            let (id, len) = (u16_at(b, at), u16_at(b, at + 2) as usize);
+            assert_eq!(len % 4, 0, "sets are padded to 4 bytes");
+            sets.push((id, b[at + 4..at + len].to_vec()));
+            at += len;
+        }
+        assert_eq!(at, b.len());
+        Decoded { version, count: u16_at(b, 2), sequence, domain, sets }
+    }
+
+    /// Record length for a template, from the exporter's own field list.
+    fn record_len(protocol: ExportProtocol, template: u16) -> usize {
+        template_fields(protocol, template == TEMPLATE_V6).iter().map(|(_, len)| *len as usize).sum()
+    }
+
+    /// Data records in a message, as (template id, record bytes).
+    fn records(protocol: ExportProtocol, d: &Decoded) -> Vec<(u16, Vec<u8>)> {
+        let mut out = Vec::new();
+        for (id, body) in &d.sets {
+            if *id < 256 {
+                continue;
+            }
+            let len = record_len(protocol, *id);
+            out.extend(body.chunks_exact(len).map(|r| (*id, r.to_vec())));
+        }
+        out
+    }
+
+    #[tokio::test]
+    async fn ipfix_sends_templates_first_then_records() {
+        let (listener, addr) = collector().await;
+        let mut exporter = IpfixExporter::new(config(addr, ExportProtocol::Ipfix), 1000);
+        let ended = vec![flow("10.0.0.1", "10.0.0.2", 990, 500)];
+        let open = vec![flow("fd00::1", "fd00::2", 980, 2000), flow("10.0.0.3", "10.0.0.4", 999, 100)];
+        exporter.export(1000, ended, &open).await.unwrap();
+
+        let d = decode(&receive(&listener).await);
+        assert_eq!(d.version, 10);
+        assert_eq!(d.sequence, 0);
+        assert_eq!(d.domain, 7);
+        assert_eq!(d.sets[0].0, 2, "template set comes first");
+        assert_eq!(u16_at(&d.sets[0].1, 0), TEMPLATE_V4);
+        let recs = records(ExportProtocol::Ipfix, &d);
+        // The ended flow and the v6 flow past the active timeout; the flow
+        // that started a second ago isn't due yet.
+        assert_eq!(recs.len(), 2);
+        let (template, v4) = &recs[0];
+        assert_eq!(*template, TEMPLATE_V4);
+        assert_eq!(u64_at(v4, 13), 500);
+        assert_eq!(*v4.last().unwrap(), END_REASON_END_OF_FLOW);
+        let (template, v6) = &recs[1];
+        assert_eq!(*template, TEMPLATE_V6);
+        assert_eq!(u64_at(v6, 37), 2000);
+        assert_eq!(*v6.last().unwrap(), END_REASON_ACTIVE_TIMEOUT);
+
+        // Within the refresh interval: data only, sequence advanced by the
+        // two data records, and only the bytes since the last report.
+        let open = vec![flow("fd00::1", "fd00::2", 980, 2600)];
+        exporter.export(1010, Vec::new(), &open).await.unwrap();
+        let d = decode(&receive(&listener).await);
+        assert_eq!(d.sequence, 2);
+        assert!(d.sets.iter().all(|(id, _)| *id >= 256), "no templates before refresh");
+        let recs = records(ExportProtocol::Ipfix, &d);
+        assert_eq!(recs.len(), 1);
+        assert_eq!(u64_at(&recs[0].1, 37), 600);
+
+        // After template_refresh_secs the templates go out again.
+        exporter.export(1060, vec![flow("10.0.0.5", "10.0.0.6", 1050, 10)], &[]).await.unwrap();
+        let d = decode(&receive(&listener).await);
+        assert_eq!(d.sets[0].0, 2);
+        assert_eq!(d.sequence, 3);
+    }
+
+    #[tokio::test]
+    async fn netflow9_counts_records_and_packets() {
+        let (listener, addr) = collector().await;
+        let mut exporter = IpfixExporter::new(config(addr, ExportProtocol::NetflowV9), 1000);
+        let ended = vec![flow("10.0.0.1", "10.0.0.2", 990, 500), flow("10.0.0.1", "10.0.0.9", 995, 50)];
+        exporter.export(1000, ended, &[]).await.unwrap();
+
+        let d = decode(&receive(&listener).await);
+        assert_eq!(d.version, 9);
+        assert_eq!(d.domain, 7);
+        assert_eq!(d.sets[0].0, 0, "template flowset comes first");
+        // v9's count covers template and data records alike.
+        assert_eq!(d.count, 2 + 2);
+        assert_eq!(records(ExportProtocol::NetflowV9, &d).len(), 2);
+        assert_eq!(d.sequence, 0);
+
+        exporter.export(1002, vec![flow("10.0.0.1", "10.0.0.3", 1001, 10)], &[]).await.unwrap();
+        let d = decode(&receive(&listener).await);
+        assert_eq!(d.count, 1);
+        // v9 sequence numbers count export packets.
+        assert_eq!(d.sequence, 1);
+    }
+
+    #[tokio::test]
+    async fn large_batches_split_across_messages() {
+        let (listener, addr) = collector().await;
+        let mut exporter = IpfixExporter::new(config(addr, ExportProtocol::Ipfix), 1000);
+        let ended: Vec<FlowRecord> = (0..100).map(|i| flow(&format!("10.0.1.{}", i), "10.0.0.2", 990, 10)).collect();
+        exporter.export(1000, ended, &[]).await.unwrap();
+        let mut total = 0;
+        let mut expected_sequence = 0;
+        while total < 100 {
+            let bytes = receive(&listener).await;
+            assert!(bytes.len() <= MAX_MESSAGE_BYTES);
+            let d = decode(&bytes);
+            assert_eq!(d.sequence, expected_sequence);
+            let n = records(ExportProtocol::Ipfix, &d).len() as u32;
+            expected_sequence += n;
+            total += n;
+        }
+        assert_eq!(total, 100);
+    }
+
+    #[tokio::test]
+    async fn failed_sends_keep_counters_for_the_next_attempt() {
+        let (listener, addr) = collector().await;
+        let mut exporter = IpfixExporter::new(config("127.0.0.1:not-a-port".into(), ExportProtocol::Ipfix), 1000);
+        let open = vec![flow("10.0.0.3", "10.0.0.4", 980, 1000)];
+        assert!(exporter.export(1000, vec![flow("10.0.0.1", "10.0.0.2", 990, 500)], &open).await.is_err());
+        assert!(exporter.reported.is_empty());
+        assert!(exporter.templates_sent_at.is_none());
+        assert_eq!(exporter.sequence, 0);
+
+        exporter.config.collector = addr;
+        let open = vec![flow("10.0.0.3", "10.0.0.4", 980, 1500)];
+        exporter.export(1002, Vec::new(), &open).await.unwrap();
+        let d = decode(&receive(&listener).await);
+        assert_eq!(d.sets[0].0, 2, "templates weren't marked sent by the failed attempt");
+        let recs = records(ExportProtocol::Ipfix, &d);
+        assert_eq!(recs.len(), 2);
+        // The unsent final record is retried, and the open flow reports
+        // everything since its start.
+        assert_eq!(u64_at(&recs[0].1, 13), 500);
+        assert_eq!(*recs[0].1.last().unwrap(), END_REASON_END_OF_FLOW);
+        assert_eq!(u64_at(&recs[1].1, 13), 1500);
+        assert!(exporter.unsent.is_empty());
+    }
+}
@@ -0,0 +1,284 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr};
+
+use serde::Serialize;
+
+use crate::daemon::conntrack::Connection;
+use crate::daemon::host_proc;
+
+/// An IPVS virtual service, e.g. a ClusterIP:port under kube-proxy's IPVS
+/// mode, and the real servers it balances over.
+#[derive(Debug, Clone, Serialize)]
+pub struct VirtualService {
+    /// `tcp`, `udp`, `sctp`, or `fwm` for firewall-mark services.
+    pub proto: String,
+    pub address: Option<IpAddr>,
+    pub port: Option<u16>,
+    pub fwmark: Option<u32>,
+    pub scheduler: String,
+    /// Anything after the scheduler, e.g. `persistent 10800`.
+    pub flags: Option<String>,
+    pub real_servers: Vec<RealServer>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RealServer {
+    pub address: IpAddr,
+    pub port: u16,
+    /// `Masq`, `Route`, `Tunnel` or `Local`.
+    pub forward: String,
+    pub weight: i64,
+    pub active_conns: u64,
+    pub inactive_conns: u64,
+    /// Current conntrack flows joined to this real server.
+    pub flows: usize,
+}
+
+/// The real server IPVS scheduled a flow to, from `/proc/net/ip_vs_conn`.
+#[derive(Debug, Clone, Serialize, Hash, PartialEq, Eq)]
+pub struct IpvsRef {
+    pub real_ip: IpAddr,
+    pub real_port: u16,
+    /// IPVS connection state, e.g. `ESTABLISHED`, `FIN_WAIT`, `UDP`.
+    pub state: String,
+}
+
+/// Client and virtual endpoint of an IPVS connection, as conntrack sees the
+/// flow's original direction.
+type IpvsKey = (String, IpAddr, u16, IpAddr, u16);
+
+/// Whether the node has an IPVS table; kube-proxy in IPVS mode loads the
+/// `ip_vs` module, which creates it.
+pub fn available() -> bool {
+    ipvs_path("ip_vs").is_some()
+}
+
+/// IPVS state lives in the host network namespace, which PID 1 is in even
+/// when the daemon isn't.
+fn ipvs_path(table: &str) -> Option<std::path::PathBuf> {
+    host_proc(&format!("1/net/{}", table)).or_else(|| host_proc(&format!("net/{}", table)))
+}
+
+/// Reads `/proc/net/ip_vs`.
+pub fn read_virtual_services() -> Vec<VirtualService> {
+    let Some(content) = ipvs_path("ip_vs").and_then(|p| std::fs::read_to_string(p).ok()) else { return Vec::new() };
+    parse_virtual_services(&content)
+}
+
+/// Parses e.g.
+/// ```text
+/// Prot LocalAddress:Port Scheduler Flags
+///   -> RemoteAddress:Port Forward Weight ActiveConn InActConn
+/// TCP  0A600001:01BB rr
+///   -> AC120003:192B      Masq    1      2          0
+/// FWM  00000001 wrr persistent 10800
+/// ```
+/// IPv6 addresses appear as `[2001:0db8:...:0001]:0050`.
+fn parse_virtual_services(content: &str) -> Vec<VirtualService> {
+    let mut out: Vec<VirtualService> = Vec::new();
+    for line in content.lines().skip(3) {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        if parts.first() == Some(&"->") {
+            let (Some(vs), Some((address, port))) = (out.last_mut(), parts.get(1).and_then(|s| parse_ipvs_endpoint(s))) else { continue };
+            if parts.len() < 6 {
+                continue;
+            }
+            vs.real_servers.push(RealServer {
+                address,
+                port,
+                forward: parts[2].to_string(),
+                weight: parts[3].parse().unwrap_or(0),
+                active_conns: parts[4].parse().unwrap_or(0),
+                inactive_conns: parts[5].parse().unwrap_or(0),
+                flows: 0,
+            });
+        } else if parts.len() >= 3 {
+            let proto = parts[0].to_ascii_lowercase();
+            let (address, port, fwmark) = if proto == "fwm" {
+                (None, None, u32::from_str_radix(parts[1], 16).ok())
+            } else {
+                let Some((ip, port)) = parse_ipvs_endpoint(parts[1]) else { continue };
+                (Some(ip), Some(port), None)
+            };
+            let flags = (parts.len() > 3).then(|| parts[3..].join(" "));
+            out.push(VirtualService { proto, address, port, fwmark, scheduler: parts[2].to_string(), flags, real_servers: Vec::new() });
+        }
+    }
+    out
+}
+
+/// Decodes `0A600001:01BB` (IPv4 in network order) or `[v6]:0050`.
+fn parse_ipvs_endpoint(s: &str) -> Option<(IpAddr, u16)> {
+    let (addr, port) = s.rsplit_once(':')?;
+    Some((parse_ipvs_addr(addr)?, u16::from_str_radix(port, 16).ok()?))
+}
+
+fn parse_ipvs_addr(s: &str) -> Option<IpAddr> {
+    if let Some(v6) = s.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+        return v6.parse().ok();
+    }
+    if s.contains(':') {
+        return s.parse().ok();
+    }
+    Some(IpAddr::V4(Ipv4Addr::from(u32::from_str_radix(s, 16).ok()?)))
+}
+
+/// Reads `/proc/net/ip_vs_conn`, keyed by the flow's original tuple:
+/// ```text
+/// Pro FromIP   FPrt ToIP     TPrt DestIP   DPrt State       Expires PEName PEData
+/// TCP 0A000001 D431 0A600001 01BB AC120003 192B ESTABLISHED     899
+/// ```
+pub fn read_connections() -> HashMap<IpvsKey, IpvsRef> {
+    let Some(content) = ipvs_path("ip_vs_conn").and_then(|p| std::fs::read_to_string(p).ok()) else { return HashMap::new() };
+    parse_connections(&content)
+}
+
+/// IPv6 rows print all three addresses in full, without brackets.
+fn parse_connections(content: &str) -> HashMap<IpvsKey, IpvsRef> {
+    let mut out = HashMap::new();
+    for line in content.lines().skip(1) {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        if parts.len() < 8 {
+            continue;
+        }
+        let port = |s: &str| u16::from_str_radix(s, 16).ok();
+        let (Some(client), Some(client_port), Some(vip), Some(vport), Some(real_ip), Some(real_port)) = (
+            parse_ipvs_addr(parts[1]),
+            port(parts[2]),
+            parse_ipvs_addr(parts[3]),
+            port(parts[4]),
+            parse_ipvs_addr(parts[5]),
+            port(parts[6]),
+        ) else {
+            continue;
+        };
+        let state = parts[7].to_string();
+        out.insert((parts[0].to_ascii_lowercase(), client, client_port, vip, vport), IpvsRef { real_ip, real_port, state });
+    }
+    out
+}
+
+/// Sets `ipvs` on flows that IPVS scheduled, matching the client and
+/// virtual endpoint against the flow's original direction.
+pub fn join_connections(flows: &mut [Connection], conns: &HashMap<IpvsKey, IpvsRef>) {
+    for c in flows.iter_mut() {
+        c.ipvs = conns.get(&(c.proto.clone(), c.src_ip, c.src_port, c.dst_ip, c.dst_port)).cloned();
+    }
+}
+
+/// Counts the current flows scheduled to each real server.
+pub fn count_flows(services: &mut [VirtualService], flows: &[Connection]) {
+    let mut counts: HashMap<(&str, IpAddr, u16, IpAddr, u16), usize> = HashMap::new();
+    for c in flows {
+        if let Some(r) = &c.ipvs {
+            *counts.entry((c.proto.as_str(), c.dst_ip, c.dst_port, r.real_ip, r.real_port)).or_default() += 1;
+        }
+    }
+    for vs in services.iter_mut() {
+        let (Some(vip), Some(vport)) = (vs.address, vs.port) else { continue };
+        for rs in vs.real_servers.iter_mut() {
+            rs.flows = counts.get(&(vs.proto.as_str(), vip, vport, rs.address, rs.port)).copied().unwrap_or(0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::daemon::conntrack::parse_conntrack_line;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn decodes_endpoints() {
+        let cases: &[(&str, Option<(&str, u16)>)] = &[
+            ("0A600001:01BB", Some(("10.96.0.1", 443))),
+            ("AC120003:192B", Some(("172.18.0.3", 6443))),
+            ("00000000:0000", Some(("0.0.0.0", 0))),
+            ("[2001:0db8:0000:0000:0000:0000:0000:0001]:0050", Some(("2001:db8::1", 80))),
+            ("[fd00:0000:0000:0000:0000:0000:0000:000a]:01BB", Some(("fd00::a", 443))),
+            ("0A600001", None),
+            ("ZZ600001:01BB", None),
+            ("0A600001:1FFFF", None),
+        ];
+        for (raw, want) in cases {
+            assert_eq!(parse_ipvs_endpoint(raw), want.map(|(a, p)| (ip(a), p)), "{}", raw);
+        }
+    }
+
+    const IP_VS: &str = "\
+IP Virtual Server version 1.2.1 (size=4096)
+Prot LocalAddress:Port Scheduler Flags
+  -> RemoteAddress:Port Forward Weight ActiveConn InActConn
+TCP  0A600001:01BB rr
+  -> AC120003:192B                Masq    1      2          0
+  -> AC120004:192B                Local   1      0          3
+UDP  0A60000A:0035 rr
+  -> 0A01000C:0035                Masq    1      0          7
+TCP  [fd00:0000:0000:0000:0000:0000:0000:000a]:0050 wrr
+  -> [fd00:0000:0000:0000:0000:0000:0001:0005]:1F90      Masq    2      1          0
+FWM  00000001 sh persistent 10800 FFFFFFFF
+  -> 0A010005:0000                Route   1      0          0
+";
+
+    #[test]
+    fn parses_virtual_services() {
+        let services = parse_virtual_services(IP_VS);
+        assert_eq!(services.len(), 4);
+
+        let tcp = &services[0];
+        assert_eq!((tcp.proto.as_str(), tcp.address, tcp.port, tcp.fwmark), ("tcp", Some(ip("10.96.0.1")), Some(443), None));
+        assert_eq!((tcp.scheduler.as_str(), tcp.flags.as_deref()), ("rr", None));
+        let rs: Vec<(IpAddr, u16, &str, i64, u64, u64)> = tcp.real_servers.iter()
+            .map(|r| (r.address, r.port, r.forward.as_str(), r.weight, r.active_conns, r.inactive_conns))
+            .collect();
+        assert_eq!(rs, [(ip("172.18.0.3"), 6443, "Masq", 1, 2, 0), (ip("172.18.0.4"), 6443, "Local", 1, 0, 3)]);
+
+        assert_eq!((services[1].proto.as_str(), services[1].port, services[1].real_servers[0].inactive_conns), ("udp", Some(53), 7));
+
+        let v6 = &services[2];
+        assert_eq!((v6.address, v6.port, v6.scheduler.as_str()), (Some(ip("fd00::a")), Some(80), "wrr"));
+        assert_eq!((v6.real_servers[0].address, v6.real_servers[0].port, v6.real_servers[0].weight), (ip("fd00::1:5"), 8080, 2));
+
+        let fwm = &services[3];
+        assert_eq!((fwm.proto.as_str(), fwm.address, fwm.port, fwm.fwmark), ("fwm", None, None, Some(1)));
+        assert_eq!(fwm.flags.as_deref(), Some("persistent 10800 FFFFFFFF"));
+        assert_eq!(fwm.real_servers[0].forward, "Route");
+    }
+
+    const IP_VS_CONN: &str = "\
+Pro FromIP   FPrt ToIP     TPrt DestIP   DPrt State       Expires PEName PEData
+TCP 0A000001 D431 0A600001 01BB AC120003 192B ESTABLISHED     899
+UDP 0A01000C 9C40 0A60000A 0035 0A01000C 0035 UDP             297
+TCP fd00:0000:0000:0000:0000:0000:0002:0001 C350 fd00:0000:0000:0000:0000:0000:0000:000a 0050 fd00:0000:0000:0000:0000:0000:0001:0005 1F90 FIN_WAIT         60
+TCP 0A000001 D432 truncated
+";
+
+    #[test]
+    fn parses_and_joins_connections() {
+        let conns = parse_connections(IP_VS_CONN);
+        assert_eq!(conns.len(), 3);
+        let v4 = &conns[&("tcp".to_string(), ip("10.0.0.1"), 0xD431, ip("10.96.0.1"), 443)];
+        assert_eq!((v4.real_ip, v4.real_port, v4.state.as_str()), (ip("172.18.0.3"), 6443, "ESTABLISHED"));
+        let v6 = &conns[&("tcp".to_string(), ip("fd00::2:1"), 50000, ip("fd00::a"), 80)];
+        assert_eq!((v6.real_ip, v6.real_port, v6.state.as_str()), (ip("fd00::1:5"), 8080, "FIN_WAIT"));
+
+        let mut flows: Vec<Connection> = [
+            "ipv4 2 tcp 6 86399 ESTABLISHED src=10.0.0.1 dst=10.96.0.1 sport=54321 dport=443 src=172.18.0.3 dst=10.0.0.1 sport=6443 dport=54321 [ASSURED] mark=0 use=1",
+            "ipv4 2 tcp 6 86399 ESTABLISHED src=10.0.0.1 dst=10.96.0.1 sport=54322 dport=443 src=10.96.0.1 dst=10.0.0.1 sport=443 dport=54322 [ASSURED] mark=0 use=1",
+        ]
+        .iter()
+        .filter_map(|l| parse_conntrack_line(l))
+        .collect();
+        join_connections(&mut flows, &conns);
+        assert_eq!(flows[0].ipvs.as_ref().map(|r| (r.real_ip, r.real_port)), Some((ip("172.18.0.3"), 6443)));
+        assert!(flows[1].ipvs.is_none());
+
+        let mut services = parse_virtual_services(IP_VS);
+        count_flows(&mut services, &flows);
+        let counts: Vec<usize> = services[0].real_servers.iter().map(|r| r.flows).collect();
+        assert_eq!(counts, [1, 0]);
+    }
+}
@@ -0,0 +1,282 @@
+use serde::{Deserialize, Deserializer};
+
+use crate::daemon::conntrack::Connection;
+
+/// Turns conntrack `mark=` and `zone=` values into labels. Enabled by
+/// `KFLOW_CNI` (`cilium` or `calico`, a built-in preset) and/or
+/// `KFLOW_MARK_DECODERS`, the path of a JSON file:
+///
+/// ```json
+/// {
+///   "presets": ["cilium"],
+///   "marks": [
+///     { "mask": "0x0f00", "value": "0x0200", "label": "to proxy" },
+///     { "mask": "0x0f00", "value": "0x0f00", "field": "0xffff0000", "label": "identity {}" }
+///   ],
+///   "zones": [ { "zone": 1, "label": "tenant-a" } ]
+/// }
+/// ```
+///
+/// Every mark rule whose `mask` bits equal `value` adds its label; without
+/// `value`, any of the `mask` bits being set is a match. `field` bits,
+/// shifted down, replace `{}`. Numbers may be integers or hex strings.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct MarkDecoders {
+    #[serde(default)]
+    pub presets: Vec<String>,
+    #[serde(default)]
+    pub marks: Vec<MarkRule>,
+    #[serde(default)]
+    pub zones: Vec<ZoneRule>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct MarkRule {
+    #[serde(deserialize_with = "number")]
+    pub mask: u32,
+    #[serde(default, deserialize_with = "optional_number")]
+    pub value: Option<u32>,
+    #[serde(default, deserialize_with = "optional_number")]
+    pub field: Option<u32>,
+    pub label: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ZoneRule {
+    pub zone: u16,
+    pub label: String,
+}
+
+impl MarkRule {
+    fn new(mask: u32, value: Option<u32>, field: Option<u32>, label: &str) -> Self {
+        MarkRule { mask, value, field, label: label.to_string() }
+    }
+
+    fn decode(&self, mark: u32) -> Option<String> {
+        let matched = match self.value {
+            Some(value) => mark & self.mask == value,
+            None => mark & self.mask != 0,
+        };
+        if !matched {
+            return None;
+        }
+        Some(match self.field {
+            Some(field) if field != 0 => self.label.replace("{}", &((mark & field) >> field.trailing_zeros()).to_string()),
+            _ => self.label.clone(),
+        })
+    }
+}
+
+/// Cilium's `MARK_MAGIC_*` values in bits 8-11; identity-carrying marks
+/// keep the security identity in the upper 16 bits.
+fn cilium() -> Vec<MarkRule> {
+    vec![
+        MarkRule::new(0x0f00, Some(0x0200), None, "cilium: to proxy"),
+        MarkRule::new(0x0f00, Some(0x0300), None, "cilium: SNAT done"),
+        MarkRule::new(0x0f00, Some(0x0400), None, "cilium: overlay"),
+        MarkRule::new(0x0f00, Some(0x0500), None, "cilium: egress gateway done"),
+        MarkRule::new(0x0f00, Some(0x0a00), Some(0xffff_0000), "cilium: from ingress proxy, identity {}"),
+        MarkRule::new(0x0f00, Some(0x0b00), Some(0xffff_0000), "cilium: from egress proxy, identity {}"),
+        MarkRule::new(0x0f00, Some(0x0c00), None, "cilium: from host"),
+        MarkRule::new(0x0f00, Some(0x0d00), None, "cilium: decrypted"),
+        MarkRule::new(0x0f00, Some(0x0e00), None, "cilium: to encrypt"),
+        MarkRule::new(0x0f00, Some(0x0f00), Some(0xffff_0000), "cilium: identity {}"),
+    ]
+}
+
+/// Felix's bits under its default `IptablesMarkMask` of `0xffff0000`.
+fn calico() -> Vec<MarkRule> {
+    vec![
+        MarkRule::new(0x0001_0000, None, None, "calico: policy accept"),
+        MarkRule::new(0x0002_0000, None, None, "calico: policy pass"),
+        MarkRule::new(0xfff0_0000, None, Some(0xfff0_0000), "calico: endpoint mark {}"),
+    ]
+}
+
+impl MarkDecoders {
+    pub fn from_env() -> Option<Self> {
+        let decoders = match std::env::var("KFLOW_MARK_DECODERS").ok().filter(|p| !p.is_empty()) {
+            Some(path) => match std::fs::read_to_string(&path).map_err(|e| e.to_string()).and_then(|s| MarkDecoders::parse(&s)) {
+                Ok(d) => d,
+                Err(e) => {
+                    eprintln!("failed to load mark decoders from {}: {}; mark decoding disabled", path, e);
+                    return None;
+                }
+            },
+            None => MarkDecoders::default(),
+        };
+        decoders.with_presets(std::env::var("KFLOW_CNI").ok().filter(|c| !c.is_empty()))
+    }
+
+    fn parse(json: &str) -> Result<Self, String> {
+        serde_json::from_str(json).map_err(|e| e.to_string())
+    }
+
+    /// Expands the configured presets, plus `cni`, into rules. `None` when
+    /// there is nothing to decode.
+    fn with_presets(mut self, cni: Option<String>) -> Option<Self> {
+        self.presets.extend(cni);
+        for preset in std::mem::take(&mut self.presets) {
+            match preset.to_ascii_lowercase().as_str() {
+                "cilium" => self.marks.extend(cilium()),
+                "calico" => self.marks.extend(calico()),
+                other => eprintln!("unknown mark decoder preset '{}'; known: cilium, calico", other),
+            }
+        }
+        (!self.marks.is_empty() || !self.zones.is_empty()).then_some(self)
+    }
+
+    /// Labels for every rule that matches a non-zero mark.
+    pub fn decode_mark(&self, mark: u32) -> Vec<String> {
+        if mark == 0 {
+            return Vec::new();
+        }
+        self.marks.iter().filter_map(|r| r.decode(mark)).collect()
+    }
+
+    pub fn decode_zone(&self, zone: u16) -> Option<String> {
+        self.zones.iter().find(|z| z.zone == zone).map(|z| z.label.clone())
+    }
+
+    pub fn decode_all(&self, flows: &mut [Connection]) {
+        for c in flows.iter_mut() {
+            c.mark_labels = c.mark.map(|m| self.decode_mark(m)).unwrap_or_default();
+            c.zone_label = c.zone.and_then(|z| self.decode_zone(z));
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Number {
+    Int(u64),
+    Text(String),
+}
+
+fn parse_number(n: Number) -> Result<u32, String> {
+    let v = match n {
+        Number::Int(v) => v,
+        Number::Text(s) => {
+            let s = s.trim();
+            match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+                Some(hex) => u64::from_str_radix(hex, 16).map_err(|e| format!("{}: {}", s, e))?,
+                None => s.parse().map_err(|e| format!("{}: {}", s, e))?,
+            }
+        }
+    };
+    u32::try_from(v).map_err(|_| format!("{} does not fit in 32 bits", v))
+}
+
+fn number<'de, D: Deserializer<'de>>(d: D) -> Result<u32, D::Error> {
+    parse_number(Number::deserialize(d)?).map_err(serde::de::Error::custom)
+}
+
+fn optional_number<'de, D: Deserializer<'de>>(d: D) -> Result<Option<u32>, D::Error> {
+    Option::<Number>::deserialize(d)?.map(parse_number).transpose().map_err(serde::de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_rules_with_hex_and_integer_numbers() {
+        let d = MarkDecoders::parse(r#"{
+            "marks": [
+                { "mask": "0x0f00", "value": "0x0200", "label": "to proxy" },
+                { "mask": 3840, "value": 3840, "field": "0XFFFF0000", "label": "identity {}" },
+                { "mask": " 16 ", "label": "bit 4" }
+            ],
+            "zones": [ { "zone": 1, "label": "tenant-a" } ]
+        }"#).unwrap();
+        let rules: Vec<(u32, Option<u32>, Option<u32>)> = d.marks.iter().map(|r| (r.mask, r.value, r.field)).collect();
+        assert_eq!(rules, [(0x0f00, Some(0x0200), None), (0x0f00, Some(0x0f00), Some(0xffff_0000)), (16, None, None)]);
+        assert_eq!(d.zones[0].zone, 1);
+        assert!(d.presets.is_empty());
+    }
+
+    #[test]
+    fn rejects_malformed_rules() {
+        let bad = [
+            r#"{ "marks": [ { "mask": "0xZZ", "label": "x" } ] }"#,
+            r#"{ "marks": [ { "mask": "0x100000000", "label": "x" } ] }"#,
+            r#"{ "marks": [ { "mask": 4294967296, "label": "x" } ] }"#,
+            r#"{ "marks": [ { "mask": -1, "label": "x" } ] }"#,
+            r#"{ "marks": [ { "mask": "12abc", "label": "x" } ] }"#,
+            r#"{ "marks": [ { "mask": "0x10" } ] }"#,
+            r#"{ "marks": [ { "label": "x" } ] }"#,
+            r#"{ "zones": [ { "zone": 70000, "label": "x" } ] }"#,
+            r#"{ "marks": {} }"#,
+            "not json",
+        ];
+        for json in bad {
+            assert!(MarkDecoders::parse(json).is_err(), "{}", json);
+        }
+    }
+
+    #[test]
+    fn presets_expand_and_unknown_ones_are_skipped() {
+        assert!(MarkDecoders::default().with_presets(None).is_none());
+        assert!(MarkDecoders::default().with_presets(Some("flannel".into())).is_none());
+        let d = MarkDecoders::parse(r#"{ "presets": ["Calico"] }"#).unwrap().with_presets(Some("cilium".into())).unwrap();
+        assert_eq!(d.marks.len(), calico().len() + cilium().len());
+        assert!(d.presets.is_empty());
+    }
+
+    #[test]
+    fn masks_values_and_fields() {
+        let d = MarkDecoders::parse(r#"{
+            "marks": [
+                { "mask": "0x0f00", "value": "0x0200", "label": "to proxy" },
+                { "mask": "0x0f00", "value": "0x0f00", "field": "0xffff0000", "label": "identity {}" },
+                { "mask": "0x30", "label": "either bit" }
+            ],
+            "zones": [ { "zone": 7, "label": "tenant-a" } ]
+        }"#).unwrap();
+        assert_eq!(d.decode_mark(0x0200), ["to proxy"]);
+        assert_eq!(d.decode_mark(0x0210), ["to proxy", "either bit"]);
+        assert_eq!(d.decode_mark(0x1234_0f00), ["identity 4660"]);
+        assert!(d.decode_mark(0x0300).is_empty());
+        assert!(d.decode_mark(0).is_empty());
+        assert_eq!(d.decode_zone(7).as_deref(), Some("tenant-a"));
+        assert_eq!(d.decode_zone(8), None);
+
+        let mut flows = vec![crate::daemon::conntrack::parse_conntrack_line(
+            "ipv4 2 tcp 6 100 ESTABLISHED src=10.0.0.1 dst=10.0.0.2 sport=1 dport=2 src=10.0.0.2 dst=10.0.0.1 sport=2 dport=1 [ASSURED] mark=512 zone=7 use=1",
+        )
+        .unwrap()];
+        d.decode_all(&mut flows);
+        assert_eq!(flows[0].mark_labels, ["to proxy"]);
+        assert_eq!(flows[0].zone_label.as_deref(), Some("tenant-a"));
+    }
+
+    #[test]
+    fn cilium_preset() {
+        let d = MarkDecoders::default().with_presets(Some("cilium".into())).unwrap();
+        let cases: &[(u32, &str)] = &[
+            (0x0200, "cilium: to proxy"),
+            (0x0300, "cilium: SNAT done"),
+            (0x0400, "cilium: overlay"),
+            (0x0500, "cilium: egress gateway done"),
+            (0x002a_0a00, "cilium: from ingress proxy, identity 42"),
+            (0x002a_0b00, "cilium: from egress proxy, identity 42"),
+            (0x0c00, "cilium: from host"),
+            (0x0d00, "cilium: decrypted"),
+            (0x0e00, "cilium: to encrypt"),
+            (0x3039_0f00, "cilium: identity 12345"),
+        ];
+        for (mark, label) in cases {
+            assert_eq!(d.decode_mark(*mark), [*label], "{:#x}", mark);
+        }
+        assert!(d.decode_mark(0x0100).is_empty());
+    }
+
+    #[test]
+    fn calico_preset() {
+        let d = MarkDecoders::default().with_presets(Some("calico".into())).unwrap();
+        assert_eq!(d.decode_mark(0x0001_0000), ["calico: policy accept"]);
+        assert_eq!(d.decode_mark(0x0002_0000), ["calico: policy pass"]);
+        assert_eq!(d.decode_mark(0x0011_0000), ["calico: policy accept", "calico: endpoint mark 1"]);
+        assert!(d.decode_mark(0x0000_ffff).is_empty());
+    }
+}
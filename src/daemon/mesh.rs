@@ -0,0 +1,86 @@
+use std::net::{IpAddr, Ipv4Addr};
+
+use serde::Serialize;
+
+use crate::daemon::conntrack::{Connection, Role};
+use crate::daemon::ipclass::IpClass;
+
+#[derive(Debug, Clone, Copy, Serialize, Hash, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Mesh {
+    Istio,
+    Linkerd,
+}
+
+/// Which hop of a sidecar-proxied connection a flow is.
+#[derive(Debug, Clone, Copy, Serialize, Hash, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MeshLeg {
+    /// The app's connection, redirected into the sidecar's outbound port.
+    /// Its original destination is the peer the app asked for.
+    Outbound,
+    /// A peer's connection to the pod, redirected into the sidecar's inbound
+    /// port.
+    Inbound,
+    /// The sidecar's own connection to the destination it picked.
+    Upstream,
+    /// A hop between the sidecar and the app over loopback.
+    Loopback,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Hash, PartialEq, Eq)]
+pub struct MeshInfo {
+    pub mesh: Mesh,
+    pub leg: MeshLeg,
+}
+
+/// Ports the sidecars' iptables rules redirect into.
+const REDIRECT_PORTS: &[(u16, Mesh, MeshLeg)] = &[
+    (15001, Mesh::Istio, MeshLeg::Outbound),
+    (15006, Mesh::Istio, MeshLeg::Inbound),
+    (4140, Mesh::Linkerd, MeshLeg::Outbound),
+    (4143, Mesh::Linkerd, MeshLeg::Inbound),
+];
+
+/// Process names of the proxies, as the kernel truncates them.
+const PROXIES: &[(&str, Mesh)] = &[("envoy", Mesh::Istio), ("linkerd2-proxy", Mesh::Linkerd)];
+
+/// Istio's sidecar connects to the app from this address on inbound
+/// traffic, so the app still sees a local peer.
+const ISTIO_INBOUND_PASSTHROUGH: IpAddr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 6));
+
+/// Sets `mesh` on flows that are legs of a sidecar-proxied connection.
+/// Expects `classify_all` and process attribution to have run.
+pub fn mark_legs(flows: &mut [Connection]) {
+    for c in flows.iter_mut() {
+        c.mesh = classify(c);
+    }
+}
+
+fn classify(c: &Connection) -> Option<MeshInfo> {
+    let redirect = |port: u16| REDIRECT_PORTS.iter().find(|(p, _, _)| *p == port);
+    // REDIRECT is a DNAT, so the reply comes from the proxy's port while
+    // the original direction keeps the real destination.
+    if let Some(r) = c.reply
+        && let Some(&(_, mesh, leg)) = redirect(r.src_port)
+    {
+        return Some(MeshInfo { mesh, leg });
+    }
+    if c.dst_class == IpClass::Loopback
+        && let Some(&(_, mesh, _)) = redirect(c.dst_port)
+    {
+        return Some(MeshInfo { mesh, leg: MeshLeg::Loopback });
+    }
+    if c.src_ip == ISTIO_INBOUND_PASSTHROUGH {
+        return Some(MeshInfo { mesh: Mesh::Istio, leg: MeshLeg::Loopback });
+    }
+    let comm = c.process.as_ref()?.comm.as_str();
+    let &(_, mesh) = PROXIES.iter().find(|(name, _)| *name == comm)?;
+    if c.src_class == IpClass::Loopback || c.dst_class == IpClass::Loopback {
+        Some(MeshInfo { mesh, leg: MeshLeg::Loopback })
+    } else if c.role == Some(Role::Client) {
+        Some(MeshInfo { mesh, leg: MeshLeg::Upstream })
+    } else {
+        None
+    }
+}
@@ -0,0 +1,227 @@
+use std::{sync::Arc, time::{Duration, SystemTime, UNIX_EPOCH}};
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+use tokio::{net::TcpListener, sync::RwLock, time::sleep};
+
+pub mod admin;
+pub mod aggregate;
+pub mod alerts;
+pub mod api;
+pub mod cidr;
+pub mod conntrack;
+pub mod expect;
+pub mod flowlog;
+pub mod flows;
+pub mod history;
+pub mod info;
+pub mod ipclass;
+pub mod ipfix;
+pub mod ipvs;
+pub mod marks;
+pub mod mesh;
+pub mod netlink;
+pub mod netns;
+pub mod otlp;
+pub mod process;
+pub mod sockets;
+pub mod stats;
+pub mod tcpinfo;
+
+use conntrack::{Connection, read_conntrack, resolve_conntrack_path};
+
+pub type SharedConnections = Arc<RwLock<Vec<Connection>>>;
+pub type SharedStats = Arc<RwLock<stats::ConntrackStats>>;
+pub type SharedExpectations = Arc<RwLock<Vec<expect::Expectation>>>;
+pub type SharedHistory = Arc<RwLock<history::History>>;
+pub type SharedAlerts = Arc<RwLock<Vec<alerts::AlertStatus>>>;
+pub type SharedListeners = Arc<RwLock<Vec<sockets::Listener>>>;
+
+#[derive(Clone)]
+pub struct AppState {
+    pub connections: SharedConnections,
+    pub stats: SharedStats,
+    pub expectations: SharedExpectations,
+    pub history: SharedHistory,
+    pub alerts: SharedAlerts,
+    pub listeners: SharedListeners,
+    /// Set when the admin API is enabled.
+    pub admin: Option<Arc<admin::AdminConfig>>,
+    /// Set when `tcp_info` is collected via sock_diag.
+    pub tcp_info: bool,
+    /// Set when connections are attributed to processes and containers.
+    pub process_attribution: bool,
+    /// Set when conntrack is read from every network namespace.
+    pub all_netns: bool,
+    /// Set when mark decoders are configured.
+    pub mark_decoding: bool,
+    pub node_name: Option<String>,
+    pub conntrack_requested: String,
+    pub conntrack_path: String,
+}
+
+/// Resolves a path under the host's procfs, preferring the `/host/proc`
+/// mount used by the DaemonSet and falling back to the container's own
+/// `/proc` (which is the host's when running with hostNetwork or locally).
+pub fn host_proc(rel: &str) -> Option<PathBuf> {
+    ["/host/proc", "/proc"].iter()
+        .map(|root| Path::new(root).join(rel))
+        .find(|p| p.exists())
+}
+
+pub async fn run_daemon() -> anyhow::Result<()> {
+    let conntrack_env = std::env::var("CONNTRACK_PATH").unwrap_or_else(|_| "auto".into());
+    let conntrack_path = resolve_conntrack_path(&conntrack_env);
+
+    if std::env::var("KFLOW_DEBUG").is_ok() {
+        eprintln!("kflow daemon starting; CONNTRACK_PATH={} -> {}", conntrack_env, if conntrack_path.is_empty() { "(no candidate found yet)".to_string() } else { conntrack_path.clone() });
+    }
+
+    if info::accounting_status() == info::Accounting::Disabled {
+        eprintln!("nf_conntrack_acct is 0 on this node; byte counters and throughput will read as zero until it is enabled");
+    }
+
+    let state: SharedConnections = Arc::new(RwLock::new(Vec::new()));
+    let node_name = std::env::var("KUBE_NODE_NAME").ok().or_else(|| {
+        hostname::get().ok().and_then(|h| h.into_string().ok())
+    });
+    
+    let stats: SharedStats = Arc::new(RwLock::new(Default::default()));
+    let state_clone = state.clone();
+    let stats_clone = stats.clone();
+    let expectations: SharedExpectations = Arc::new(RwLock::new(Vec::new()));
+    let expectations_clone = expectations.clone();
+    let history: SharedHistory = Arc::new(RwLock::new(history::History::new(history::HistoryConfig::from_env())));
+    let history_clone = history.clone();
+    let alerts: SharedAlerts = Arc::new(RwLock::new(Vec::new()));
+    let alerts_clone = alerts.clone();
+    let listeners: SharedListeners = Arc::new(RwLock::new(Vec::new()));
+    let listeners_clone = listeners.clone();
+    let path = conntrack_path.clone();
+    let tracker_node = node_name.clone();
+    let alert_node = node_name.clone();
+    let collect_tcp_info = tcpinfo::enabled();
+    let attribute_processes = process::enabled();
+    let all_netns = netns::enabled();
+    let own_netns = if all_netns { netns::own_netns() } else { None };
+    let networks = ipclass::ClusterNetworks::from_env();
+    let mark_decoders = marks::MarkDecoders::from_env();
+    let mark_decoding = mark_decoders.is_some();
+    tokio::spawn(async move {
+        let mut stats_sampler = stats::StatsSampler::default();
+        let sample_interval = 2u64;
+        let mut tracker = flows::FlowTracker::new(tracker_node, sample_interval);
+        let mut flow_log = flowlog::FlowLogConfig::from_env().map(flowlog::FlowLog::new);
+        let exporter = ipfix::IpfixConfig::from_env().map(|c| ipfix::spawn(c, unix_now()));
+        let mut alert_engine = alerts::AlertConfig::from_env().map(|c| alerts::AlertEngine::new(c, alert_node.clone()));
+        let mut owners = sockets::SocketOwners::default();
+        
+        loop {
+            let now = unix_now();
+            let mut flows = read_conntrack(&path);
+            let local_sockets = sockets::read_sockets();
+            let mut local_addrs = sockets::local_addresses();
+            local_addrs.extend(&networks.node_ips);
+            sockets::assign_roles(&mut flows, &local_sockets, &local_addrs);
+            // One lookup covers both listeners and flows, and only walks
+            // /proc for sockets not seen in earlier samples.
+            let mut wanted: HashSet<u64> = local_sockets.iter().filter(|s| s.is_listening()).map(|s| s.inode).collect();
+            let flow_inodes = if attribute_processes { process::socket_inodes(&flows, &local_sockets, &local_addrs) } else { Vec::new() };
+            wanted.extend(flow_inodes.iter().flatten());
+            owners.refresh(now, &local_sockets, &wanted);
+            if attribute_processes {
+                process::attribute(&mut flows, &flow_inodes, &owners);
+            }
+            *listeners_clone.write().await = sockets::listeners(&local_sockets, &owners);
+            // Local sockets only explain the daemon's own namespace, so the
+            // others are added after the socket-based enrichment.
+            if all_netns {
+                if let Some(own) = own_netns {
+                    for f in flows.iter_mut() {
+                        netns::tag(f, own);
+                    }
+                }
+                flows.extend(netns::read_other_namespaces(own_netns));
+            }
+            ipclass::classify_all(&mut flows, &networks);
+            if ipvs::available() {
+                ipvs::join_connections(&mut flows, &ipvs::read_connections());
+            }
+            mesh::mark_legs(&mut flows);
+            if let Some(decoders) = &mark_decoders {
+                decoders.decode_all(&mut flows);
+            }
+            if collect_tcp_info {
+                match tcpinfo::read_tcp_info() {
+                    Ok(info) => tcpinfo::join_tcp_info(&mut flows, &info),
+                    Err(e) => {
+                        if std::env::var("KFLOW_DEBUG").is_ok() {
+                            eprintln!("Failed to read tcp_info via sock_diag: {}", e);
+                        }
+                    }
+                }
+            }
+            let ended = tracker.update(now, &mut flows);
+
+            if let Some(log) = flow_log.as_mut()
+                && let Err(e) = log.write(now, &ended) {
+                eprintln!("failed to write flow log: {}", e);
+            }
+            if let Some(exp) = &exporter
+                && exp.try_send(ipfix::Batch { now, ended: ended.clone(), open: tracker.open_records() }).is_err() {
+                eprintln!("flow exporter is falling behind; dropped this sample's records");
+            }
+
+            let expects = expect::read_expectations(&path, &flows);
+            let sample = stats_sampler.sample();
+            if let Some(engine) = alert_engine.as_mut() {
+                engine.evaluate(now, &flows, &sample);
+                *alerts_clone.write().await = engine.statuses();
+            }
+            history_clone.write().await.push(now, flows.clone());
+            {
+                let mut w = state_clone.write().await;
+                *w = flows;
+            }
+            *expectations_clone.write().await = expects;
+            *stats_clone.write().await = sample;
+            sleep(Duration::from_secs(sample_interval)).await;
+        }
+    });
+
+    let app_state = AppState {
+        connections: state,
+        stats,
+        expectations,
+        history,
+        alerts,
+        listeners,
+        admin: admin::AdminConfig::from_env().map(Arc::new),
+        tcp_info: collect_tcp_info,
+        process_attribution: attribute_processes,
+        all_netns,
+        mark_decoding,
+        node_name,
+        conntrack_requested: conntrack_env,
+        conntrack_path,
+    };
+    if let Some(config) = otlp::OtlpConfig::from_env() {
+        tokio::spawn(otlp::run_exporter(config, app_state.clone()));
+    }
+    if app_state.admin.is_some() {
+        eprintln!("admin API enabled: conntrack entries can be deleted by holders of the admin token");
+    }
+    let app = api::router(app_state);
+
+    let addr: SocketAddr = "0.0.0.0:8080".parse().unwrap();
+    println!("Listening on {addr}");
+    let listener = TcpListener::bind(addr).await?;
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await?;
+
+    Ok(())
+}
+
+pub fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
@@ -0,0 +1,179 @@
+use std::io;
+use std::net::IpAddr;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+
+// A minimal netlink socket, plus just enough of ctnetlink
+// (linux/netfilter/nfnetlink_conntrack.h) to delete one entry by its original
+// tuple, the equivalent of `conntrack -D`.
+const NETLINK_NETFILTER: libc::c_int = 12;
+const NFNL_SUBSYS_CTNETLINK: u16 = 1;
+const IPCTNL_MSG_CT_DELETE: u16 = 2;
+pub const NLM_F_REQUEST: u16 = 0x1;
+const NLM_F_ACK: u16 = 0x4;
+pub const NLMSG_ERROR: u16 = 0x2;
+pub const NLMSG_DONE: u16 = 0x3;
+const NLA_F_NESTED: u16 = 0x8000;
+
+const CTA_TUPLE_ORIG: u16 = 1;
+const CTA_ZONE: u16 = 18;
+const CTA_TUPLE_IP: u16 = 1;
+const CTA_TUPLE_PROTO: u16 = 2;
+const CTA_IP_V4_SRC: u16 = 1;
+const CTA_IP_V4_DST: u16 = 2;
+const CTA_IP_V6_SRC: u16 = 3;
+const CTA_IP_V6_DST: u16 = 4;
+const CTA_PROTO_NUM: u16 = 1;
+const CTA_PROTO_SRC_PORT: u16 = 2;
+const CTA_PROTO_DST_PORT: u16 = 3;
+
+/// The original-direction tuple identifying a conntrack entry, and the
+/// conntrack zone it lives in.
+#[derive(Debug, Clone, Copy)]
+pub struct Tuple {
+    pub proto: u8,
+    pub src_ip: IpAddr,
+    pub src_port: u16,
+    pub dst_ip: IpAddr,
+    pub dst_port: u16,
+    /// The kernel only finds entries outside the default zone 0 when it is
+    /// given their zone.
+    pub zone: Option<u16>,
+}
+
+/// Deletes the conntrack entry with this original tuple. Returns `Ok(false)`
+/// when the kernel has no such entry (it may have expired meanwhile). Needs
+/// CAP_NET_ADMIN in the host network namespace.
+pub fn delete_conntrack(t: &Tuple) -> io::Result<bool> {
+    let family = match (t.src_ip, t.dst_ip) {
+        (IpAddr::V4(_), IpAddr::V4(_)) => libc::AF_INET as u8,
+        (IpAddr::V6(_), IpAddr::V6(_)) => libc::AF_INET6 as u8,
+        _ => return Err(io::Error::new(io::ErrorKind::InvalidInput, "mixed address families in tuple")),
+    };
+
+    let mut ip = Vec::new();
+    match (t.src_ip, t.dst_ip) {
+        (IpAddr::V4(s), IpAddr::V4(d)) => {
+            put_attr(&mut ip, CTA_IP_V4_SRC, &s.octets());
+            put_attr(&mut ip, CTA_IP_V4_DST, &d.octets());
+        }
+        (IpAddr::V6(s), IpAddr::V6(d)) => {
+            put_attr(&mut ip, CTA_IP_V6_SRC, &s.octets());
+            put_attr(&mut ip, CTA_IP_V6_DST, &d.octets());
+        }
+        _ => unreachable!(),
+    }
+    let mut proto = Vec::new();
+    put_attr(&mut proto, CTA_PROTO_NUM, &[t.proto]);
+    put_attr(&mut proto, CTA_PROTO_SRC_PORT, &t.src_port.to_be_bytes());
+    put_attr(&mut proto, CTA_PROTO_DST_PORT, &t.dst_port.to_be_bytes());
+    let mut tuple = Vec::new();
+    put_attr(&mut tuple, CTA_TUPLE_IP | NLA_F_NESTED, &ip);
+    put_attr(&mut tuple, CTA_TUPLE_PROTO | NLA_F_NESTED, &proto);
+
+    // nfgenmsg: family, NFNETLINK_V0, res_id (big endian).
+    let mut payload = vec![family, 0, 0, 0];
+    put_attr(&mut payload, CTA_TUPLE_ORIG | NLA_F_NESTED, &tuple);
+    if let Some(zone) = t.zone {
+        put_attr(&mut payload, CTA_ZONE, &zone.to_be_bytes());
+    }
+
+    let msg = message((NFNL_SUBSYS_CTNETLINK << 8) | IPCTNL_MSG_CT_DELETE, NLM_F_REQUEST | NLM_F_ACK, &payload);
+    let reply = roundtrip(&msg)?;
+    if reply.len() < 20 || u16::from_ne_bytes([reply[4], reply[5]]) != NLMSG_ERROR {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "unexpected netlink reply"));
+    }
+    match i32::from_ne_bytes([reply[16], reply[17], reply[18], reply[19]]) {
+        0 => Ok(true),
+        e if e == -libc::ENOENT => Ok(false),
+        e => Err(io::Error::from_raw_os_error(-e)),
+    }
+}
+
+/// Sends one request on a fresh NETLINK_NETFILTER socket and returns the
+/// first reply message.
+fn roundtrip(msg: &[u8]) -> io::Result<Vec<u8>> {
+    let sock = NetlinkSocket::open(NETLINK_NETFILTER)?;
+    sock.send(msg)?;
+    sock.recv()
+}
+
+/// A blocking netlink socket talking to the kernel, with a receive timeout
+/// so a silent kernel can't hang the caller.
+pub struct NetlinkSocket {
+    fd: OwnedFd,
+}
+
+impl NetlinkSocket {
+    pub fn open(protocol: libc::c_int) -> io::Result<Self> {
+        // SAFETY: plain socket syscalls; the descriptor is owned by `fd` from
+        // the moment it is created.
+        unsafe {
+            let raw = libc::socket(libc::AF_NETLINK, libc::SOCK_RAW | libc::SOCK_CLOEXEC, protocol);
+            if raw < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let fd = OwnedFd::from_raw_fd(raw);
+            let timeout = libc::timeval { tv_sec: 2, tv_usec: 0 };
+            libc::setsockopt(
+                fd.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_RCVTIMEO,
+                &timeout as *const _ as *const libc::c_void,
+                std::mem::size_of::<libc::timeval>() as libc::socklen_t,
+            );
+            Ok(NetlinkSocket { fd })
+        }
+    }
+
+    pub fn send(&self, msg: &[u8]) -> io::Result<()> {
+        // SAFETY: `msg` and `kernel` outlive the call.
+        unsafe {
+            let mut kernel: libc::sockaddr_nl = std::mem::zeroed();
+            kernel.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+            let sent = libc::sendto(
+                self.fd.as_raw_fd(),
+                msg.as_ptr() as *const libc::c_void,
+                msg.len(),
+                0,
+                &kernel as *const _ as *const libc::sockaddr,
+                std::mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+            );
+            if sent < 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+
+    /// One datagram from the kernel, which may hold several messages.
+    pub fn recv(&self) -> io::Result<Vec<u8>> {
+        let mut buf = vec![0u8; 32 * 1024];
+        // SAFETY: the kernel writes at most `buf.len()` bytes into `buf`.
+        let n = unsafe { libc::recv(self.fd.as_raw_fd(), buf.as_mut_ptr() as *mut libc::c_void, buf.len(), 0) };
+        if n < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        buf.truncate(n as usize);
+        Ok(buf)
+    }
+}
+
+/// Builds a netlink message: header, then `payload`.
+pub fn message(ty: u16, flags: u16, payload: &[u8]) -> Vec<u8> {
+    let mut msg = Vec::with_capacity(16 + payload.len());
+    msg.extend_from_slice(&((16 + payload.len()) as u32).to_ne_bytes());
+    msg.extend_from_slice(&ty.to_ne_bytes());
+    msg.extend_from_slice(&flags.to_ne_bytes());
+    msg.extend_from_slice(&1u32.to_ne_bytes());
+    msg.extend_from_slice(&0u32.to_ne_bytes());
+    msg.extend_from_slice(payload);
+    msg
+}
+
+fn put_attr(buf: &mut Vec<u8>, ty: u16, payload: &[u8]) {
+    let len = 4 + payload.len();
+    buf.extend_from_slice(&(len as u16).to_ne_bytes());
+    buf.extend_from_slice(&ty.to_ne_bytes());
+    buf.extend_from_slice(payload);
+    buf.resize(buf.len() + (4 - len % 4) % 4, 0);
+}
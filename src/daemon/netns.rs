@@ -0,0 +1,81 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use crate::daemon::conntrack::{read_conntrack, Connection};
+use crate::daemon::host_proc;
+
+/// Whether to read conntrack from every network namespace on the node, set
+/// by `KFLOW_ALL_NETNS=1`. Needed on kind, k3d and CNIs whose flows never
+/// reach the daemon's own table.
+pub fn enabled() -> bool {
+    matches!(std::env::var("KFLOW_ALL_NETNS").as_deref(), Ok("1") | Ok("true"))
+}
+
+/// Inode of the daemon's own network namespace: the one the configured
+/// conntrack path shows and the one netlink requests act on.
+pub fn own_netns() -> Option<u64> {
+    netns_inode(Path::new("/proc/self/ns/net"))
+}
+
+/// A network namespace and one process living in it.
+#[derive(Debug, Clone, Copy)]
+pub struct Namespace {
+    pub inode: u64,
+    pub pid: u32,
+}
+
+/// Every network namespace with at least one process, found through
+/// `/host/proc/<pid>/ns/net` and deduplicated by inode, lowest PID first.
+pub fn list_namespaces() -> Vec<Namespace> {
+    let Some(proc_root) = host_proc("self").and_then(|p| p.parent().map(Path::to_path_buf)) else { return Vec::new() };
+    let Ok(entries) = std::fs::read_dir(&proc_root) else { return Vec::new() };
+    let mut pids: Vec<u32> = entries
+        .filter_map(|e| e.ok())
+        .filter_map(|e| e.file_name().to_str().and_then(|n| n.parse().ok()))
+        .collect();
+    pids.sort_unstable();
+
+    let mut seen = HashSet::new();
+    let mut out = Vec::new();
+    for pid in pids {
+        let Some(inode) = netns_inode(&proc_root.join(pid.to_string()).join("ns/net")) else { continue };
+        if seen.insert(inode) {
+            out.push(Namespace { inode, pid });
+        }
+    }
+    out
+}
+
+/// Reads the conntrack table of every namespace except `own`, which the
+/// caller has already read from the configured path, tagging each
+/// connection with its namespace.
+pub fn read_other_namespaces(own: Option<u64>) -> Vec<Connection> {
+    let mut out = Vec::new();
+    for ns in list_namespaces().into_iter().filter(|ns| Some(ns.inode) != own) {
+        let Some(path) = conntrack_path(ns.pid) else { continue };
+        for mut c in read_conntrack(&path.to_string_lossy()) {
+            tag(&mut c, ns.inode);
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Records the namespace a connection was read from and appends its inode
+/// to the ID, since the same 5-tuple can appear in several namespaces. Every
+/// namespace is tagged, the daemon's own included, so an ID names exactly
+/// one connection.
+pub fn tag(c: &mut Connection, inode: u64) {
+    c.id = format!("{}-netns{}", c.id, inode);
+    c.netns = Some(inode);
+}
+
+fn conntrack_path(pid: u32) -> Option<PathBuf> {
+    ["net/nf_conntrack", "net/ip_conntrack"].iter().find_map(|rel| host_proc(&format!("{}/{}", pid, rel)))
+}
+
+/// Parses the `net:[4026531992]` target of a namespace link.
+fn netns_inode(link: &Path) -> Option<u64> {
+    let target = std::fs::read_link(link).ok()?;
+    target.to_str()?.strip_prefix("net:[")?.strip_suffix(']')?.parse().ok()
+}
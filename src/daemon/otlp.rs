@@ -0,0 +1,161 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use serde_json::{Value, json};
+
+use crate::daemon::{AppState, unix_now};
+use crate::daemon::conntrack::{Connection, Role};
+use crate::daemon::ipclass::IpClass;
+
+/// Pushes per-node connection aggregates to an OpenTelemetry collector as
+/// OTLP/HTTP JSON, enabled by setting `KFLOW_OTLP_ENDPOINT` (or the standard
+/// `OTEL_EXPORTER_OTLP_ENDPOINT`) to the collector's base URL:
+///
+/// - `KFLOW_OTLP_INTERVAL_SECS`: push interval (default 30s)
+/// - `KFLOW_OTLP_HEADERS`: extra request headers as `key=value,key=value`
+/// - `KFLOW_CLUSTER_NAME`: sent as the `k8s.cluster.name` resource attribute
+#[derive(Debug, Clone)]
+pub struct OtlpConfig {
+    pub url: String,
+    pub interval_secs: u64,
+    pub headers: Vec<(String, String)>,
+    pub cluster_name: Option<String>,
+}
+
+impl OtlpConfig {
+    pub fn from_env() -> Option<Self> {
+        let endpoint = std::env::var("KFLOW_OTLP_ENDPOINT")
+            .or_else(|_| std::env::var("OTEL_EXPORTER_OTLP_ENDPOINT"))
+            .ok()
+            .filter(|e| !e.is_empty())?;
+        let endpoint = endpoint.trim_end_matches('/');
+        let url = if endpoint.ends_with("/v1/metrics") { endpoint.to_string() } else { format!("{}/v1/metrics", endpoint) };
+        let headers = std::env::var("KFLOW_OTLP_HEADERS")
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|kv| kv.split_once('='))
+            .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+            .collect();
+        Some(OtlpConfig {
+            url,
+            interval_secs: std::env::var("KFLOW_OTLP_INTERVAL_SECS").ok().and_then(|v| v.parse().ok()).unwrap_or(30).max(1),
+            headers,
+            cluster_name: std::env::var("KFLOW_CLUSTER_NAME").ok().filter(|c| !c.is_empty()),
+        })
+    }
+}
+
+/// Ports beyond this many (ranked by throughput) are folded into `other` to
+/// keep metric cardinality bounded.
+const MAX_PORTS: usize = 20;
+
+/// Pushes metrics every `interval_secs` until the daemon exits.
+pub async fn run_exporter(config: OtlpConfig, state: AppState) {
+    let client = reqwest::Client::new();
+    loop {
+        tokio::time::sleep(Duration::from_secs(config.interval_secs)).await;
+        let body = {
+            let connections = state.connections.read().await;
+            metrics_payload(&config, state.node_name.as_deref(), &connections, unix_now())
+        };
+        let mut req = client.post(&config.url).json(&body);
+        for (k, v) in &config.headers {
+            req = req.header(k, v);
+        }
+        match req.send().await {
+            Ok(resp) if !resp.status().is_success() => {
+                eprintln!("OTLP export to {} failed: HTTP {}", config.url, resp.status());
+            }
+            Ok(_) => {
+                if std::env::var("KFLOW_DEBUG").is_ok() {
+                    eprintln!("pushed OTLP metrics to {}", config.url);
+                }
+            }
+            Err(e) => eprintln!("OTLP export to {} failed: {}", config.url, e),
+        }
+    }
+}
+
+/// The class of the connection's remote end: its source when this node
+/// accepted it, its destination otherwise.
+fn remote_class(c: &Connection) -> IpClass {
+    match c.role {
+        Some(Role::Server) => c.src_class,
+        _ => c.dst_class,
+    }
+}
+
+/// Builds an OTLP `ExportMetricsServiceRequest` in its JSON encoding.
+fn metrics_payload(config: &OtlpConfig, node_name: Option<&str>, connections: &[Connection], now: u64) -> Value {
+    let time = (now as u128 * 1_000_000_000).to_string();
+
+    let mut by_state: BTreeMap<(&str, &str), u64> = BTreeMap::new();
+    let mut by_port: BTreeMap<u16, u64> = BTreeMap::new();
+    let mut by_class: BTreeMap<&str, u64> = BTreeMap::new();
+    for c in connections {
+        *by_state.entry((c.proto.as_str(), c.state.as_str())).or_default() += 1;
+        *by_port.entry(c.dst_port).or_default() += c.throughput_bytes_per_sec;
+        *by_class.entry(remote_class(c).as_str()).or_default() += c.throughput_bytes_per_sec;
+    }
+
+    let mut ports: Vec<(u16, u64)> = by_port.into_iter().collect();
+    ports.sort_by_key(|(_, bps)| std::cmp::Reverse(*bps));
+    let other: u64 = ports.iter().skip(MAX_PORTS).map(|(_, bps)| bps).sum();
+    let mut port_points: Vec<Value> = ports
+        .iter()
+        .take(MAX_PORTS)
+        .map(|(port, bps)| point(&time, &[("port", port.to_string())], *bps))
+        .collect();
+    if ports.len() > MAX_PORTS {
+        port_points.push(point(&time, &[("port", "other".to_string())], other));
+    }
+
+    let state_points: Vec<Value> = by_state
+        .iter()
+        .map(|((proto, state), n)| point(&time, &[("protocol", proto.to_string()), ("state", state.to_string())], *n))
+        .collect();
+    let class_points: Vec<Value> = by_class
+        .iter()
+        .map(|(class, bps)| point(&time, &[("peer_class", class.to_string())], *bps))
+        .collect();
+
+    let mut resource = vec![
+        attribute("service.name", "kflow"),
+        attribute("service.version", env!("CARGO_PKG_VERSION")),
+    ];
+    if let Some(node) = node_name {
+        resource.push(attribute("k8s.node.name", node));
+        resource.push(attribute("host.name", node));
+    }
+    if let Some(cluster) = &config.cluster_name {
+        resource.push(attribute("k8s.cluster.name", cluster));
+    }
+
+    json!({
+        "resourceMetrics": [{
+            "resource": { "attributes": resource },
+            "scopeMetrics": [{
+                "scope": { "name": "kflow", "version": env!("CARGO_PKG_VERSION") },
+                "metrics": [
+                    gauge("kflow.connections", "Tracked connections by protocol and state", "{connection}", state_points),
+                    gauge("kflow.throughput.by_port", "Throughput by destination port", "By/s", port_points),
+                    gauge("kflow.throughput.by_peer_class", "Throughput by remote address class", "By/s", class_points),
+                ],
+            }],
+        }],
+    })
+}
+
+fn gauge(name: &str, description: &str, unit: &str, points: Vec<Value>) -> Value {
+    json!({ "name": name, "description": description, "unit": unit, "gauge": { "dataPoints": points } })
+}
+
+fn point(time: &str, attrs: &[(&str, String)], value: u64) -> Value {
+    let attributes: Vec<Value> = attrs.iter().map(|(k, v)| attribute(k, v)).collect();
+    // OTLP JSON encodes 64-bit integers as strings.
+    json!({ "attributes": attributes, "timeUnixNano": time, "asInt": value.to_string() })
+}
+
+fn attribute(key: &str, value: &str) -> Value {
+    json!({ "key": key, "value": { "stringValue": value } })
+}
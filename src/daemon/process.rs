@@ -0,0 +1,222 @@
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+
+use crate::daemon::conntrack::Connection;
+use crate::daemon::host_proc;
+use crate::daemon::sockets::{Bound, Socket, SocketOwners};
+
+/// Whether to attribute connections to processes; on unless
+/// `KFLOW_PROCESS_ATTRIBUTION=0`. Owners are cached per socket, but every
+/// new socket still costs a walk of `/proc/<pid>/fd`, which can be worth
+/// turning off on nodes with heavy connection churn and many open files.
+pub fn enabled() -> bool {
+    !matches!(std::env::var("KFLOW_PROCESS_ATTRIBUTION").as_deref(), Ok("0") | Ok("false"))
+}
+
+/// Where a process runs, derived from its cgroup path.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Container {
+    container_id: Option<String>,
+    pod_uid: Option<String>,
+}
+
+/// The local socket behind each flow, found by the exact 5-tuple from either
+/// end, falling back to a socket listening on the flow's destination. An
+/// unconnected UDP socket may also have sent the flow from its source end.
+/// Wildcard sockets only match this node's own addresses, `local_addrs`.
+pub fn socket_inodes(flows: &[Connection], sockets: &[Socket], local_addrs: &HashSet<IpAddr>) -> Vec<Option<u64>> {
+    let mut connected: HashMap<(&str, IpAddr, u16, IpAddr, u16), u64> = HashMap::new();
+    let mut bound = Bound::new(local_addrs);
+    for s in sockets.iter().filter(|s| s.inode != 0) {
+        if s.is_listening() {
+            bound.insert(s, s.inode);
+        } else {
+            connected.insert((s.proto, s.local_ip, s.local_port, s.remote_ip, s.remote_port), s.inode);
+        }
+    }
+
+    flows
+        .iter()
+        .map(|c| {
+            let proto = c.proto.as_str();
+            connected
+                .get(&(proto, c.src_ip, c.src_port, c.dst_ip, c.dst_port))
+                .or_else(|| connected.get(&(proto, c.dst_ip, c.dst_port, c.src_ip, c.src_port)))
+                .copied()
+                .or_else(|| bound.accepting(proto, c.dst_ip, c.dst_port))
+                .or_else(|| if proto == "udp" { bound.accepting(proto, c.src_ip, c.src_port) } else { None })
+        })
+        .collect()
+}
+
+/// Sets `process`, `container_id` and `pod_uid` on connections backed by a
+/// local socket, given each flow's socket from [`socket_inodes`] and owners
+/// already refreshed for them.
+pub fn attribute(flows: &mut [Connection], inodes: &[Option<u64>], owners: &SocketOwners) {
+    let mut containers: HashMap<u32, Container> = HashMap::new();
+    for (c, inode) in flows.iter_mut().zip(inodes) {
+        let Some(process) = inode.and_then(|i| owners.get(i)) else {
+            c.process = None;
+            c.container_id = None;
+            c.pod_uid = None;
+            continue;
+        };
+        let container = containers.entry(process.pid).or_insert_with(|| read_container(process.pid));
+        c.container_id = container.container_id.clone();
+        c.pod_uid = container.pod_uid.clone();
+        c.process = Some(process.clone());
+    }
+}
+
+fn read_container(pid: u32) -> Container {
+    host_proc(&format!("{}/cgroup", pid))
+        .and_then(|p| std::fs::read_to_string(p).ok())
+        .map(|content| parse_cgroup(&content))
+        .unwrap_or_default()
+}
+
+/// Finds the container ID and pod UID in `/proc/<pid>/cgroup`, for both the
+/// systemd and cgroupfs drivers, e.g.
+/// `0::/kubepods.slice/kubepods-burstable.slice/kubepods-burstable-pod1b2c_..._9f.slice/cri-containerd-<id>.scope`
+/// (containerd), `.../crio-<id>.scope` (CRI-O) or
+/// `/kubepods/besteffort/pod1b2c-...-9f/<id>` (cgroupfs). Host processes
+/// have neither.
+fn parse_cgroup(content: &str) -> Container {
+    let mut out = Container::default();
+    for line in content.lines() {
+        // hierarchy-ID:controllers:path; v2 has a single `0::` line.
+        let Some(path) = line.splitn(3, ':').nth(2) else { continue };
+        for segment in path.split('/') {
+            if out.pod_uid.is_none() {
+                out.pod_uid = pod_uid(segment);
+            }
+            if out.container_id.is_none() {
+                out.container_id = container_id(segment);
+            }
+        }
+        if out.container_id.is_some() && out.pod_uid.is_some() {
+            break;
+        }
+    }
+    out
+}
+
+fn pod_uid(segment: &str) -> Option<String> {
+    let segment = segment.strip_suffix(".slice").unwrap_or(segment);
+    let uid = match segment.rfind("-pod") {
+        Some(i) => &segment[i + 4..],
+        None => segment.strip_prefix("pod")?,
+    };
+    // The systemd driver escapes the UID's dashes as underscores.
+    let uid = uid.replace('_', "-");
+    let well_formed = uid.len() == 36 && uid.chars().all(|ch| ch == '-' || ch.is_ascii_hexdigit());
+    well_formed.then_some(uid)
+}
+
+fn container_id(segment: &str) -> Option<String> {
+    let segment = segment.strip_suffix(".scope").unwrap_or(segment);
+    let id = ["cri-containerd-", "crio-", "docker-", "containerd-"]
+        .iter()
+        .find_map(|prefix| segment.strip_prefix(prefix))
+        .unwrap_or(segment);
+    (id.len() == 64 && id.chars().all(|ch| ch.is_ascii_hexdigit())).then(|| id.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ID: &str = "3f4e5d6c7b8a99887766554433221100ffeeddccbbaa00112233445566778899";
+    const UID: &str = "1b2c3d4e-5f60-4718-9a0b-1c2d3e4f5a6b";
+
+    fn expected() -> Container {
+        Container { container_id: Some(ID.to_string()), pod_uid: Some(UID.to_string()) }
+    }
+
+    #[test]
+    fn cgroup_v1_cgroupfs() {
+        let content = format!(
+            "12:pids:/kubepods/besteffort/pod{uid}/{id}\n11:memory:/kubepods/besteffort/pod{uid}/{id}\n1:name=systemd:/kubepods/besteffort/pod{uid}/{id}\n",
+            uid = UID, id = ID
+        );
+        assert_eq!(parse_cgroup(&content), expected());
+    }
+
+    #[test]
+    fn cgroup_v1_systemd_docker() {
+        let content = format!(
+            "4:cpu,cpuacct:/kubepods.slice/kubepods-pod{uid}.slice/docker-{id}.scope\n",
+            uid = UID.replace('-', "_"), id = ID
+        );
+        assert_eq!(parse_cgroup(&content), expected());
+    }
+
+    #[test]
+    fn cgroup_v2_systemd_slices() {
+        let containerd = format!(
+            "0::/kubepods.slice/kubepods-burstable.slice/kubepods-burstable-pod{uid}.slice/cri-containerd-{id}.scope\n",
+            uid = UID.replace('-', "_"), id = ID
+        );
+        assert_eq!(parse_cgroup(&containerd), expected());
+        let crio = format!(
+            "0::/kubepods.slice/kubepods-besteffort.slice/kubepods-besteffort-pod{uid}.slice/crio-{id}.scope\n",
+            uid = UID.replace('-', "_"), id = ID
+        );
+        assert_eq!(parse_cgroup(&crio), expected());
+    }
+
+    #[test]
+    fn cgroup_v2_cgroupfs() {
+        let content = format!("0::/kubepods/burstable/pod{uid}/{id}\n", uid = UID, id = ID);
+        assert_eq!(parse_cgroup(&content), expected());
+    }
+
+    #[test]
+    fn host_processes_have_no_container() {
+        assert_eq!(parse_cgroup("0::/system.slice/containerd.service\n"), Container::default());
+        assert_eq!(parse_cgroup("0::/user.slice/user-1000.slice/session-3.scope\n"), Container::default());
+    }
+
+    fn socket(proto: &'static str, local: &str, remote: &str, state: u8, inode: u64) -> Socket {
+        let local: std::net::SocketAddr = local.parse().unwrap();
+        let remote: std::net::SocketAddr = remote.parse().unwrap();
+        Socket { proto, local_ip: local.ip(), local_port: local.port(), remote_ip: remote.ip(), remote_port: remote.port(), state, uid: 0, inode }
+    }
+
+    fn flow(proto: &str, src: &str, dst: &str) -> Connection {
+        let src: std::net::SocketAddr = src.parse().unwrap();
+        let dst: std::net::SocketAddr = dst.parse().unwrap();
+        let line = match proto {
+            "tcp" => format!(
+                "ipv4 2 tcp 6 100 ESTABLISHED src={} dst={} sport={} dport={} src={} dst={} sport={} dport={} [ASSURED] mark=0 use=1",
+                src.ip(), dst.ip(), src.port(), dst.port(), dst.ip(), src.ip(), dst.port(), src.port()
+            ),
+            _ => format!(
+                "ipv4 2 udp 17 30 src={} dst={} sport={} dport={} src={} dst={} sport={} dport={} mark=0 use=1",
+                src.ip(), dst.ip(), src.port(), dst.port(), dst.ip(), src.ip(), dst.port(), src.port()
+            ),
+        };
+        crate::daemon::conntrack::parse_conntrack_line(&line).unwrap()
+    }
+
+    #[test]
+    fn forwarded_flows_are_not_pinned_on_wildcard_listeners() {
+        let sockets = vec![
+            socket("tcp", "0.0.0.0:9100", "0.0.0.0:0", 0x0a, 11),
+            socket("tcp", "192.168.1.1:40000", "10.1.0.9:443", 0x01, 12),
+            socket("udp", "0.0.0.0:53", "0.0.0.0:0", 0x07, 13),
+        ];
+        let local = HashSet::from(["192.168.1.1".parse().unwrap()]);
+        let flows = vec![
+            flow("tcp", "10.1.0.7:50000", "10.1.0.5:9100"),
+            flow("tcp", "10.0.0.2:50000", "192.168.1.1:9100"),
+            // The reply of a forwarded flow from a port a host process listens on.
+            flow("tcp", "192.168.1.1:9100", "10.1.0.5:50000"),
+            flow("tcp", "192.168.1.1:40000", "10.1.0.9:443"),
+            // A query sent from the unconnected UDP socket.
+            flow("udp", "192.168.1.1:53", "10.1.0.5:5353"),
+            flow("udp", "10.1.0.7:53", "10.1.0.5:5353"),
+        ];
+        assert_eq!(socket_inodes(&flows, &sockets, &local), [None, Some(11), None, Some(12), Some(13), None]);
+    }
+}
@@ -0,0 +1,416 @@
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::daemon::conntrack::{Connection, Role};
+use crate::daemon::host_proc;
+
+/// One row of `/proc/net/{tcp,tcp6,udp,udp6}`.
+#[derive(Debug, Clone)]
+pub struct Socket {
+    pub proto: &'static str,
+    pub local_ip: IpAddr,
+    pub local_port: u16,
+    pub remote_ip: IpAddr,
+    pub remote_port: u16,
+    /// Kernel TCP state number (`0A` is LISTEN; UDP sockets report `07`).
+    pub state: u8,
+    pub uid: u32,
+    pub inode: u64,
+}
+
+impl Socket {
+    /// Listening TCP sockets, and UDP sockets not connected to a peer.
+    pub fn is_listening(&self) -> bool {
+        match self.proto {
+            "tcp" => self.state == TCP_LISTEN,
+            _ => self.remote_port == 0 && self.remote_ip.is_unspecified(),
+        }
+    }
+}
+
+const TCP_LISTEN: u8 = 0x0a;
+
+const TABLES: &[(&str, &str)] = &[("net/tcp", "tcp"), ("net/tcp6", "tcp"), ("net/udp", "udp"), ("net/udp6", "udp")];
+
+/// A listening socket on this node, served by `/v1/listeners`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Listener {
+    pub proto: String,
+    pub address: IpAddr,
+    pub port: u16,
+    pub inode: u64,
+    pub uid: u32,
+    pub process: Option<ProcessRef>,
+}
+
+#[derive(Debug, Clone, Serialize, Hash, PartialEq, Eq)]
+pub struct ProcessRef {
+    pub pid: u32,
+    pub comm: String,
+    /// Arguments joined with spaces; empty for kernel threads.
+    pub cmdline: String,
+}
+
+/// Reads all four socket tables from the host's procfs.
+pub fn read_sockets() -> Vec<Socket> {
+    let mut out = Vec::new();
+    for (rel, proto) in TABLES {
+        let Some(path) = host_proc(rel) else { continue };
+        match std::fs::read_to_string(&path) {
+            Ok(content) => out.extend(content.lines().skip(1).filter_map(|l| parse_socket_line(l, proto))),
+            Err(e) => {
+                if std::env::var("KFLOW_DEBUG").is_ok() {
+                    eprintln!("Failed to read socket table {}: {}", path.display(), e);
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Parses e.g.
+/// `0: 00000000:0016 00000000:0000 0A 00000000:00000000 00:00000000 00000000 0 0 12345 1 ...`.
+fn parse_socket_line(line: &str, proto: &'static str) -> Option<Socket> {
+    let parts: Vec<&str> = line.split_whitespace().collect();
+    if parts.len() < 10 {
+        return None;
+    }
+    let (local_ip, local_port) = parse_endpoint(parts[1])?;
+    let (remote_ip, remote_port) = parse_endpoint(parts[2])?;
+    Some(Socket {
+        proto,
+        local_ip,
+        local_port,
+        remote_ip,
+        remote_port,
+        state: u8::from_str_radix(parts[3], 16).ok()?,
+        uid: parts[7].parse().ok()?,
+        inode: parts[9].parse().ok()?,
+    })
+}
+
+/// Decodes `ADDR:PORT`. The kernel prints addresses as 32-bit words in host
+/// byte order and the port as a plain hex number.
+fn parse_endpoint(s: &str) -> Option<(IpAddr, u16)> {
+    let (addr, port) = s.split_once(':')?;
+    let port = u16::from_str_radix(port, 16).ok()?;
+    let mut words = Vec::with_capacity(4);
+    for chunk in addr.as_bytes().chunks(8) {
+        let word = u32::from_str_radix(std::str::from_utf8(chunk).ok()?, 16).ok()?;
+        words.extend_from_slice(&word.to_ne_bytes());
+    }
+    let ip = match words.len() {
+        4 => IpAddr::V4(Ipv4Addr::new(words[0], words[1], words[2], words[3])),
+        16 => {
+            let octets: [u8; 16] = words.try_into().ok()?;
+            let v6 = Ipv6Addr::from(octets);
+            // Dual-stack sockets show IPv4 peers as ::ffff:a.b.c.d.
+            v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(IpAddr::V6(v6))
+        }
+        _ => return None,
+    };
+    Some((ip, port))
+}
+
+/// Addresses configured on the daemon's network interfaces, loopback
+/// included. A wildcard listener only accepts flows addressed to one of
+/// these.
+pub fn local_addresses() -> HashSet<IpAddr> {
+    let mut out = HashSet::new();
+    let mut ifaddrs: *mut libc::ifaddrs = std::ptr::null_mut();
+    // SAFETY: getifaddrs fills in a list we only read and then free; every
+    // `ifa_addr` is checked for null and read as the family it declares.
+    unsafe {
+        if libc::getifaddrs(&mut ifaddrs) != 0 {
+            return out;
+        }
+        let mut cur = ifaddrs;
+        while !cur.is_null() {
+            let addr = (*cur).ifa_addr;
+            if !addr.is_null() {
+                match (*addr).sa_family as libc::c_int {
+                    libc::AF_INET => {
+                        let sin = &*(addr as *const libc::sockaddr_in);
+                        out.insert(IpAddr::V4(Ipv4Addr::from(u32::from_be(sin.sin_addr.s_addr))));
+                    }
+                    libc::AF_INET6 => {
+                        let sin6 = &*(addr as *const libc::sockaddr_in6);
+                        out.insert(IpAddr::V6(Ipv6Addr::from(sin6.sin6_addr.s6_addr)));
+                    }
+                    _ => {}
+                }
+            }
+            cur = (*cur).ifa_next;
+        }
+        libc::freeifaddrs(ifaddrs);
+    }
+    out
+}
+
+/// Listening sockets keyed by protocol, address and port, and the lookup of
+/// the one that would accept a flow to `ip:port`. Wildcard listeners only
+/// count for this node's own addresses, since forwarded pod traffic to the
+/// same port never reaches them.
+pub(crate) struct Bound<'a, T> {
+    listening: HashMap<(&'a str, IpAddr, u16), T>,
+    local_addrs: &'a HashSet<IpAddr>,
+}
+
+impl<'a, T: Copy> Bound<'a, T> {
+    pub(crate) fn new(local_addrs: &'a HashSet<IpAddr>) -> Self {
+        Bound { listening: HashMap::new(), local_addrs }
+    }
+
+    pub(crate) fn insert(&mut self, s: &'a Socket, value: T) {
+        self.listening.insert((s.proto, s.local_ip, s.local_port), value);
+    }
+
+    pub(crate) fn accepting(&self, proto: &str, ip: IpAddr, port: u16) -> Option<T> {
+        if let Some(v) = self.listening.get(&(proto, ip, port)) {
+            return Some(*v);
+        }
+        if !self.local_addrs.contains(&ip) {
+            return None;
+        }
+        let any = if ip.is_ipv4() { IpAddr::V4(Ipv4Addr::UNSPECIFIED) } else { IpAddr::V6(Ipv6Addr::UNSPECIFIED) };
+        // IPv6 wildcard sockets also take IPv4 unless bound v6-only.
+        [any, IpAddr::V6(Ipv6Addr::UNSPECIFIED)].iter().find_map(|a| self.listening.get(&(proto, *a, port)).copied())
+    }
+}
+
+/// Marks each connection as `server` when a local socket listens on its
+/// destination (a wildcard listener only for this node's own addresses,
+/// `local_addrs`), or `client` when a local socket owns its source end.
+/// Flows that only pass through the node (e.g. forwarded pod traffic in
+/// another network namespace) are left unset.
+pub fn assign_roles(flows: &mut [Connection], sockets: &[Socket], local_addrs: &HashSet<IpAddr>) {
+    let mut listening = Bound::new(local_addrs);
+    let mut local: HashSet<(&str, IpAddr, u16)> = HashSet::new();
+    for s in sockets {
+        if s.is_listening() {
+            listening.insert(s, ());
+        } else {
+            local.insert((s.proto, s.local_ip, s.local_port));
+        }
+    }
+
+    for c in flows.iter_mut() {
+        let proto = c.proto.as_str();
+        c.role = if listening.accepting(proto, c.dst_ip, c.dst_port).is_some() {
+            Some(Role::Server)
+        } else if local.contains(&(proto, c.src_ip, c.src_port)) {
+            Some(Role::Client)
+        } else {
+            None
+        };
+    }
+}
+
+/// Listening sockets among `sockets`, with the owning process where
+/// `owners` knows it.
+pub fn listeners(sockets: &[Socket], owners: &SocketOwners) -> Vec<Listener> {
+    let mut out: Vec<Listener> = sockets
+        .iter()
+        .filter(|s| s.is_listening())
+        .map(|s| Listener {
+            proto: s.proto.to_string(),
+            address: s.local_ip,
+            port: s.local_port,
+            inode: s.inode,
+            uid: s.uid,
+            process: owners.get(s.inode).cloned(),
+        })
+        .collect();
+    out.sort_by(|a, b| (a.port, &a.proto, a.address).cmp(&(b.port, &b.proto, b.address)));
+    out
+}
+
+/// How long an inode no process was found for is remembered before
+/// `/proc` is walked for it again.
+const MISSING_RETRY_SECS: u64 = 60;
+
+/// Socket owners remembered across samples, so `/proc/<pid>/fd` is only
+/// walked when a socket shows up that hasn't been looked up yet. A socket
+/// inode keeps its owner for as long as the socket exists, so entries are
+/// dropped only once the socket leaves the tables.
+#[derive(Debug, Default)]
+pub struct SocketOwners {
+    known: HashMap<u64, Lookup>,
+}
+
+#[derive(Debug)]
+struct Lookup {
+    owner: Option<ProcessRef>,
+    checked_at: u64,
+}
+
+impl SocketOwners {
+    /// Forgets sockets no longer in `sockets`, then looks up the owners of
+    /// those `wanted` inodes not already known.
+    pub fn refresh(&mut self, now: u64, sockets: &[Socket], wanted: &HashSet<u64>) {
+        let live: HashSet<u64> = sockets.iter().map(|s| s.inode).collect();
+        self.known.retain(|inode, _| live.contains(inode));
+        let unseen: HashSet<u64> = wanted
+            .iter()
+            .copied()
+            .filter(|i| *i != 0)
+            .filter(|i| match self.known.get(i) {
+                Some(Lookup { owner: Some(_), .. }) => false,
+                Some(Lookup { owner: None, checked_at }) => now.saturating_sub(*checked_at) >= MISSING_RETRY_SECS,
+                None => true,
+            })
+            .collect();
+        if unseen.is_empty() {
+            return;
+        }
+        let mut found = socket_owners(&unseen);
+        for inode in unseen {
+            self.known.insert(inode, Lookup { owner: found.remove(&inode), checked_at: now });
+        }
+    }
+
+    pub fn get(&self, inode: u64) -> Option<&ProcessRef> {
+        self.known.get(&inode).and_then(|l| l.owner.as_ref())
+    }
+}
+
+/// Finds which process holds each socket inode by walking
+/// `/proc/<pid>/fd`. Processes we may not inspect are skipped.
+fn socket_owners(inodes: &HashSet<u64>) -> HashMap<u64, ProcessRef> {
+    let mut owners = HashMap::new();
+    if inodes.is_empty() {
+        return owners;
+    }
+    let Some(proc_root) = host_proc("self").and_then(|p| p.parent().map(Path::to_path_buf)) else { return owners };
+    let Ok(entries) = std::fs::read_dir(&proc_root) else { return owners };
+
+    for entry in entries.filter_map(|e| e.ok()) {
+        let Some(pid) = entry.file_name().to_str().and_then(|n| n.parse::<u32>().ok()) else { continue };
+        let Ok(fds) = std::fs::read_dir(entry.path().join("fd")) else { continue };
+        for fd in fds.filter_map(|e| e.ok()) {
+            let Ok(target) = std::fs::read_link(fd.path()) else { continue };
+            let Some(inode) = target
+                .to_str()
+                .and_then(|t| t.strip_prefix("socket:["))
+                .and_then(|t| t.strip_suffix(']'))
+                .and_then(|t| t.parse::<u64>().ok())
+            else {
+                continue;
+            };
+            if inodes.contains(&inode) && !owners.contains_key(&inode) {
+                let comm = std::fs::read_to_string(entry.path().join("comm")).unwrap_or_default().trim().to_string();
+                let cmdline = std::fs::read(entry.path().join("cmdline"))
+                    .map(|raw| raw.split(|b| *b == 0).filter(|a| !a.is_empty()).map(String::from_utf8_lossy).collect::<Vec<_>>().join(" "))
+                    .unwrap_or_default();
+                owners.insert(inode, ProcessRef { pid, comm, cmdline });
+            }
+        }
+        if owners.len() == inodes.len() {
+            break;
+        }
+    }
+    owners
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn owners_are_found_once_and_forgotten_with_their_socket() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let sockets = read_sockets();
+        let Some(inode) = sockets.iter().find(|s| s.is_listening() && s.local_port == port).map(|s| s.inode) else {
+            eprintln!("socket tables not readable here; skipping");
+            return;
+        };
+
+        let mut owners = SocketOwners::default();
+        owners.refresh(100, &sockets, &HashSet::from([inode]));
+        assert_eq!(owners.get(inode).map(|p| p.pid), Some(std::process::id()));
+        let found = listeners(&sockets, &owners);
+        let ours = found.iter().find(|l| l.inode == inode).unwrap();
+        assert_eq!(ours.process.as_ref().map(|p| p.pid), Some(std::process::id()));
+
+        drop(listener);
+        let remaining: Vec<Socket> = sockets.into_iter().filter(|s| s.inode != inode).collect();
+        owners.refresh(102, &remaining, &HashSet::new());
+        assert!(owners.get(inode).is_none());
+    }
+
+    #[test]
+    fn missing_owners_are_retried_only_after_a_while() {
+        let socket = Socket {
+            proto: "tcp",
+            local_ip: "10.0.0.1".parse().unwrap(),
+            local_port: 80,
+            remote_ip: "0.0.0.0".parse().unwrap(),
+            remote_port: 0,
+            state: TCP_LISTEN,
+            uid: 0,
+            // No process holds this inode.
+            inode: u64::MAX,
+        };
+        let wanted = HashSet::from([u64::MAX]);
+        let mut owners = SocketOwners::default();
+        owners.refresh(100, std::slice::from_ref(&socket), &wanted);
+        assert_eq!(owners.known[&u64::MAX].checked_at, 100);
+        owners.refresh(100 + MISSING_RETRY_SECS - 1, std::slice::from_ref(&socket), &wanted);
+        assert_eq!(owners.known[&u64::MAX].checked_at, 100);
+        owners.refresh(100 + MISSING_RETRY_SECS, std::slice::from_ref(&socket), &wanted);
+        assert_eq!(owners.known[&u64::MAX].checked_at, 100 + MISSING_RETRY_SECS);
+    }
+
+    fn socket(local: &str, remote: &str, state: u8) -> Socket {
+        let local: std::net::SocketAddr = local.parse().unwrap();
+        let remote: std::net::SocketAddr = remote.parse().unwrap();
+        Socket {
+            proto: "tcp",
+            local_ip: local.ip(),
+            local_port: local.port(),
+            remote_ip: remote.ip(),
+            remote_port: remote.port(),
+            state,
+            uid: 0,
+            inode: 1,
+        }
+    }
+
+    fn flow(src: &str, dst: &str) -> Connection {
+        let src: std::net::SocketAddr = src.parse().unwrap();
+        let dst: std::net::SocketAddr = dst.parse().unwrap();
+        let line = format!(
+            "ipv4 2 tcp 6 100 ESTABLISHED src={} dst={} sport={} dport={} src={} dst={} sport={} dport={} [ASSURED] mark=0 use=1",
+            src.ip(), dst.ip(), src.port(), dst.port(), dst.ip(), src.ip(), dst.port(), src.port()
+        );
+        crate::daemon::conntrack::parse_conntrack_line(&line).unwrap()
+    }
+
+    #[test]
+    fn wildcard_listeners_only_serve_local_addresses() {
+        let sockets = vec![
+            socket("0.0.0.0:9100", "0.0.0.0:0", TCP_LISTEN),
+            socket("[::]:22", "[::]:0", TCP_LISTEN),
+            socket("10.1.0.5:8080", "0.0.0.0:0", TCP_LISTEN),
+            socket("192.168.1.1:40000", "10.1.0.9:443", 0x01),
+        ];
+        let local = HashSet::from(["192.168.1.1".parse().unwrap(), "127.0.0.1".parse().unwrap()]);
+        let mut flows = vec![
+            // Forwarded pod traffic to a port a host process listens on everywhere.
+            flow("10.1.0.7:50000", "10.1.0.5:9100"),
+            flow("10.0.0.2:50000", "192.168.1.1:9100"),
+            // An IPv6 wildcard socket takes IPv4 too.
+            flow("10.0.0.2:50001", "192.168.1.1:22"),
+            // A listener bound to the exact address, local or not.
+            flow("10.1.0.7:50002", "10.1.0.5:8080"),
+            flow("192.168.1.1:40000", "10.1.0.9:443"),
+        ];
+        assign_roles(&mut flows, &sockets, &local);
+        let roles: Vec<Option<Role>> = flows.iter().map(|c| c.role).collect();
+        assert_eq!(roles, [None, Some(Role::Server), Some(Role::Server), Some(Role::Server), Some(Role::Client)]);
+    }
+}
@@ -0,0 +1,86 @@
+use std::collections::BTreeMap;
+use std::time::Instant;
+
+use serde::Serialize;
+
+use crate::daemon::{host_proc, unix_now};
+use crate::daemon::info::read_sysctl_u64;
+
+/// Conntrack table utilisation and the kernel's own conntrack counters,
+/// summed over CPUs, with per-second rates since the previous sample.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ConntrackStats {
+    pub sampled_at: u64,
+    pub count: Option<u64>,
+    pub max: Option<u64>,
+    pub fill_percent: Option<f64>,
+    pub counters: BTreeMap<String, u64>,
+    pub rates_per_sec: BTreeMap<String, f64>,
+}
+
+#[derive(Default)]
+pub struct StatsSampler {
+    prev: Option<(Instant, BTreeMap<String, u64>)>,
+}
+
+impl StatsSampler {
+    pub fn sample(&mut self) -> ConntrackStats {
+        let now = Instant::now();
+        let count = read_sysctl_u64("net/netfilter/nf_conntrack_count");
+        let max = read_sysctl_u64("net/netfilter/nf_conntrack_max");
+        let counters = read_stat_counters().unwrap_or_default();
+
+        let mut rates_per_sec = BTreeMap::new();
+        if let Some((then, ref prev)) = self.prev {
+            let elapsed = now.duration_since(then).as_secs_f64();
+            if elapsed > 0.0 {
+                for (name, value) in &counters {
+                    // "entries" is a gauge, not a counter.
+                    if name == "entries" { continue; }
+                    if let Some(before) = prev.get(name) {
+                        rates_per_sec.insert(name.clone(), value.saturating_sub(*before) as f64 / elapsed);
+                    }
+                }
+            }
+        }
+        self.prev = Some((now, counters.clone()));
+
+        let fill_percent = match (count, max) {
+            (Some(c), Some(m)) if m > 0 => Some(c as f64 * 100.0 / m as f64),
+            _ => None,
+        };
+
+        ConntrackStats {
+            sampled_at: unix_now(),
+            count,
+            max,
+            fill_percent,
+            counters,
+            rates_per_sec,
+        }
+    }
+}
+
+/// Parses `/proc/net/stat/nf_conntrack`: a header naming the columns, then
+/// one row of hex values per CPU. Counters are summed across CPUs; `entries`
+/// is global and repeated on every row, so it is taken once.
+fn read_stat_counters() -> Option<BTreeMap<String, u64>> {
+    let path = host_proc("net/stat/nf_conntrack")?;
+    let content = std::fs::read_to_string(path).ok()?;
+    let mut lines = content.lines();
+    let header: Vec<&str> = lines.next()?.split_whitespace().collect();
+
+    let mut totals: BTreeMap<String, u64> = BTreeMap::new();
+    for line in lines {
+        for (name, raw) in header.iter().zip(line.split_whitespace()) {
+            let Ok(v) = u64::from_str_radix(raw, 16) else { continue };
+            let entry = totals.entry(name.to_string()).or_insert(0);
+            if *name == "entries" {
+                *entry = v;
+            } else {
+                *entry += v;
+            }
+        }
+    }
+    Some(totals)
+}
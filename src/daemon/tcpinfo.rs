@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use serde::Serialize;
+
+use crate::daemon::conntrack::Connection;
+use crate::daemon::netlink::{message, NetlinkSocket, NLMSG_DONE, NLMSG_ERROR, NLM_F_REQUEST};
+
+// sock_diag (linux/sock_diag.h, linux/inet_diag.h).
+const NETLINK_SOCK_DIAG: libc::c_int = 4;
+const SOCK_DIAG_BY_FAMILY: u16 = 20;
+const NLM_F_DUMP: u16 = 0x300;
+const INET_DIAG_INFO: u16 = 2;
+const TCP_LISTEN: u32 = 10;
+const TCP_TIME_WAIT: u32 = 6;
+
+/// Kernel `tcp_info` for one local socket, the subset that explains slow
+/// connections.
+#[derive(Debug, Clone, Copy, Serialize, Hash, PartialEq, Eq)]
+pub struct TcpInfo {
+    /// Smoothed round-trip time, microseconds.
+    pub rtt_us: u32,
+    pub rttvar_us: u32,
+    /// Segments currently retransmitted and not yet acknowledged.
+    pub retrans: u32,
+    /// Retransmissions over the socket's lifetime.
+    pub total_retrans: u32,
+    /// Congestion window, in segments.
+    pub cwnd: u32,
+    /// Segments sent and not yet acknowledged.
+    pub unacked: u32,
+    /// Bytes/s; `None` when the kernel isn't pacing this socket.
+    pub pacing_rate: Option<u64>,
+}
+
+/// Local and remote endpoint of a socket.
+type SocketKey = (IpAddr, u16, IpAddr, u16);
+
+/// Whether to collect `tcp_info`, set by `KFLOW_TCP_INFO=1`. It only sees
+/// sockets in the daemon's network namespace (the host's, with hostNetwork).
+pub fn enabled() -> bool {
+    matches!(std::env::var("KFLOW_TCP_INFO").as_deref(), Ok("1") | Ok("true"))
+}
+
+/// Dumps `tcp_info` for every non-listening TCP socket.
+pub fn read_tcp_info() -> io::Result<HashMap<SocketKey, TcpInfo>> {
+    let mut out = HashMap::new();
+    for family in [libc::AF_INET as u8, libc::AF_INET6 as u8] {
+        dump_family(family, &mut out)?;
+    }
+    Ok(out)
+}
+
+fn dump_family(family: u8, out: &mut HashMap<SocketKey, TcpInfo>) -> io::Result<()> {
+    // inet_diag_req_v2: family, protocol, extensions, pad, states, then a
+    // zeroed inet_diag_sockid (48 bytes) to match every socket.
+    let states: u32 = 0xfff & !(1 << TCP_LISTEN) & !(1 << TCP_TIME_WAIT);
+    let mut req = vec![family, libc::IPPROTO_TCP as u8, 1 << (INET_DIAG_INFO - 1), 0];
+    req.extend_from_slice(&states.to_ne_bytes());
+    req.extend_from_slice(&[0u8; 48]);
+
+    let sock = NetlinkSocket::open(NETLINK_SOCK_DIAG)?;
+    sock.send(&message(SOCK_DIAG_BY_FAMILY, NLM_F_REQUEST | NLM_F_DUMP, &req))?;
+
+    loop {
+        let buf = sock.recv()?;
+        let mut off = 0;
+        while off + 16 <= buf.len() {
+            let len = u32::from_ne_bytes(buf[off..off + 4].try_into().unwrap()) as usize;
+            let ty = u16::from_ne_bytes([buf[off + 4], buf[off + 5]]);
+            if len < 16 || off + len > buf.len() {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "truncated sock_diag message"));
+            }
+            match ty {
+                NLMSG_DONE => return Ok(()),
+                NLMSG_ERROR => {
+                    let errno = i32::from_ne_bytes(buf[off + 16..off + 20].try_into().unwrap_or([0; 4]));
+                    return Err(io::Error::from_raw_os_error(-errno));
+                }
+                SOCK_DIAG_BY_FAMILY => {
+                    if let Some((key, info)) = parse_diag_msg(&buf[off + 16..off + len]) {
+                        out.insert(key, info);
+                    }
+                }
+                _ => {}
+            }
+            off += (len + 3) & !3;
+        }
+    }
+}
+
+/// Parses an `inet_diag_msg` and its `INET_DIAG_INFO` attribute.
+fn parse_diag_msg(msg: &[u8]) -> Option<(SocketKey, TcpInfo)> {
+    // family, state, timer, retrans, then inet_diag_sockid, then five u32s.
+    if msg.len() < 72 {
+        return None;
+    }
+    let family = msg[0];
+    let sport = u16::from_be_bytes([msg[4], msg[5]]);
+    let dport = u16::from_be_bytes([msg[6], msg[7]]);
+    let src = addr(family, &msg[8..24])?;
+    let dst = addr(family, &msg[24..40])?;
+
+    let mut off = 72;
+    while off + 4 <= msg.len() {
+        let len = u16::from_ne_bytes([msg[off], msg[off + 1]]) as usize;
+        let ty = u16::from_ne_bytes([msg[off + 2], msg[off + 3]]);
+        if len < 4 || off + len > msg.len() {
+            break;
+        }
+        if ty == INET_DIAG_INFO {
+            let info = parse_tcp_info(&msg[off + 4..off + len])?;
+            return Some(((src, sport, dst, dport), info));
+        }
+        off += (len + 3) & !3;
+    }
+    None
+}
+
+fn addr(family: u8, raw: &[u8]) -> Option<IpAddr> {
+    if family == libc::AF_INET as u8 {
+        return Some(IpAddr::V4(Ipv4Addr::new(raw[0], raw[1], raw[2], raw[3])));
+    }
+    let octets: [u8; 16] = raw.try_into().ok()?;
+    let v6 = Ipv6Addr::from(octets);
+    Some(v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(IpAddr::V6(v6)))
+}
+
+/// Reads fields of `struct tcp_info` by offset; older kernels send a shorter
+/// struct, so later fields are optional.
+fn parse_tcp_info(b: &[u8]) -> Option<TcpInfo> {
+    let u32_at = |off: usize| b.get(off..off + 4).map(|s| u32::from_ne_bytes(s.try_into().unwrap()));
+    let u64_at = |off: usize| b.get(off..off + 8).map(|s| u64::from_ne_bytes(s.try_into().unwrap()));
+    Some(TcpInfo {
+        unacked: u32_at(24)?,
+        retrans: u32_at(36)?,
+        rtt_us: u32_at(68)?,
+        rttvar_us: u32_at(72)?,
+        cwnd: u32_at(80)?,
+        total_retrans: u32_at(100).unwrap_or(0),
+        pacing_rate: u64_at(104).filter(|r| *r != u64::MAX),
+    })
+}
+
+/// Attaches `tcp_info` to connections whose 5-tuple matches a local socket,
+/// from either end: this node may be the flow's source or its destination.
+pub fn join_tcp_info(flows: &mut [Connection], info: &HashMap<SocketKey, TcpInfo>) {
+    for c in flows.iter_mut().filter(|c| c.proto == "tcp") {
+        c.tcp_info = info
+            .get(&(c.src_ip, c.src_port, c.dst_ip, c.dst_port))
+            .or_else(|| info.get(&(c.dst_ip, c.dst_port, c.src_ip, c.src_port)))
+            .copied();
+    }
+}
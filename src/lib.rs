@@ -1,2 +1,3 @@
 pub mod cli;
+pub mod daemon;
 // This is part of the lol hack to make docker go phrrrrrrmmmmmph
\ No newline at end of file